@@ -41,11 +41,12 @@ fn criterion_benchmark(c: &mut Criterion) {
 
     c.bench_function("pir::automorphism with T_COEFF_REGEV", |b| {
         let mut rng = ChaCha20Rng::from_entropy();
-        let scalar_key = SPIRALTest::encode_setup();
+        let scalar_key = SPIRALTest::encode_setup(&mut rng);
         let auto_key_regev = SPIRALTest::auto_setup::<
             { SPIRAL_TEST_PARAMS.T_PROJ_SHORT },
             { SPIRAL_TEST_PARAMS.Z_PROJ_SHORT },
-        >(3, &scalar_key);
+            _,
+        >(3, &scalar_key, &mut rng);
         let ct = Matrix::rand_uniform(&mut rng);
         b.iter(|| {
             SPIRALTest::auto_hom::<
@@ -57,11 +58,12 @@ fn criterion_benchmark(c: &mut Criterion) {
 
     c.bench_function("pir::automorphism with T_COEFF_GSW", |b| {
         let mut rng = ChaCha20Rng::from_entropy();
-        let scalar_key = SPIRALTest::encode_setup();
+        let scalar_key = SPIRALTest::encode_setup(&mut rng);
         let auto_key_regev = SPIRALTest::auto_setup::<
             { SPIRAL_TEST_PARAMS.T_PROJ_LONG },
             { SPIRAL_TEST_PARAMS.Z_PROJ_LONG },
-        >(3, &scalar_key);
+            _,
+        >(3, &scalar_key, &mut rng);
         let ct = Matrix::rand_uniform(&mut rng);
         b.iter(|| {
             SPIRALTest::auto_hom::<
@@ -75,11 +77,13 @@ fn criterion_benchmark(c: &mut Criterion) {
     for i in 0..4 {
         group.bench_with_input(BenchmarkId::from_parameter(i), &i, |b, &i| {
             let mut rng = ChaCha20Rng::from_entropy();
-            let scalar_key = SPIRALTest::encode_setup();
-            let auto_key_regev = SPIRALTest::auto_setup::<
-                { SPIRAL_TEST_PARAMS.T_PROJ_SHORT },
-                { SPIRAL_TEST_PARAMS.Z_PROJ_SHORT },
-            >(SPIRAL_TEST_PARAMS.D1 / (1 << i) + 1, &scalar_key);
+            let scalar_key = SPIRALTest::encode_setup(&mut rng);
+            let auto_key_regev =
+                SPIRALTest::auto_setup::<
+                    { SPIRAL_TEST_PARAMS.T_PROJ_SHORT },
+                    { SPIRAL_TEST_PARAMS.Z_PROJ_SHORT },
+                    _,
+                >(SPIRAL_TEST_PARAMS.D1 / (1 << i) + 1, &scalar_key, &mut rng);
             let mut cts = Vec::with_capacity(1 << i);
             for _ in 0..(1 << i) {
                 cts.push(Matrix::rand_uniform(&mut rng));
@@ -102,11 +106,13 @@ fn criterion_benchmark(c: &mut Criterion) {
     for i in 0..4 {
         group.bench_with_input(BenchmarkId::from_parameter(i), &i, |b, &i| {
             let mut rng = ChaCha20Rng::from_entropy();
-            let scalar_key = SPIRALTest::encode_setup();
-            let auto_key_regev = SPIRALTest::auto_setup::<
-                { SPIRAL_TEST_PARAMS.T_PROJ_LONG },
-                { SPIRAL_TEST_PARAMS.Z_PROJ_LONG },
-            >(SPIRAL_TEST_PARAMS.D1 / (1 << i) + 1, &scalar_key);
+            let scalar_key = SPIRALTest::encode_setup(&mut rng);
+            let auto_key_regev =
+                SPIRALTest::auto_setup::<
+                    { SPIRAL_TEST_PARAMS.T_PROJ_LONG },
+                    { SPIRAL_TEST_PARAMS.Z_PROJ_LONG },
+                    _,
+                >(SPIRAL_TEST_PARAMS.D1 / (1 << i) + 1, &scalar_key, &mut rng);
             let mut cts = Vec::with_capacity(1 << i);
             for _ in 0..(1 << i) {
                 cts.push(Matrix::rand_uniform(&mut rng));
@@ -133,15 +139,15 @@ fn criterion_benchmark(c: &mut Criterion) {
 
     c.bench_function("pir::regev to gsw", |b| {
         let mut rng = ChaCha20Rng::from_entropy();
-        let s = SPIRALTest::encode_setup();
-        let regev_to_gsw_key = SPIRALTest::rlwe_to_gsw_setup(&s);
+        let s = SPIRALTest::encode_setup(&mut rng);
+        let regev_to_gsw_key = SPIRALTest::rlwe_to_gsw_setup(&s, &mut rng);
 
         let msg: IntModCyclo<{ SPIRAL_TEST_PARAMS.D1 }, { SPIRAL_TEST_PARAMS.Q1 }> =
             IntModCyclo::rand_uniform(&mut rng);
         let mut msg_curr = msg.include_into();
         let mut encrypt_vec = Vec::with_capacity(SPIRAL_TEST_PARAMS.T_GSW);
         for _ in 0..SPIRAL_TEST_PARAMS.T_GSW {
-            encrypt_vec.push(SPIRALTest::encode_rlwe(&s, &msg_curr));
+            encrypt_vec.push(SPIRALTest::encode_rlwe(&s, &msg_curr, &mut rng));
             msg_curr *= IntMod::from(SPIRAL_TEST_PARAMS.Z_GSW);
         }
 
@@ -171,15 +177,10 @@ fn criterion_benchmark(c: &mut Criterion) {
         let mut rng = ChaCha20Rng::from_entropy();
         let (qk, pp) = SPIRALTest::setup(None);
         let idx = rng.gen_range(0..SPIRALTest::PACKED_DB_SIZE);
-        let q = SPIRALTest::query_one(&qk, idx, None);
+        let q = SPIRALTest::query_one(&qk, idx, None).unwrap();
         b.iter(|| {
             let time_stats: Option<&mut Stats<Duration>> = None;
-            SPIRALTest::answer_query_unpack(
-                black_box(&pp),
-                black_box(&q),
-                black_box(None),
-                black_box(time_stats),
-            )
+            SPIRALTest::answer_query_unpack(black_box(&pp), black_box(&q), black_box(time_stats))
         });
     });
 
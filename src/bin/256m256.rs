@@ -1,8 +1,8 @@
 use respire::pir::respire::RespireParamsExpanded;
 use respire::pir::respire_harness::FactoryParams;
-use respire::{generate_main, respire};
+use respire::{generate_respire_main, respire};
 
 const PARAMS: RespireParamsExpanded = FactoryParams::single_record_256(9, 9).expand().expand();
 
 type ThePIR = respire!(PARAMS);
-generate_main!(ThePIR);
+generate_respire_main!(ThePIR);
@@ -0,0 +1,9 @@
+use respire::generate_noise_check_main;
+use respire::pir::respire::RespireParamsExpanded;
+use respire::pir::respire_harness::{FactoryParams, RESPIRE_TEST_PARAMS};
+
+// Small database, so that many queries can be measured quickly
+const SMALL_PARAMS: RespireParamsExpanded =
+    FactoryParams::single_record_256(2, 2).expand().expand();
+
+generate_noise_check_main!(SMALL_PARAMS, RESPIRE_TEST_PARAMS);
@@ -0,0 +1,77 @@
+use clap::Parser;
+use respire::pir::param_search::{search_params, CostWeights, SearchGoal};
+
+/// Search for RESPIRE parameters for a database shape, and print them as TOML (the format read
+/// by `respire::pir::engine::load_params`).
+#[derive(Parser, Debug)]
+struct Args {
+    num_records: usize,
+    bytes_per_record: usize,
+    #[arg(long, default_value_t = 1)]
+    batch_size: usize,
+    /// Target error rate per record, as a base 2 logarithm
+    #[arg(long, default_value_t = -40.0, allow_negative_numbers = true)]
+    max_error_rate_log2: f64,
+    /// Number of cuckoo hash functions (with --batch-size > 1)
+    #[arg(long, default_value_t = 3)]
+    num_hash: usize,
+    /// Target probability that a batch cannot be placed in the cuckoo buckets, as a base 2
+    /// logarithm (with --batch-size > 1)
+    #[arg(long, default_value_t = -20.0, allow_negative_numbers = true)]
+    max_cuckoo_failure_rate_log2: f64,
+    #[arg(long, default_value_t = CostWeights::default().query)]
+    query_weight: f64,
+    #[arg(long, default_value_t = CostWeights::default().response)]
+    response_weight: f64,
+    #[arg(long, default_value_t = CostWeights::default().public_params)]
+    public_params_weight: f64,
+    #[arg(long, default_value_t = CostWeights::default().server_time)]
+    server_time_weight: f64,
+}
+
+fn main() {
+    env_logger::init();
+    let args = Args::parse();
+    let goal = SearchGoal {
+        num_records: args.num_records,
+        bytes_per_record: args.bytes_per_record,
+        batch_size: args.batch_size,
+        max_error_rate: args.max_error_rate_log2.exp2(),
+        num_hash: args.num_hash,
+        max_cuckoo_failure_rate: args.max_cuckoo_failure_rate_log2.exp2(),
+        weights: CostWeights {
+            query: args.query_weight,
+            response: args.response_weight,
+            public_params: args.public_params_weight,
+            server_time: args.server_time_weight,
+        },
+    };
+
+    let Some(result) = search_params(&goal) else {
+        eprintln!("No parameters found for {:?}", goal);
+        std::process::exit(1);
+    };
+
+    eprintln!(
+        "Error rate (estimated): 2^({:.3})",
+        result.error_rate.log2()
+    );
+    eprintln!("Noise margin (estimated): {:.3} bits", result.margin_bits);
+    eprintln!("Query size: {:.3} KiB", result.query_size as f64 / 1024_f64);
+    eprintln!(
+        "Response size: {:.3} KiB",
+        result.response_size as f64 / 1024_f64
+    );
+    eprintln!(
+        "Public param size: {:.3} KiB",
+        result.public_param_size as f64 / 1024_f64
+    );
+    eprintln!("Server ops (estimated): {:.3e}", result.server_ops);
+    if let Some(num_buckets) = result.num_buckets {
+        eprintln!(
+            "Use with CuckooRespireImpl<{}, {}, {}, _, {}>",
+            args.batch_size, num_buckets, args.num_records, args.num_hash
+        );
+    }
+    print!("{}", toml::to_string(&result.params.expand()).unwrap());
+}
@@ -0,0 +1,9 @@
+use respire::pir::respire::RespireParamsExpanded;
+use respire::pir::respire_harness::FactoryParams;
+use respire::{generate_client_main, respire};
+
+// Must match the parameters of `respire-server`
+const PARAMS: RespireParamsExpanded = FactoryParams::single_record_256(9, 9).expand().expand();
+
+type ThePIR = respire!(PARAMS);
+generate_client_main!(ThePIR);
@@ -0,0 +1,9 @@
+use respire::pir::respire::RespireParamsExpanded;
+use respire::pir::respire_harness::FactoryParams;
+use respire::{generate_server_main, respire};
+
+// Must match the parameters of `respire-client`
+const PARAMS: RespireParamsExpanded = FactoryParams::single_record_256(9, 9).expand().expand();
+
+type ThePIR = respire!(PARAMS);
+generate_server_main!(ThePIR);
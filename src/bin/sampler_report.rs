@@ -0,0 +1,72 @@
+use clap::Parser;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use respire::math::discrete_gaussian::{AliasSampler, CdtSampler};
+use respire::math::sampler_validation::{
+    validate_cyclo_discrete_gaussian, validate_sampler, validate_uniform_int_mod,
+    DistributionReport,
+};
+
+/// Run the statistical tests of `respire::math::sampler_validation` on the samplers, and print
+/// their reports.
+#[derive(Parser, Debug)]
+struct Args {
+    /// Discrete gaussian widths to test
+    #[arg(long, value_delimiter = ',', default_value = "6.4,253.6")]
+    widths: Vec<f64>,
+    #[arg(long, default_value_t = 1_000_000)]
+    samples: u64,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Significance level at which a test is reported as failing
+    #[arg(long, default_value_t = 1e-4)]
+    alpha: f64,
+}
+
+fn main() {
+    let args = Args::parse();
+    let mut rng = ChaCha20Rng::seed_from_u64(args.seed);
+    let mut failed = false;
+    let mut report = |name: String, report: DistributionReport| {
+        let pass = report.passes(args.alpha);
+        failed |= !pass;
+        println!("{} [{}]", name, if pass { "pass" } else { "FAIL" });
+        println!("{}", report);
+    };
+
+    for width in args.widths.iter() {
+        let width_millionths = (width * 1_000_000_f64).round() as u64;
+        report(
+            format!("CdtSampler, width {}", width),
+            validate_sampler::<CdtSampler, _>(width_millionths, args.samples, &mut rng),
+        );
+        report(
+            format!("AliasSampler, width {}", width),
+            validate_sampler::<AliasSampler, _>(width_millionths, args.samples, &mut rng),
+        );
+        report(
+            format!("IntModCyclo<2048, 268369921> coefficients, width {}", width),
+            validate_cyclo_discrete_gaussian::<2048, 268369921, CdtSampler, _>(
+                width_millionths,
+                args.samples.div_ceil(2048),
+                &mut rng,
+            ),
+        );
+    }
+    report(
+        "IntMod<17> uniform".to_string(),
+        validate_uniform_int_mod::<17, _>(args.samples, &mut rng),
+    );
+    report(
+        "IntMod<268369921> uniform".to_string(),
+        validate_uniform_int_mod::<268369921, _>(args.samples, &mut rng),
+    );
+    report(
+        "IntMod<0> uniform".to_string(),
+        validate_uniform_int_mod::<0, _>(args.samples, &mut rng),
+    );
+
+    if failed {
+        std::process::exit(1);
+    }
+}
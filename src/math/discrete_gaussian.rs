@@ -1,25 +1,47 @@
 //! Discrete gaussian distribution over the integers. Related to [RandDiscreteGaussianSampled].
+//!
+//! Two samplers are provided. [`CdtSampler`] runs in constant time and is used by default, as it
+//! produces secrets and encryption noise. [`AliasSampler`] is faster but its running time and
+//! memory accesses depend on the sampled value, so it should only be used for public randomness.
+//!
+//! Sampler tables are memoized per noise width. [`DiscreteGaussian::precompute`] builds the table
+//! ahead of time, and ring elements look their table up once rather than per coefficient.
+//!
+//! [RandDiscreteGaussianSampled]: crate::math::rand_sampled::RandDiscreteGaussianSampled
 
 use once_cell::sync::Lazy;
 use rand::prelude::Distribution;
 use rand::Rng;
 use rand_distr::WeightedAliasIndex;
+use std::any::{Any, TypeId};
 use std::collections::HashMap;
 use std::f64::consts::PI;
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 
 /// Number of std deviations (width) to sample from. A z-score of 8 has probability < 1.23 x 10^-15.
 pub const NUM_WIDTHS: usize = 8;
 
-/// A single table used for sampling a discrete gaussian of a particular width.
-struct DiscreteGaussianTable {
+/// A sampler for a discrete gaussian of a particular width, built from a precomputed table.
+pub trait DiscreteGaussianSampler: Send + Sync + Sized + 'static {
+    /// Builds the table for the given width.
+    fn init(noise_width: f64) -> Self;
+
+    /// Samples a value in `-max..=max`, where `max` is [`DiscreteGaussian::max_value`].
+    fn sample<T: Rng>(&self, rng: &mut T) -> i64;
+}
+
+///
+/// Samples with a `WeightedAliasIndex` over all values. This is not constant time: the alias
+/// method branches on the drawn bucket and indexes the table by the sampled value.
+///
+pub struct AliasSampler {
     choices: Vec<i64>,
     dist: WeightedAliasIndex<f64>,
 }
 
-impl DiscreteGaussianTable {
+impl DiscreteGaussianSampler for AliasSampler {
     fn init(noise_width: f64) -> Self {
-        let max_val = (noise_width * (NUM_WIDTHS as f64)).ceil() as i64;
+        let max_val = max_value(noise_width) as i64;
         let mut choices = Vec::new();
         let mut weights = vec![0f64; 0];
         for i in -max_val..max_val + 1 {
@@ -36,31 +58,159 @@ impl DiscreteGaussianTable {
     }
 }
 
-/// Memoization table for discrete gaussian sampling.
-static DISCRETE_GAUSSIAN_TABLES: Lazy<RwLock<HashMap<u64, DiscreteGaussianTable>>> =
+///
+/// Constant time cumulative distribution table (CDT) sampler. The table holds the tail
+/// probabilities `P(|x| > k)` scaled to 64 bits. A sample compares a uniform `u64` against every
+/// entry of the table without branching, so that the number of entries it is below is `|x|`, and
+/// then applies a uniform sign without branching.
+///
+/// Storing tails rather than cumulative probabilities keeps the small probabilities far from the
+/// mean accurate to the full precision of `f64`.
+///
+pub struct CdtSampler {
+    tails: Vec<u64>,
+}
+
+impl DiscreteGaussianSampler for CdtSampler {
+    fn init(noise_width: f64) -> Self {
+        let max_val = max_value(noise_width) as usize;
+        let rho = |i: usize| f64::exp(-PI * f64::powi(i as f64, 2) / f64::powi(noise_width, 2));
+        // Weight of |x| = k, summed from the smallest terms up
+        let mut tail_weights = vec![0f64; max_val + 1];
+        let mut acc = 0f64;
+        for k in (0..max_val).rev() {
+            acc += 2f64 * rho(k + 1);
+            tail_weights[k] = acc;
+        }
+        let total = rho(0) + acc;
+        let scale = f64::powi(2f64, 64) / total;
+        let tails = tail_weights[..max_val]
+            .iter()
+            .map(|w| (w * scale) as u64)
+            .collect();
+        Self { tails }
+    }
+
+    fn sample<T: Rng>(&self, rng: &mut T) -> i64 {
+        let r = rng.next_u64();
+        let mut abs = 0_i64;
+        for tail in self.tails.iter() {
+            abs += r.overflowing_sub(*tail).1 as i64;
+        }
+        // neg is all ones to negate, and zero otherwise
+        let neg = -((rng.next_u32() & 1) as i64);
+        (abs ^ neg) - neg
+    }
+}
+
+fn max_value(noise_width: f64) -> u64 {
+    (noise_width * (NUM_WIDTHS as f64)).ceil() as u64
+}
+
+///
+/// Reduces `x` into `0..n` without branching on its sign, assuming `|x| < n`. With `n = 0` this
+/// is the two's complement representation of `x`, matching `IntMod<0>`.
+///
+pub fn reduce_signed(x: i64, n: u64) -> u64 {
+    let mask = (x >> 63) as u64;
+    (x as u64).wrapping_add(n & mask)
+}
+
+type SamplerTables = HashMap<(TypeId, u64), Arc<dyn Any + Send + Sync>>;
+
+/// Memoization table for discrete gaussian sampling, keyed by sampler and noise width.
+static DISCRETE_GAUSSIAN_TABLES: Lazy<RwLock<SamplerTables>> =
     Lazy::new(|| RwLock::new(HashMap::new()));
 
 /// Discrete gaussian distributions
 pub struct DiscreteGaussian {}
 
 impl DiscreteGaussian {
-    /// Samples a discrete gaussian of the given width. This function memoized based on the noise
-    /// width, so the first call of a particular noise width will take longer than future calls.
-    pub fn sample<T: Rng, const NOISE_WIDTH_MILLIONTHS: u64>(rng: &mut T) -> i64 {
-        if let Some(table) = DISCRETE_GAUSSIAN_TABLES
-            .read()
-            .unwrap()
-            .get(&NOISE_WIDTH_MILLIONTHS)
-        {
-            return table.sample(rng);
+    /// The largest absolute value [`DiscreteGaussian::sample`] can return for the given width.
+    pub fn max_value(noise_width_millionths: u64) -> u64 {
+        max_value(noise_width_millionths as f64 / 1_000_000_f64)
+    }
+
+    /// Returns the memoized table of sampler `S` for the given width, building it on first use.
+    pub fn table<S: DiscreteGaussianSampler>(noise_width_millionths: u64) -> Arc<S> {
+        let key = (TypeId::of::<S>(), noise_width_millionths);
+        if let Some(table) = DISCRETE_GAUSSIAN_TABLES.read().unwrap().get(&key) {
+            return table.clone().downcast::<S>().unwrap();
         }
 
-        let table = DiscreteGaussianTable::init(NOISE_WIDTH_MILLIONTHS as f64 / 1_000_000_f64);
-        let ret = table.sample(rng);
+        let table = Arc::new(S::init(noise_width_millionths as f64 / 1_000_000_f64));
         DISCRETE_GAUSSIAN_TABLES
             .write()
             .unwrap()
-            .insert(NOISE_WIDTH_MILLIONTHS, table);
-        ret
+            .entry(key)
+            .or_insert(table)
+            .clone()
+            .downcast::<S>()
+            .unwrap()
+    }
+
+    /// Builds the table of sampler `S` for the given width ahead of its first sample.
+    pub fn precompute<S: DiscreteGaussianSampler>(noise_width_millionths: u64) {
+        Self::table::<S>(noise_width_millionths);
+    }
+
+    /// Samples a discrete gaussian of the given width with the constant time [`CdtSampler`]. This
+    /// looks up the memoized table on every call; use [`DiscreteGaussian::table`] to sample many
+    /// values.
+    pub fn sample<T: Rng, const NOISE_WIDTH_MILLIONTHS: u64>(rng: &mut T) -> i64 {
+        Self::table::<CdtSampler>(NOISE_WIDTH_MILLIONTHS).sample(rng)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn test_cdt_table() {
+        let table = CdtSampler::init(6.4);
+        assert_eq!(table.tails.len() as u64, max_value(6.4));
+        assert!(table.tails.windows(2).all(|w| w[0] >= w[1]));
+        // P(x = 0) = 1 / width for wide enough gaussians
+        let p_zero = 1f64 - table.tails[0] as f64 / f64::powi(2f64, 64);
+        assert!((p_zero - 1f64 / 6.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_samplers_agree() {
+        const WIDTH_MILLIONTHS: u64 = 6_400_000;
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let cdt = DiscreteGaussian::table::<CdtSampler>(WIDTH_MILLIONTHS);
+        let alias = DiscreteGaussian::table::<AliasSampler>(WIDTH_MILLIONTHS);
+        let max = DiscreteGaussian::max_value(WIDTH_MILLIONTHS) as i64;
+        let trials = 100_000;
+        let moments = |sample: &mut dyn FnMut() -> i64| {
+            let (mut sum, mut sum_sq) = (0f64, 0f64);
+            for _ in 0..trials {
+                let x = sample();
+                assert!(x.abs() <= max);
+                sum += x as f64;
+                sum_sq += (x * x) as f64;
+            }
+            (sum / trials as f64, sum_sq / trials as f64)
+        };
+        let (cdt_mean, cdt_var) = moments(&mut || cdt.sample(&mut rng));
+        let (alias_mean, alias_var) = moments(&mut || alias.sample(&mut rng));
+        // Variance of a (continuous) gaussian of width w is w^2 / (2 pi)
+        let var = 6.4 * 6.4 / (2f64 * PI);
+        for (mean, v) in [(cdt_mean, cdt_var), (alias_mean, alias_var)] {
+            assert!(mean.abs() < 0.05);
+            assert!((v / var - 1f64).abs() < 0.03);
+        }
+    }
+
+    #[test]
+    fn test_reduce_signed() {
+        assert_eq!(reduce_signed(5, 17), 5);
+        assert_eq!(reduce_signed(-5, 17), 12);
+        assert_eq!(reduce_signed(0, 17), 0);
+        assert_eq!(reduce_signed(-1, 0), u64::MAX);
     }
 }
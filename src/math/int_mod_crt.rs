@@ -1,6 +1,6 @@
 //! The ring `Z_n` of integers modulo `n = n_1 * n_2`, internally represented by its residues modulo `n_1` and `n_2`.
 
-use crate::math::discrete_gaussian::DiscreteGaussian;
+use crate::math::discrete_gaussian::{reduce_signed, DiscreteGaussianSampler};
 use crate::math::gadget::{IntModDecomposition, RingElementDecomposable};
 use crate::math::int_mod::IntMod;
 use crate::math::matrix::Matrix;
@@ -255,8 +255,15 @@ impl<const N1: u64, const N2: u64> RandZeroOneSampled for IntModCRT<N1, N2> {
 }
 
 impl<const N1: u64, const N2: u64> RandDiscreteGaussianSampled for IntModCRT<N1, N2> {
-    fn rand_discrete_gaussian<T: Rng, const NOISE_WIDTH_MILLIONTHS: u64>(rng: &mut T) -> Self {
-        DiscreteGaussian::sample::<_, NOISE_WIDTH_MILLIONTHS>(rng).into()
+    fn rand_discrete_gaussian_with<S: DiscreteGaussianSampler, T: Rng>(
+        sampler: &S,
+        rng: &mut T,
+    ) -> Self {
+        let x = sampler.sample(rng);
+        IntModCRT {
+            proj1: reduce_signed(x, N1).into(),
+            proj2: reduce_signed(x, N2).into(),
+        }
     }
 }
 
@@ -283,22 +290,22 @@ mod test {
         type Z55 = IntModCRT<5, 11>;
 
         let a: Z55 = 0_u64.into();
-        assert_eq!(0_u64, a.into());
+        assert_eq!(0_u64, u64::from(a));
 
         let a: Z55 = 1_u64.into();
-        assert_eq!(1_u64, a.into());
+        assert_eq!(1_u64, u64::from(a));
 
         let a: Z55 = 54_u64.into();
-        assert_eq!(54_u64, a.into());
+        assert_eq!(54_u64, u64::from(a));
 
         let a: Z55 = 55_u64.into();
-        assert_eq!(0_u64, a.into());
+        assert_eq!(0_u64, u64::from(a));
 
         let a: Z55 = 56_u64.into();
-        assert_eq!(1_u64, a.into());
+        assert_eq!(1_u64, u64::from(a));
 
         let a: Z55 = ((55 * 439885 + 16) as u64).into();
-        assert_eq!(16_u64, a.into());
+        assert_eq!(16_u64, u64::from(a));
     }
 
     #[test]
@@ -307,7 +314,7 @@ mod test {
 
         let a: Z55 = 21_u64.into();
         let b: Z55 = -a;
-        assert_eq!(34_u64, b.into());
+        assert_eq!(34_u64, u64::from(b));
 
         let a: Z55 = 0_u64.into();
         let b: Z55 = -a;
@@ -315,21 +322,21 @@ mod test {
 
         let mut a: Z55 = 23_u64.into();
         let b: Z55 = 45_u64.into();
-        assert_eq!(13_u64, (a + b).into());
+        assert_eq!(13_u64, u64::from(a + b));
         a += Z55::from(45_u64);
-        assert_eq!(13_u64, a.into());
+        assert_eq!(13_u64, u64::from(a));
 
         let mut a: Z55 = 23_u64.into();
         let b: Z55 = 45_u64.into();
-        assert_eq!(33_u64, (a - b).into());
+        assert_eq!(33_u64, u64::from(a - b));
         a -= Z55::from(45_u64);
-        assert_eq!(33_u64, a.into());
+        assert_eq!(33_u64, u64::from(a));
 
         let mut a: Z55 = 16_u64.into();
         let b: Z55 = 4_u64.into();
-        assert_eq!(9_u64, (a * b).into());
+        assert_eq!(9_u64, u64::from(a * b));
         a *= Z55::from(4_u64);
-        assert_eq!(9_u64, a.into());
+        assert_eq!(9_u64, u64::from(a));
     }
 
     #[test]
@@ -1,5 +1,6 @@
 //! The cyclotomic ring `Z_n[x]/x^d + 1)`. `d` is assumed to be a power of `2`.
 
+use crate::math::discrete_gaussian::DiscreteGaussianSampler;
 use crate::math::gadget::{IntModDecomposition, RingElementDecomposable};
 use crate::math::int_mod::{IntMod, NoReduce};
 use crate::math::int_mod_crt::IntModCRT;
@@ -18,6 +19,7 @@ use serde_with::serde_as;
 use std::cmp::max;
 use std::iter;
 use std::ops::{Add, AddAssign, Index, Mul, MulAssign, Neg, Sub, SubAssign};
+use zeroize::Zeroize;
 
 /// The raw (coefficient) representation of an element of a cyclotomic ring.
 ///
@@ -36,6 +38,12 @@ impl<const D: usize, const N: u64> IntModCyclo<D, N> {
     }
 }
 
+impl<const D: usize, const N: u64> Zeroize for IntModCyclo<D, N> {
+    fn zeroize(&mut self) {
+        self.coeff.zeroize();
+    }
+}
+
 /// Conversions
 
 impl<const D: usize, const N: u64> From<u64> for IntModCyclo<D, N> {
@@ -392,10 +400,13 @@ impl<const D: usize, const N: u64> RandZeroOneSampled for IntModCyclo<D, N> {
 }
 
 impl<const D: usize, const N: u64> RandDiscreteGaussianSampled for IntModCyclo<D, N> {
-    fn rand_discrete_gaussian<T: Rng, const NOISE_WIDTH_MILLIONTHS: u64>(rng: &mut T) -> Self {
+    fn rand_discrete_gaussian_with<S: DiscreteGaussianSampler, T: Rng>(
+        sampler: &S,
+        rng: &mut T,
+    ) -> Self {
         let mut result = Self::zero();
         for i in 0..D {
-            result.coeff[i] = IntMod::<N>::rand_discrete_gaussian::<_, NOISE_WIDTH_MILLIONTHS>(rng);
+            result.coeff[i] = IntMod::<N>::rand_discrete_gaussian_with(sampler, rng);
         }
         result
     }
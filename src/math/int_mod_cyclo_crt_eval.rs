@@ -11,6 +11,7 @@ use crate::math::ring_elem::*;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use zeroize::Zeroize;
 
 // TODO: documentation
 
@@ -21,6 +22,13 @@ pub struct IntModCycloCRTEval<const D: usize, const N1: u64, const N2: u64> {
     pub proj2: IntModCycloEval<D, N2>,
 }
 
+impl<const D: usize, const N1: u64, const N2: u64> Zeroize for IntModCycloCRTEval<D, N1, N2> {
+    fn zeroize(&mut self) {
+        self.proj1.zeroize();
+        self.proj2.zeroize();
+    }
+}
+
 /// Conversions
 
 impl<const D: usize, const N1: u64, const N2: u64> From<u64> for IntModCycloCRTEval<D, N1, N2> {
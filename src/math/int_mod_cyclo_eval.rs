@@ -1,5 +1,6 @@
 //! The cyclotomic ring `Z_n[x]/x^d + 1)`, represented as its DFT. `d` is assumed to be a power of `2`.
 
+use crate::math::discrete_gaussian::DiscreteGaussianSampler;
 use crate::math::gadget::{IntModDecomposition, RingElementDecomposable};
 use crate::math::int_mod::IntMod;
 use crate::math::int_mod_cyclo::IntModCyclo;
@@ -15,6 +16,7 @@ use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use zeroize::Zeroize;
 
 // TODO
 // We need a way to bind a root of the right order to the type.
@@ -43,6 +45,12 @@ impl<const D: usize, const N: u64> IntModCycloEval<D, N> {
     }
 }
 
+impl<const D: usize, const N: u64> Zeroize for IntModCycloEval<D, N> {
+    fn zeroize(&mut self) {
+        self.evals.zeroize();
+    }
+}
+
 /// Conversions
 
 impl<const D: usize, const N: u64> From<u64> for IntModCycloEval<D, N> {
@@ -230,8 +238,11 @@ impl<const D: usize, const N: u64> RandZeroOneSampled for IntModCycloEval<D, N>
 }
 
 impl<const D: usize, const N: u64> RandDiscreteGaussianSampled for IntModCycloEval<D, N> {
-    fn rand_discrete_gaussian<T: Rng, const NOISE_WIDTH_MILLIONTHS: u64>(rng: &mut T) -> Self {
-        IntModCyclo::rand_discrete_gaussian::<_, NOISE_WIDTH_MILLIONTHS>(rng).into()
+    fn rand_discrete_gaussian_with<S: DiscreteGaussianSampler, T: Rng>(
+        sampler: &S,
+        rng: &mut T,
+    ) -> Self {
+        IntModCyclo::rand_discrete_gaussian_with(sampler, rng).into()
     }
 }
 
@@ -1,5 +1,6 @@
 //! Matrices over generic rings.
 
+use crate::math::discrete_gaussian::DiscreteGaussianSampler;
 use crate::math::rand_sampled::*;
 use crate::math::ring_elem::*;
 use rand::Rng;
@@ -8,6 +9,7 @@ use serde::Serialize;
 use std::cmp::max;
 use std::mem::ManuallyDrop;
 use std::ops::{Add, AddAssign, Index, IndexMut, Mul, Neg, Sub, SubAssign};
+use zeroize::Zeroize;
 
 // TODO
 // * Implement as an array instead of as a `Vec`. The main sticking point is that to move a matrix
@@ -173,6 +175,16 @@ where
     }
 }
 
+impl<const N: usize, const M: usize, R: RingElement + Zeroize> Zeroize for Matrix<N, M, R>
+where
+    for<'a> &'a R: RingElementRef<R>,
+{
+    /// Zeroizes every entry, keeping the dimensions.
+    fn zeroize(&mut self) {
+        self.data.iter_mut().for_each(Zeroize::zeroize);
+    }
+}
+
 /// Conversions
 impl<const N: usize, const M: usize, R: RingElement> Matrix<N, M, R>
 where
@@ -424,11 +436,14 @@ where
     R: RandDiscreteGaussianSampled,
 {
     /// Element-wise random discrete gaussian sampling.
-    fn rand_discrete_gaussian<T: Rng, const NOISE_WIDTH_MILLIONTHS: u64>(rng: &mut T) -> Self {
+    fn rand_discrete_gaussian_with<S: DiscreteGaussianSampler, T: Rng>(
+        sampler: &S,
+        rng: &mut T,
+    ) -> Self {
         let mut result = Self::zero();
         for i in 0..N {
             for j in 0..M {
-                result[(i, j)] = R::rand_discrete_gaussian::<_, NOISE_WIDTH_MILLIONTHS>(rng);
+                result[(i, j)] = R::rand_discrete_gaussian_with(sampler, rng);
             }
         }
         result
@@ -13,6 +13,7 @@ pub mod ntt;
 pub mod number_theory;
 pub mod rand_sampled;
 pub mod ring_elem;
+pub mod sampler_validation;
 pub mod utils;
 
 pub mod simd_utils;
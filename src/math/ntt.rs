@@ -260,7 +260,7 @@ pub fn ntt_neg_backward<const D: usize, const N: u64>(values: &mut Aligned32<[In
 
                         let x_new = x + y;
                         let x_new = if x_new >= 2 * N { x_new - 2 * N } else { x_new };
-                        let sum = x - y + 2 * N;
+                        let sum = x + 2 * N - y;
                         let quotient = (w_table.ratio32 * sum) >> 32;
                         let y_new = (Wrapping(u64::from(w_table.value) as u32)
                             * Wrapping(sum as u32)
@@ -362,6 +362,20 @@ mod test {
         assert_eq!(values.0, expected);
     }
 
+    #[test]
+    fn test_ntt_neg_backward_butterfly_underflow() {
+        // The left input of the first butterflies is smaller than the right one, which must not
+        // underflow in the lazy reduction of the AVX2 path
+        let mut values: Aligned32<[IntMod<P>; 4]> =
+            Aligned32([0_u64.into(), (P - 1).into(), 0_u64.into(), (P - 1).into()]);
+        let expected = values.0;
+
+        ntt_neg_backward::<D, P>(&mut values);
+        ntt_neg_forward::<D, P>(&mut values);
+
+        assert_eq!(values.0, expected);
+    }
+
     #[test]
     fn test_ntt_neg_mul() {
         let mut values1: Aligned32<[IntMod<P>; 4]> =
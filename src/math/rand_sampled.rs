@@ -1,8 +1,8 @@
-//! Traits for randomly sampling from types (esp. rings).
+//! Traits for randomly sampling from types (esp. rings). Their distributions are checked by
+//! [`sampler_validation`](crate::math::sampler_validation).
+use crate::math::discrete_gaussian::{CdtSampler, DiscreteGaussian, DiscreteGaussianSampler};
 use rand::Rng;
 
-// TODO: test randomness (especially discrete gaussian)
-
 /// Used for sampling elements uniformly.
 pub trait RandUniformSampled {
     /// Sample an element uniformly.
@@ -16,7 +16,19 @@ pub trait RandZeroOneSampled {
 }
 
 /// Used for sampling elements with the discrete gaussian distribution.
-pub trait RandDiscreteGaussianSampled {
-    /// Sample an element with the discrete gaussian distribution.
-    fn rand_discrete_gaussian<T: Rng, const NOISE_WIDTH_MILLIONTHS: u64>(rng: &mut T) -> Self;
+pub trait RandDiscreteGaussianSampled: Sized {
+    /// Sample an element with the discrete gaussian distribution, using the constant time
+    /// [`CdtSampler`].
+    fn rand_discrete_gaussian<T: Rng, const NOISE_WIDTH_MILLIONTHS: u64>(rng: &mut T) -> Self {
+        Self::rand_discrete_gaussian_with(
+            &*DiscreteGaussian::table::<CdtSampler>(NOISE_WIDTH_MILLIONTHS),
+            rng,
+        )
+    }
+
+    /// Sample an element with the discrete gaussian distribution of the given sampler table.
+    fn rand_discrete_gaussian_with<S: DiscreteGaussianSampler, T: Rng>(
+        sampler: &S,
+        rng: &mut T,
+    ) -> Self;
 }
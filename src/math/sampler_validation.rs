@@ -0,0 +1,450 @@
+//! Statistical validation of the samplers in [rand_sampled], against their ideal distributions.
+//!
+//! Samples are binned over the support of the ideal distribution and compared with a chi-square
+//! test and a Kolmogorov-Smirnov (KS) test. The report also carries the measured statistical
+//! distance, together with the distance expected from sampling alone, so that a biased sampler
+//! stands out even when the support is too wide for the tests to have much power.
+//!
+//! The discrete gaussian is compared against the untruncated distribution, so the mass cut off
+//! beyond [`NUM_WIDTHS`] widths shows up in the statistical distance and in
+//! [`DistributionReport::tail_mass`].
+//!
+//! [rand_sampled]: crate::math::rand_sampled
+//! [`NUM_WIDTHS`]: crate::math::discrete_gaussian::NUM_WIDTHS
+
+use crate::math::discrete_gaussian::{DiscreteGaussian, DiscreteGaussianSampler};
+use crate::math::int_mod::IntMod;
+use crate::math::int_mod_cyclo::IntModCyclo;
+use crate::math::rand_sampled::{RandDiscreteGaussianSampled, RandUniformSampled};
+use rand::Rng;
+use std::f64::consts::PI;
+use std::fmt;
+
+/// Smallest expected count of a chi-square bin. Sparser bins are merged with their neighbours.
+const MIN_EXPECTED_PER_BIN: f64 = 5_f64;
+
+/// Largest number of bins used for uniform distributions over large moduli.
+pub const MAX_UNIFORM_BINS: u64 = 1024;
+
+/// Outcome of comparing samples with an ideal distribution.
+#[derive(Clone, Debug)]
+pub struct DistributionReport {
+    pub samples: u64,
+    /// Samples that fell outside the support of the sampler.
+    pub out_of_support: u64,
+    pub chi_square: f64,
+    pub degrees_of_freedom: usize,
+    /// Probability of a chi-square statistic at least this large under the ideal distribution.
+    pub chi_square_p: f64,
+    pub ks_statistic: f64,
+    /// Probability of a KS statistic at least this large under the ideal distribution. This is
+    /// conservative (too large) for discrete distributions.
+    pub ks_p: f64,
+    /// Statistical distance between the empirical and the ideal distribution.
+    pub statistical_distance: f64,
+    /// Statistical distance expected from sampling alone, for a sampler that is exactly ideal.
+    pub sampling_distance: f64,
+    /// Ideal mass outside the support of the sampler.
+    pub tail_mass: f64,
+}
+
+impl DistributionReport {
+    /// Whether neither test rejects the ideal distribution at significance level `alpha`, and no
+    /// sample fell outside the support.
+    pub fn passes(&self, alpha: f64) -> bool {
+        self.out_of_support == 0 && self.chi_square_p >= alpha && self.ks_p >= alpha
+    }
+}
+
+impl fmt::Display for DistributionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "  samples: {} ({} out of support)",
+            self.samples, self.out_of_support
+        )?;
+        writeln!(
+            f,
+            "  chi-square: {:.2} with {} dof, p = {:.4}",
+            self.chi_square, self.degrees_of_freedom, self.chi_square_p
+        )?;
+        writeln!(
+            f,
+            "  KS: D = {:.3e}, p = {:.4}",
+            self.ks_statistic, self.ks_p
+        )?;
+        writeln!(
+            f,
+            "  statistical distance: {:.3e} (sampling alone: {:.3e})",
+            self.statistical_distance, self.sampling_distance
+        )?;
+        write!(f, "  tail mass: 2^({:.1})", self.tail_mass.log2())
+    }
+}
+
+/// An ideal distribution over the consecutive categories `0..probs.len()`.
+struct Ideal {
+    probs: Vec<f64>,
+    tail_mass: f64,
+}
+
+impl Ideal {
+    ///
+    /// The discrete gaussian of the given width, over `-max..=max` where `max` is
+    /// [`DiscreteGaussian::max_value`]. Category `i` is the value `i - max`.
+    ///
+    fn discrete_gaussian(noise_width_millionths: u64) -> Self {
+        let noise_width = noise_width_millionths as f64 / 1_000_000_f64;
+        let max = DiscreteGaussian::max_value(noise_width_millionths) as i64;
+        let rho = |i: i64| f64::exp(-PI * f64::powi(i as f64, 2) / f64::powi(noise_width, 2));
+        // Terms beyond twice the support are below 2^-1000 of the total
+        let tail: f64 = (max + 1..=2 * max + 1).rev().map(|i| 2_f64 * rho(i)).sum();
+        let inside: Vec<f64> = (-max..=max).map(rho).collect();
+        let total = tail + inside.iter().sum::<f64>();
+        Self {
+            probs: inside.into_iter().map(|p| p / total).collect(),
+            tail_mass: tail / total,
+        }
+    }
+
+    /// The uniform distribution over `0..modulus` (`2^64` for `modulus = 0`), in `bins` buckets.
+    fn uniform(modulus: u64, bins: u64) -> Self {
+        let n = if modulus == 0 {
+            1_u128 << 64
+        } else {
+            modulus as u128
+        };
+        // Bucket `b` holds the values `v` with `floor(v * bins / n) = b`
+        let first = |b: u128| (b * n).div_ceil(bins as u128);
+        let probs = (0..bins as u128)
+            .map(|b| (first(b + 1) - first(b)) as f64 / n as f64)
+            .collect();
+        Self {
+            probs,
+            tail_mass: 0_f64,
+        }
+    }
+
+    fn compare(&self, counts: &[u64], out_of_support: u64) -> DistributionReport {
+        assert_eq!(counts.len(), self.probs.len());
+        let samples = counts.iter().sum::<u64>() + out_of_support;
+        let n = samples as f64;
+
+        let mut chi_square = 0_f64;
+        let mut bins = 0_usize;
+        let (mut expected, mut observed) = (0_f64, 0_f64);
+        for (p, &c) in self.probs.iter().zip(counts) {
+            expected += p * n;
+            observed += c as f64;
+            if expected >= MIN_EXPECTED_PER_BIN {
+                chi_square += f64::powi(observed - expected, 2) / expected;
+                bins += 1;
+                (expected, observed) = (0_f64, 0_f64);
+            }
+        }
+        // The out of support samples and the leftover sparse bins form a final bin
+        expected += self.tail_mass * n;
+        observed += out_of_support as f64;
+        if expected > 0_f64 {
+            chi_square += f64::powi(observed - expected, 2) / expected;
+            bins += 1;
+        } else if observed > 0_f64 {
+            chi_square = f64::INFINITY;
+        }
+        let degrees_of_freedom = bins.saturating_sub(1).max(1);
+
+        let mut ks_statistic = 0_f64;
+        let (mut cdf, mut empirical_cdf) = (0_f64, 0_f64);
+        for (p, &c) in self.probs.iter().zip(counts) {
+            cdf += p;
+            empirical_cdf += c as f64 / n;
+            ks_statistic = ks_statistic.max((empirical_cdf - cdf).abs());
+        }
+
+        let statistical_distance = 0.5_f64
+            * (self
+                .probs
+                .iter()
+                .zip(counts)
+                .map(|(p, &c)| (c as f64 / n - p).abs())
+                .sum::<f64>()
+                + (out_of_support as f64 / n - self.tail_mass).abs());
+        // E|c/n - p| for a binomial count is about sqrt(2p(1 - p) / (pi n))
+        let sampling_distance = 0.5_f64
+            * self
+                .probs
+                .iter()
+                .map(|p| f64::sqrt(2_f64 * p * (1_f64 - p) / (PI * n)))
+                .sum::<f64>();
+
+        DistributionReport {
+            samples,
+            out_of_support,
+            chi_square,
+            degrees_of_freedom,
+            chi_square_p: chi_square_survival(chi_square, degrees_of_freedom),
+            ks_statistic,
+            ks_p: ks_survival(ks_statistic, n),
+            statistical_distance,
+            sampling_distance,
+            tail_mass: self.tail_mass,
+        }
+    }
+}
+
+///
+/// Compares values with the discrete gaussian of the given width. Values beyond
+/// [`DiscreteGaussian::max_value`] count as out of support.
+///
+pub fn check_discrete_gaussian(
+    noise_width_millionths: u64,
+    values: impl IntoIterator<Item = i64>,
+) -> DistributionReport {
+    let ideal = Ideal::discrete_gaussian(noise_width_millionths);
+    let max = DiscreteGaussian::max_value(noise_width_millionths) as i64;
+    let mut counts = vec![0_u64; ideal.probs.len()];
+    let mut out_of_support = 0;
+    for x in values {
+        if x.abs() <= max {
+            counts[(x + max) as usize] += 1;
+        } else {
+            out_of_support += 1;
+        }
+    }
+    ideal.compare(&counts, out_of_support)
+}
+
+///
+/// Compares values with the uniform distribution over `0..modulus` (`2^64` for `modulus = 0`).
+/// Large moduli are bucketed into [`MAX_UNIFORM_BINS`] bins.
+///
+pub fn check_uniform(modulus: u64, values: impl IntoIterator<Item = u64>) -> DistributionReport {
+    let bins = if modulus == 0 {
+        MAX_UNIFORM_BINS
+    } else {
+        modulus.min(MAX_UNIFORM_BINS)
+    };
+    let ideal = Ideal::uniform(modulus, bins);
+    let n = if modulus == 0 {
+        1_u128 << 64
+    } else {
+        modulus as u128
+    };
+    let mut counts = vec![0_u64; bins as usize];
+    let mut out_of_support = 0;
+    for v in values {
+        if (v as u128) < n {
+            counts[(v as u128 * bins as u128 / n) as usize] += 1;
+        } else {
+            out_of_support += 1;
+        }
+    }
+    ideal.compare(&counts, out_of_support)
+}
+
+/// Validates `samples` draws of the discrete gaussian sampler `S` of the given width.
+pub fn validate_sampler<S: DiscreteGaussianSampler, T: Rng>(
+    noise_width_millionths: u64,
+    samples: u64,
+    rng: &mut T,
+) -> DistributionReport {
+    let sampler = DiscreteGaussian::table::<S>(noise_width_millionths);
+    check_discrete_gaussian(
+        noise_width_millionths,
+        (0..samples).map(|_| sampler.sample(rng)),
+    )
+}
+
+/// Validates `samples` draws of [`RandUniformSampled`] for `IntMod<N>`.
+pub fn validate_uniform_int_mod<const N: u64, T: Rng>(
+    samples: u64,
+    rng: &mut T,
+) -> DistributionReport {
+    check_uniform(
+        N,
+        (0..samples).map(|_| u64::from(IntMod::<N>::rand_uniform(rng))),
+    )
+}
+
+///
+/// Validates the coefficients of `polys` draws of [`RandDiscreteGaussianSampled`] for
+/// `IntModCyclo<D, N>` with the sampler `S`, so that the reduction modulo `N` is covered too.
+///
+pub fn validate_cyclo_discrete_gaussian<
+    const D: usize,
+    const N: u64,
+    S: DiscreteGaussianSampler,
+    T: Rng,
+>(
+    noise_width_millionths: u64,
+    polys: u64,
+    rng: &mut T,
+) -> DistributionReport {
+    let sampler = DiscreteGaussian::table::<S>(noise_width_millionths);
+    check_discrete_gaussian(
+        noise_width_millionths,
+        (0..polys).flat_map(|_| {
+            IntModCyclo::<D, N>::rand_discrete_gaussian_with(&*sampler, rng)
+                .coeff
+                .map(i64::from)
+        }),
+    )
+}
+
+///
+/// Upper tail of the chi-square distribution, using the Wilson-Hilferty cube root
+/// approximation. This is accurate to a few digits for the degrees of freedom seen here.
+///
+fn chi_square_survival(x: f64, degrees_of_freedom: usize) -> f64 {
+    if x.is_infinite() {
+        return 0_f64;
+    }
+    let k = degrees_of_freedom as f64;
+    let s = 2_f64 / (9_f64 * k);
+    let z = (f64::cbrt(x / k) - (1_f64 - s)) / s.sqrt();
+    0.5_f64 * erfc(z / std::f64::consts::SQRT_2)
+}
+
+/// Upper tail of the Kolmogorov distribution, with Stephens' correction for `n` samples.
+fn ks_survival(d: f64, n: f64) -> f64 {
+    let sqrt_n = n.sqrt();
+    let lambda = (sqrt_n + 0.12_f64 + 0.11_f64 / sqrt_n) * d;
+    if lambda < 0.2_f64 {
+        return 1_f64;
+    }
+    let mut sum = 0_f64;
+    for j in 1..=100 {
+        let term = f64::exp(-2_f64 * f64::powi(j as f64 * lambda, 2));
+        sum += if j % 2 == 1 { term } else { -term };
+        if term < 1e-16 {
+            break;
+        }
+    }
+    (2_f64 * sum).clamp(0_f64, 1_f64)
+}
+
+/// Complementary error function, with relative error below `1.2 x 10^-7`.
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1_f64 / (1_f64 + 0.5_f64 * z);
+    let poly = [
+        -1.26551223,
+        1.00002368,
+        0.37409196,
+        0.09678418,
+        -0.18628806,
+        0.27886807,
+        -1.13520398,
+        1.48851587,
+        -0.82215223,
+        0.17087277,
+    ]
+    .iter()
+    .rev()
+    .fold(0_f64, |acc, c| acc * t + c);
+    let ans = t * f64::exp(-z * z + poly);
+    if x >= 0_f64 {
+        ans
+    } else {
+        2_f64 - ans
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::discrete_gaussian::{AliasSampler, CdtSampler};
+    use crate::math::int_mod_cyclo_eval::IntModCycloEval;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    const ALPHA: f64 = 1e-4;
+
+    #[test]
+    fn test_statistics() {
+        assert!((erfc(0_f64) - 1_f64).abs() < 1e-7);
+        assert!((erfc(1_f64) - 0.157299207).abs() < 1e-7);
+        assert!((erfc(-1_f64) - 1.842700793).abs() < 1e-7);
+        // 95th percentiles
+        assert!((chi_square_survival(18.307, 10) - 0.05).abs() < 1e-3);
+        assert!((chi_square_survival(124.342, 100) - 0.05).abs() < 1e-3);
+        assert!((ks_survival(1.358 / 1e4, 1e8) - 0.05).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_discrete_gaussian_samplers() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        for width_millionths in [1_000_000, 6_400_000, 253_600_000] {
+            let samples = if width_millionths > 100_000_000 {
+                200_000
+            } else {
+                1_000_000
+            };
+            for report in [
+                validate_sampler::<CdtSampler, _>(width_millionths, samples, &mut rng),
+                validate_sampler::<AliasSampler, _>(width_millionths, samples, &mut rng),
+            ] {
+                assert!(report.passes(ALPHA), "width {width_millionths}:\n{report}");
+                assert!(report.statistical_distance < 1.1 * report.sampling_distance);
+                assert!(report.tail_mass < f64::powi(2_f64, -100));
+            }
+        }
+    }
+
+    #[test]
+    fn test_detects_bias() {
+        const WIDTH_MILLIONTHS: u64 = 6_400_000;
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let sampler = DiscreteGaussian::table::<CdtSampler>(WIDTH_MILLIONTHS);
+        // Too narrow by 2%
+        let narrow = (0..1_000_000).map(|_| {
+            let x = sampler.sample(&mut rng);
+            if x.abs() > 6 {
+                x - x.signum()
+            } else {
+                x
+            }
+        });
+        assert!(!check_discrete_gaussian(WIDTH_MILLIONTHS, narrow).passes(ALPHA));
+        // Missing its tails
+        let cut = (0..1_000_000).map(|_| sampler.sample(&mut rng).clamp(-10, 10));
+        assert!(!check_discrete_gaussian(WIDTH_MILLIONTHS, cut).passes(ALPHA));
+        let outside = [DiscreteGaussian::max_value(WIDTH_MILLIONTHS) as i64 + 1];
+        assert_eq!(
+            check_discrete_gaussian(WIDTH_MILLIONTHS, outside).out_of_support,
+            1
+        );
+    }
+
+    #[test]
+    fn test_uniform() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        assert!(validate_uniform_int_mod::<17, _>(100_000, &mut rng).passes(ALPHA));
+        assert!(validate_uniform_int_mod::<268369921, _>(1_000_000, &mut rng).passes(ALPHA));
+        assert!(validate_uniform_int_mod::<0, _>(1_000_000, &mut rng).passes(ALPHA));
+        // Biased towards small values, as from reducing a too small range
+        let biased = (0..1_000_000).map(|_| rng.gen_range(0..3 * 268369921_u64 / 2) % 268369921);
+        assert!(!check_uniform(268369921, biased).passes(ALPHA));
+    }
+
+    #[test]
+    fn test_ring_discrete_gaussian() {
+        const WIDTH_MILLIONTHS: u64 = 6_400_000;
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let report = validate_cyclo_discrete_gaussian::<2048, 268369921, CdtSampler, _>(
+            WIDTH_MILLIONTHS,
+            256,
+            &mut rng,
+        );
+        assert!(report.passes(ALPHA), "{report}");
+
+        let coeffs = (0..256).flat_map(|_| {
+            let eval = IntModCycloEval::<2048, 268369921>::rand_discrete_gaussian::<
+                _,
+                WIDTH_MILLIONTHS,
+            >(&mut rng);
+            IntModCyclo::from(&eval).coeff.map(i64::from)
+        });
+        assert!(check_discrete_gaussian(WIDTH_MILLIONTHS, coeffs).passes(ALPHA));
+    }
+}
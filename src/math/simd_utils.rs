@@ -4,7 +4,7 @@
 use std::arch::x86_64::*;
 
 #[repr(C, align(32))]
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
 ///
 /// Wrapper type that has the same repr as `T`, except with a 32-byte alignment.
 ///
@@ -24,6 +24,44 @@ pub const SIMD_LANES: usize = 1;
 #[cfg(not(target_feature = "avx2"))]
 pub type SimdVec = u64;
 
+///
+/// Lane `lane` of `v`, for `lane < SIMD_LANES`.
+///
+#[cfg(target_feature = "avx2")]
+#[inline(always)]
+pub fn simd_lane(v: &SimdVec, lane: usize) -> u64 {
+    v.0[lane]
+}
+
+///
+/// Mutable reference to lane `lane` of `v`, for `lane < SIMD_LANES`.
+///
+#[cfg(target_feature = "avx2")]
+#[inline(always)]
+pub fn simd_lane_mut(v: &mut SimdVec, lane: usize) -> &mut u64 {
+    &mut v.0[lane]
+}
+
+///
+/// Lane `lane` of `v`, for `lane < SIMD_LANES`.
+///
+#[cfg(not(target_feature = "avx2"))]
+#[inline(always)]
+pub fn simd_lane(v: &SimdVec, lane: usize) -> u64 {
+    debug_assert_eq!(lane, 0);
+    *v
+}
+
+///
+/// Mutable reference to lane `lane` of `v`, for `lane < SIMD_LANES`.
+///
+#[cfg(not(target_feature = "avx2"))]
+#[inline(always)]
+pub fn simd_lane_mut(v: &mut SimdVec, lane: usize) -> &mut u64 {
+    debug_assert_eq!(lane, 0);
+    v
+}
+
 ///
 /// Executes `s += a * b` on all four lanes. `a` and `b` are 32 bit; `s` is 64 bit.
 ///
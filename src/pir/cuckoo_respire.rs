@@ -1,24 +1,90 @@
-use crate::pir::pir::{Stats, PIR};
-use crate::pir::respire::Respire;
+use crate::math::simd_utils::SIMD_LANES;
+use crate::pir::database::{map_db_file, write_db_file, SimdDatabase};
+use crate::pir::parallel;
+use crate::pir::pir::{PirError, Stats, PIR};
+use crate::pir::respire::{fingerprint_extend, Respire};
 use itertools::Itertools;
 use log::{info, warn};
-use rand::{thread_rng, Rng};
-use std::collections::hash_map::DefaultHasher;
+use rand::{thread_rng, CryptoRng, Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use siphasher::sip::SipHasher13;
 use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
+use std::hash::Hasher;
+use std::io;
 use std::marker::PhantomData;
+use std::ops::Range;
+use std::path::Path;
 use std::time::{Duration, Instant};
 
+///
+/// Key of the bucket hash functions.
+///
+pub type CuckooSeed = [u8; 16];
+
+///
+/// What a client needs to locate records. Each bucket holds the records hashing to it in order of
+/// record index, so the layout is determined by the seed of the bucket hash functions alone. The
+/// server picks the seed when encoding, builds the hint along with the database, and publishes it.
+///
+/// To avoid rehashing every record on each lookup, the hint also keeps the size of each bucket at
+/// checkpoints every `2^checkpoint_log2` records. A lookup rehashes at most that many records, and
+/// the hint holds `NUM_BUCKET` counts per checkpoint; by default the checkpoints are spaced so that
+/// both are about `sqrt(NUM_BUCKET * NUM_RECORDS)` (see
+/// [`CuckooRespireImpl::DEFAULT_CHECKPOINT_LOG2`]).
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CuckooDatabaseHint {
+    pub seed: CuckooSeed,
+    /// Number of records between consecutive checkpoints, as a base 2 logarithm
+    checkpoint_log2: u32,
+    /// `bucket_counts[k * NUM_BUCKET + b]` is the number of slots of bucket `b` taken by records
+    /// `0..k << checkpoint_log2`
+    bucket_counts: Vec<u32>,
+}
+
+///
+/// Number of times a batch is cuckoo hashed, each time with fresh eviction randomness, before the
+/// query fails with [`PirError::CuckooInsertionFailed`].
+///
+pub const CUCKOO_ATTEMPTS: usize = 4;
+
+/// Number of evictions after which a cuckoo hashing attempt gives up.
+pub const CUCKOO_MAX_DEPTH: usize = 1 << 16;
+
+///
+/// Client state between `query` and `extract`.
+///
+#[derive(Clone, Debug)]
+pub struct CuckooQueryState {
+    /// (query index, distinct record index) pairs. Query `r * NUM_BUCKET + b` is the query to
+    /// bucket `b` in round `r`, where round 0 is the cuckoo round and the others serve the stash.
+    cuckoo_mapping: Vec<(usize, usize)>,
+    /// For each requested record, its index among the distinct records of the batch
+    distinct_idxs: Vec<usize>,
+}
+
 pub trait CuckooRespire: PIR {
     type BaseRespire: PIR + Respire;
     const NUM_BUCKET: usize;
 }
 
+///
+/// Batch PIR by cuckoo hashing: each record is stored in the `NUM_HASH` buckets it hashes to, and
+/// each bucket is a `BaseRespire` database.
+///
+/// A batch is normally retrieved with one query per bucket. With a nonzero `STASH_SIZE`, up to that
+/// many records that cuckoo hashing cannot place are kept in a stash, and every query carries
+/// `STASH_SIZE` extra rounds of one query per bucket (each covering the whole database) to retrieve
+/// them. The extra rounds are always sent, so the server cannot tell whether the stash was used.
+///
 pub struct CuckooRespireImpl<
     const BATCH_SIZE: usize,
     const NUM_BUCKET: usize,
     const NUM_RECORDS: usize,
     BaseRespire: PIR + Respire,
+    const NUM_HASH: usize = 3,
+    const STASH_SIZE: usize = 0,
 > {
     phantom: PhantomData<BaseRespire>,
 }
@@ -28,7 +94,10 @@ impl<
         const NUM_BUCKET: usize,
         const NUM_RECORDS: usize,
         BaseRespire: PIR + Respire,
-    > CuckooRespire for CuckooRespireImpl<BATCH_SIZE, NUM_BUCKET, NUM_RECORDS, BaseRespire>
+        const NUM_HASH: usize,
+        const STASH_SIZE: usize,
+    > CuckooRespire
+    for CuckooRespireImpl<BATCH_SIZE, NUM_BUCKET, NUM_RECORDS, BaseRespire, NUM_HASH, STASH_SIZE>
 {
     type BaseRespire = BaseRespire;
     const NUM_BUCKET: usize = NUM_BUCKET;
@@ -39,15 +108,18 @@ impl<
         const NUM_BUCKET: usize,
         const NUM_RECORDS: usize,
         BaseRespire: PIR + Respire,
-    > PIR for CuckooRespireImpl<BATCH_SIZE, NUM_BUCKET, NUM_RECORDS, BaseRespire>
+        const NUM_HASH: usize,
+        const STASH_SIZE: usize,
+    > PIR
+    for CuckooRespireImpl<BATCH_SIZE, NUM_BUCKET, NUM_RECORDS, BaseRespire, NUM_HASH, STASH_SIZE>
 {
     type QueryKey = BaseRespire::QueryKey;
     type PublicParams = BaseRespire::PublicParams;
     type Query = Vec<BaseRespire::QueryOne>;
     type Response = Vec<BaseRespire::AnswerOneCompressed>;
     type Database = Vec<<BaseRespire as PIR>::Database>;
-    type DatabaseHint = Vec<Vec<Option<usize>>>;
-    type State = Vec<(usize, usize)>;
+    type DatabaseHint = CuckooDatabaseHint;
+    type State = CuckooQueryState;
     type RecordBytes = BaseRespire::RecordBytes;
     const BYTES_PER_RECORD: usize = BaseRespire::BYTES_PER_RECORD;
     const NUM_RECORDS: usize = NUM_RECORDS;
@@ -62,10 +134,12 @@ impl<
             (BaseRespire::BYTES_PER_RECORD * Self::NUM_RECORDS) as f64 / 1024_f64 / 1024_f64,
         );
         eprintln!(
-            "Cuckoo hashing with 3 hash functions, {} batch size, {} buckets, {} bucket size",
+            "Cuckoo hashing with {} hash functions, {} batch size, {} buckets, {} bucket size, {} stash size",
+            NUM_HASH,
             Self::BATCH_SIZE,
             Self::NUM_BUCKET,
             BaseRespire::DB_SIZE,
+            STASH_SIZE,
         );
         eprintln!("Parameters (base RESPIRE): {:#?}", BaseRespire::params());
         eprintln!(
@@ -79,7 +153,8 @@ impl<
 
         let (resp_size, resp_full_vecs, resp_rem) = Self::params_response_info();
         info!(
-            "Response: {} record(s) => {} ring elem(s) => {} full vector(s), {} remainder",
+            "Response: {} round(s) of {} record(s) => {} ring elem(s) => {} full vector(s), {} remainder",
+            Self::NUM_ROUNDS,
             Self::NUM_BUCKET,
             Self::NUM_BUCKET.div_ceil(BaseRespire::PACK_RATIO_RESPONSE),
             resp_full_vecs,
@@ -99,57 +174,97 @@ impl<
         eprintln!(
             "Error rate (estimated): 2^({:.3})",
             BaseRespire::params_error_rate_estimate().log2()
-        )
+        );
+        eprintln!(
+            "Cuckoo failure rate (bound, stash size {}): 2^({:.3})",
+            STASH_SIZE,
+            Self::params_cuckoo_failure_bound().log2()
+        );
     }
 
     fn encode_db<F: Fn(usize) -> Self::RecordBytes>(
         records_generator: F,
         time_stats: Option<&mut Stats<Duration>>,
     ) -> (Self::Database, Self::DatabaseHint) {
-        let begin = Instant::now();
-        // TODO the bucket layouts can be determined during setup since it is database independent
-        let mut bucket_layouts = vec![Vec::with_capacity(BaseRespire::DB_SIZE); Self::NUM_BUCKET];
-        for i in 0..Self::NUM_RECORDS {
-            let (b1, b2, b3) = Self::idx_to_buckets(i);
-            bucket_layouts[b1].push(Some(i));
-            bucket_layouts[b2].push(Some(i));
-            bucket_layouts[b3].push(Some(i));
-        }
-        let max_count = bucket_layouts.iter().map(|b| b.len()).max().unwrap();
-        info!(
-            "Cuckoo DB encoding: worst bucket size {} out of {}",
-            max_count,
-            BaseRespire::DB_SIZE
-        );
-        if (max_count as f64 / BaseRespire::DB_SIZE as f64) < 2f64 / 3f64 {
-            warn!(
-                "Buckets are not very full ({} / {})",
-                max_count,
-                BaseRespire::DB_SIZE
-            );
+        Self::encode_db_with_seed(thread_rng().gen(), records_generator, time_stats)
+    }
+
+    fn save_db(dbs: &Self::Database, hint: &Self::DatabaseHint, path: &Path) -> io::Result<()> {
+        let parts = dbs.iter().map(|db| &db[..]).collect_vec();
+        // The seed, the checkpoint spacing, then the bucket counts
+        let hint = hint
+            .seed
+            .chunks_exact(8)
+            .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+            .chain([hint.checkpoint_log2 as u64])
+            .chain(hint.bucket_counts.iter().map(|&count| count as u64))
+            .collect_vec();
+        write_db_file(path, Self::fingerprint(), &parts, &hint)
+    }
+
+    fn load_db(path: &Path) -> io::Result<(Self::Database, Self::DatabaseHint)> {
+        let part_len = (BaseRespire::params().D1 / SIMD_LANES) * BaseRespire::PACKED_DB_SIZE;
+        let (dbs, hint) = map_db_file(path, Self::fingerprint(), part_len)?;
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "database file does not match the cuckoo bucket layout",
+            )
+        };
+        if dbs.len() != Self::NUM_BUCKET || hint.len() < 3 {
+            return Err(invalid());
         }
-        assert!(max_count <= BaseRespire::DB_SIZE);
+        let mut seed = CuckooSeed::default();
+        seed[..8].copy_from_slice(&hint[0].to_le_bytes());
+        seed[8..].copy_from_slice(&hint[1].to_le_bytes());
+        let hint = CuckooDatabaseHint {
+            seed,
+            checkpoint_log2: u32::try_from(hint[2]).map_err(|_| invalid())?,
+            bucket_counts: hint[3..]
+                .iter()
+                .map(|&count| u32::try_from(count))
+                .collect::<Result<_, _>>()
+                .map_err(|_| invalid())?,
+        };
+        Self::check_hint(&hint).map_err(|_| invalid())?;
+        Ok((dbs, hint))
+    }
 
-        for b in bucket_layouts.iter_mut() {
-            while b.len() < BaseRespire::DB_SIZE {
-                b.push(None);
+    fn update_records(
+        dbs: &mut Self::Database,
+        hint: &Self::DatabaseHint,
+        records: &[(usize, Self::RecordBytes)],
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<(), PirError> {
+        let begin = Instant::now();
+        Self::check_hint(hint)?;
+        if dbs.len() != Self::NUM_BUCKET {
+            return Err(PirError::InvalidDatabaseHint);
+        }
+        let mut updates_by_bucket = vec![Vec::new(); Self::NUM_BUCKET];
+        for (idx, record) in records {
+            if *idx >= Self::NUM_RECORDS {
+                return Err(PirError::IndexOutOfRange {
+                    idx: *idx,
+                    num_records: Self::NUM_RECORDS,
+                });
+            }
+            // Each record is stored in every bucket it hashes to (once per hash function)
+            for b in Self::idx_to_buckets(&hint.seed, *idx).into_iter().unique() {
+                let positions = Self::bucket_positions(hint, *idx, b);
+                updates_by_bucket[b].extend(positions.map(|pos| (pos, record.clone())));
             }
         }
-
-        let mut result = Vec::with_capacity(Self::NUM_BUCKET);
-        let zero = Self::RecordBytes::default();
-        for (b_idx, b) in bucket_layouts.iter().enumerate() {
-            info!("Encoding bucket {} of {}...", b_idx + 1, Self::NUM_BUCKET);
-            let bucket_records_generator =
-                |i: usize| b[i].map_or(zero.clone(), |i| records_generator(i));
-            result.push(BaseRespire::encode_db(bucket_records_generator, None).0);
+        for (db, updates) in dbs.iter_mut().zip(updates_by_bucket) {
+            if !updates.is_empty() {
+                BaseRespire::update_records(db, &(), &updates, None)?;
+            }
         }
-
         let end = Instant::now();
         if let Some(time_stats) = time_stats {
-            time_stats.add("encode", end - begin);
+            time_stats.add("update", end - begin);
         }
-        (result, bucket_layouts)
+        Ok(())
     }
 
     fn setup(time_stats: Option<&mut Stats<Duration>>) -> (Self::QueryKey, Self::PublicParams) {
@@ -159,90 +274,80 @@ impl<
     fn query(
         qk: &Self::QueryKey,
         record_idxs: &[usize],
-        bucket_layouts: &Self::DatabaseHint,
-        mut time_stats: Option<&mut Stats<Duration>>,
-    ) -> (Self::Query, Self::State) {
-        let cuckoo_begin = Instant::now();
-        assert_eq!(record_idxs.len(), Self::BATCH_SIZE);
-        let cuckoo_mapping = Self::cuckoo(record_idxs, 2usize.pow(16)).unwrap();
-        assert_eq!(cuckoo_mapping.len(), Self::BATCH_SIZE);
-
-        let mut actual_idxs = vec![0usize; Self::NUM_BUCKET];
-        for (bucket_idx, idxs_idx) in cuckoo_mapping.iter().copied() {
-            let record_idx = record_idxs[idxs_idx];
-            // TODO optimize this linear search
-            actual_idxs[bucket_idx] = bucket_layouts[bucket_idx]
-                .iter()
-                .copied()
-                .find_position(|slot| slot.is_some_and(|i| i == record_idx))
-                .unwrap()
-                .0;
-        }
-        let cuckoo_end = Instant::now();
-        if let Some(time_stats) = time_stats.as_deref_mut() {
-            time_stats.add("query_cuckoo", cuckoo_end - cuckoo_begin);
-        }
-
-        assert_eq!(actual_idxs.len(), Self::NUM_BUCKET);
-        let q = actual_idxs
-            .iter()
-            .copied()
-            .map(|idx| BaseRespire::query_one(qk, idx, time_stats.as_deref_mut()))
-            .collect_vec();
-
-        (q, cuckoo_mapping)
+        hint: &Self::DatabaseHint,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<(Self::Query, Self::State), PirError> {
+        Self::query_with_rng(
+            qk,
+            record_idxs,
+            hint,
+            &mut ChaCha20Rng::from_entropy(),
+            time_stats,
+        )
     }
 
     fn answer(
         pp: &Self::PublicParams,
         dbs: &Self::Database,
         qs: &Self::Query,
-        qk: Option<&Self::QueryKey>,
         mut time_stats: Option<&mut Stats<Duration>>,
-    ) -> Self::Response {
-        assert_eq!(qs.len(), Self::NUM_BUCKET);
-        let answers = qs
-            .iter()
-            .zip(dbs)
-            .map(|(q, db)| BaseRespire::answer_one(pp, db, q, qk, time_stats.as_deref_mut()))
-            .collect_vec();
-        let answers_compressed = answers
-            .chunks(BaseRespire::RESPONSE_CHUNK_SIZE)
-            .map(|chunk| {
-                BaseRespire::answer_compress_chunk(pp, chunk, qk, time_stats.as_deref_mut())
-            })
-            .collect_vec();
-        answers_compressed
+    ) -> Result<Self::Response, PirError> {
+        if qs.len() != Self::NUM_BUCKET * Self::NUM_ROUNDS {
+            return Err(PirError::BatchSizeMismatch {
+                expected: Self::NUM_BUCKET * Self::NUM_ROUNDS,
+                found: qs.len(),
+            });
+        }
+        let mut result = Vec::new();
+        for round in qs.chunks(Self::NUM_BUCKET) {
+            result.extend(answer_buckets::<BaseRespire>(
+                pp,
+                dbs,
+                round,
+                time_stats.as_deref_mut(),
+            )?);
+        }
+        Ok(result)
     }
 
     fn extract(
         qk: &Self::QueryKey,
         r: &Self::Response,
-        cuckoo_mapping: &Self::State,
+        state: &Self::State,
         mut time_stats: Option<&mut Stats<Duration>>,
-    ) -> Vec<Self::RecordBytes> {
-        let mut result_by_bucket = Vec::with_capacity(Self::NUM_BUCKET);
-        for r_one in r {
-            let extracted = BaseRespire::extract_one(qk, r_one, time_stats.as_deref_mut());
-            for record in extracted {
-                if result_by_bucket.len() < Self::NUM_BUCKET {
-                    result_by_bucket.push(record);
-                }
-            }
+    ) -> Result<Vec<Self::RecordBytes>, PirError> {
+        let round_len = Self::NUM_BUCKET.div_ceil(BaseRespire::RESPONSE_CHUNK_SIZE);
+        if r.len() != round_len * Self::NUM_ROUNDS {
+            return Err(PirError::BatchSizeMismatch {
+                expected: round_len * Self::NUM_ROUNDS,
+                found: r.len(),
+            });
+        }
+        let mut result_by_query = Vec::with_capacity(Self::NUM_BUCKET * Self::NUM_ROUNDS);
+        for round in r.chunks(round_len) {
+            result_by_query.extend(extract_buckets::<BaseRespire>(
+                qk,
+                round,
+                Self::NUM_BUCKET,
+                time_stats.as_deref_mut(),
+            )?);
         }
-        assert_eq!(result_by_bucket.len(), Self::NUM_BUCKET);
 
         let uncuckoo_begin = Instant::now();
-        let mut result = vec![BaseRespire::RecordBytes::default(); Self::BATCH_SIZE];
-        assert_eq!(cuckoo_mapping.len(), Self::BATCH_SIZE);
-        for (bucket_idx, idxs_idx) in cuckoo_mapping.iter().copied() {
-            result[idxs_idx] = result_by_bucket[bucket_idx].clone();
+        let mut distinct = vec![BaseRespire::RecordBytes::default(); state.cuckoo_mapping.len()];
+        for (query_idx, distinct_idx) in state.cuckoo_mapping.iter().copied() {
+            distinct[distinct_idx] = result_by_query[query_idx].clone();
         }
+        let result = state
+            .distinct_idxs
+            .iter()
+            .map(|&i| distinct[i].clone())
+            .collect_vec();
         let uncuckoo_end = Instant::now();
         if let Some(time_stats) = time_stats {
             time_stats.add("extract_uncuckoo", uncuckoo_end - uncuckoo_begin);
         }
-        result
+        Ok(result)
     }
 }
 
@@ -251,65 +356,273 @@ impl<
         const NUM_BUCKET: usize,
         const NUM_RECORDS: usize,
         BaseRespire: PIR + Respire,
-    > CuckooRespireImpl<BATCH_SIZE, NUM_BUCKET, NUM_RECORDS, BaseRespire>
+        const NUM_HASH: usize,
+        const STASH_SIZE: usize,
+    > CuckooRespireImpl<BATCH_SIZE, NUM_BUCKET, NUM_RECORDS, BaseRespire, NUM_HASH, STASH_SIZE>
 {
-    fn idx_to_buckets(i: usize) -> (usize, usize, usize) {
-        let modulus = Self::NUM_BUCKET as u64;
-        assert!(modulus.checked_pow(3).is_some());
-        // TODO: DefaultHasher is not stable
-        let mut hasher = DefaultHasher::new();
-        i.hash(&mut hasher);
-        let hashed = hasher.finish();
-        let h1 = hashed % modulus;
-        let h2 = (hashed / modulus) % modulus;
-        let h3 = (hashed / modulus / modulus) % modulus;
-        (h1 as usize, h2 as usize, h3 as usize)
+    /// Number of rounds of one query per bucket: the cuckoo round, and one per stash entry.
+    pub const NUM_ROUNDS: usize = 1 + STASH_SIZE;
+
+    ///
+    /// Checkpoint spacing of the hints built by [`Self::encode_db_with_seed`] and
+    /// [`Self::hint_from_seed`]: about `sqrt(NUM_BUCKET * NUM_RECORDS)` records, which balances the
+    /// hint size against the rehashing done per lookup.
+    ///
+    pub const DEFAULT_CHECKPOINT_LOG2: u32 = (Self::NUM_BUCKET * Self::NUM_RECORDS)
+        .isqrt()
+        .next_power_of_two()
+        .ilog2();
+
+    ///
+    /// Like [`PIR::query`], but drawing all randomness (of the cuckoo insertion and of the
+    /// encryption) from `rng`.
+    ///
+    pub fn query_with_rng<R: RngCore + CryptoRng>(
+        qk: &BaseRespire::QueryKey,
+        record_idxs: &[usize],
+        hint: &CuckooDatabaseHint,
+        rng: &mut R,
+        mut time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<(Vec<BaseRespire::QueryOne>, CuckooQueryState), PirError> {
+        let cuckoo_begin = Instant::now();
+        if record_idxs.is_empty() || record_idxs.len() > Self::BATCH_SIZE {
+            return Err(PirError::BatchSizeMismatch {
+                expected: Self::BATCH_SIZE,
+                found: record_idxs.len(),
+            });
+        }
+        if let Some(&idx) = record_idxs.iter().find(|&&idx| idx >= Self::NUM_RECORDS) {
+            return Err(PirError::IndexOutOfRange {
+                idx,
+                num_records: Self::NUM_RECORDS,
+            });
+        }
+        Self::check_hint(hint)?;
+
+        // A record requested several times is only placed (and retrieved) once
+        let distinct = record_idxs.iter().copied().unique().collect_vec();
+        let distinct_idxs = record_idxs
+            .iter()
+            .map(|idx| distinct.iter().position(|d| d == idx).unwrap())
+            .collect_vec();
+        let (mut cuckoo_mapping, stash) = (0..CUCKOO_ATTEMPTS)
+            .find_map(|_| Self::cuckoo(&hint.seed, &distinct, CUCKOO_MAX_DEPTH, rng))
+            .ok_or(PirError::CuckooInsertionFailed)?;
+        // Stashed record `j` is retrieved in round `j + 1`, from the bucket of its first hash
+        for (j, distinct_idx) in stash.into_iter().enumerate() {
+            let bucket_idx = Self::idx_to_buckets(&hint.seed, distinct[distinct_idx])[0];
+            cuckoo_mapping.push(((j + 1) * Self::NUM_BUCKET + bucket_idx, distinct_idx));
+        }
+        assert_eq!(cuckoo_mapping.len(), distinct.len());
+
+        // Buckets without a record of the batch query slot 0
+        let mut actual_idxs = vec![0usize; Self::NUM_BUCKET * Self::NUM_ROUNDS];
+        for (query_idx, distinct_idx) in cuckoo_mapping.iter().copied() {
+            let bucket_idx = query_idx % Self::NUM_BUCKET;
+            actual_idxs[query_idx] =
+                Self::bucket_positions(hint, distinct[distinct_idx], bucket_idx).start;
+        }
+        let cuckoo_end = Instant::now();
+        if let Some(time_stats) = time_stats.as_deref_mut() {
+            time_stats.add("query_cuckoo", cuckoo_end - cuckoo_begin);
+        }
+
+        let q = actual_idxs
+            .iter()
+            .copied()
+            .map(|idx| BaseRespire::query_one_with_rng(qk, idx, rng, time_stats.as_deref_mut()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok((
+            q,
+            CuckooQueryState {
+                cuckoo_mapping,
+                distinct_idxs,
+            },
+        ))
     }
 
     ///
-    /// Returns a vector of (bucket slot index, item index) pairs.
+    /// Encodes the database with the given bucket hash seed (see [`PIR::encode_db`], which picks a
+    /// random seed).
     ///
-    fn cuckoo(items: &[usize], max_depth: usize) -> Option<Vec<(usize, usize)>> {
-        // Maps bucket slot indices to item indices
-        let mut mapping = HashMap::with_capacity(items.len());
-        let mut remaining = Vec::from_iter((0..items.len()).map(|idx| (idx, 0usize)));
-        let mut rng = thread_rng();
-        while let Some((idx, depth)) = remaining.pop() {
-            if depth >= max_depth {
-                return None;
+    pub fn encode_db_with_seed<F: Fn(usize) -> BaseRespire::RecordBytes>(
+        seed: CuckooSeed,
+        records_generator: F,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> (Vec<SimdDatabase>, CuckooDatabaseHint) {
+        let begin = Instant::now();
+        let mut bucket_layouts = vec![Vec::with_capacity(BaseRespire::DB_SIZE); Self::NUM_BUCKET];
+        for i in 0..Self::NUM_RECORDS {
+            for b in Self::idx_to_buckets(&seed, i) {
+                bucket_layouts[b].push(i);
+            }
+        }
+        let max_count = bucket_layouts.iter().map(|b| b.len()).max().unwrap();
+        info!(
+            "Cuckoo DB encoding: worst bucket size {} out of {}",
+            max_count,
+            BaseRespire::DB_SIZE
+        );
+        if (max_count as f64 / BaseRespire::DB_SIZE as f64) < 2f64 / 3f64 {
+            warn!(
+                "Buckets are not very full ({} / {})",
+                max_count,
+                BaseRespire::DB_SIZE
+            );
+        }
+        assert!(max_count <= BaseRespire::DB_SIZE);
+
+        let mut result = Vec::with_capacity(Self::NUM_BUCKET);
+        let zero = BaseRespire::RecordBytes::default();
+        for (b_idx, b) in bucket_layouts.iter().enumerate() {
+            info!("Encoding bucket {} of {}...", b_idx + 1, Self::NUM_BUCKET);
+            let bucket_records_generator =
+                |i: usize| b.get(i).map_or(zero.clone(), |&i| records_generator(i));
+            result.push(BaseRespire::encode_db(bucket_records_generator, None).0);
+        }
+
+        let end = Instant::now();
+        if let Some(time_stats) = time_stats {
+            time_stats.add("encode", end - begin);
+        }
+        let checkpoint_log2 = Self::DEFAULT_CHECKPOINT_LOG2;
+        let bucket_counts = (0..Self::num_checkpoints(checkpoint_log2))
+            .flat_map(|k| {
+                bucket_layouts
+                    .iter()
+                    .map(move |b| b.partition_point(|&i| i < k << checkpoint_log2) as u32)
+            })
+            .collect_vec();
+        (
+            result,
+            CuckooDatabaseHint {
+                seed,
+                checkpoint_log2,
+                bucket_counts,
+            },
+        )
+    }
+
+    ///
+    /// Builds the database hint for the given seed, with [`Self::DEFAULT_CHECKPOINT_LOG2`]. This
+    /// takes `NUM_HASH` hash evaluations per record, so clients should get the hint from the server
+    /// (which builds it while encoding) rather than call this.
+    ///
+    pub fn hint_from_seed(seed: CuckooSeed) -> CuckooDatabaseHint {
+        Self::hint_from_seed_with_checkpoints(seed, Self::DEFAULT_CHECKPOINT_LOG2)
+    }
+
+    ///
+    /// Like [`Self::hint_from_seed`], with checkpoints every `2^checkpoint_log2` records.
+    ///
+    pub fn hint_from_seed_with_checkpoints(
+        seed: CuckooSeed,
+        checkpoint_log2: u32,
+    ) -> CuckooDatabaseHint {
+        assert!(BaseRespire::DB_SIZE <= u32::MAX as usize);
+        assert!(checkpoint_log2 < usize::BITS);
+        let mut bucket_counts =
+            Vec::with_capacity(Self::num_checkpoints(checkpoint_log2) * Self::NUM_BUCKET);
+        let mut counts = vec![0_u32; Self::NUM_BUCKET];
+        for i in 0..Self::NUM_RECORDS {
+            if i % (1 << checkpoint_log2) == 0 {
+                bucket_counts.extend_from_slice(&counts);
             }
-            let (i1, i2, i3) = Self::idx_to_buckets(items[idx]);
-            match (mapping.get(&i1), mapping.get(&i2), mapping.get(&i3)) {
-                (None, _, _) => {
-                    mapping.insert(i1, idx);
-                }
-                (_, None, _) => {
-                    mapping.insert(i2, idx);
-                }
-                (_, _, None) => {
-                    mapping.insert(i3, idx);
-                }
-                (Some(&curr1), Some(&curr2), Some(&curr3)) => match rng.gen_range(0..3) {
-                    0 => {
-                        remaining.push((curr1, depth + 1));
-                        mapping.insert(i1, idx);
-                    }
-                    1 => {
-                        remaining.push((curr2, depth + 1));
-                        mapping.insert(i2, idx);
-                    }
-                    _ => {
-                        remaining.push((curr3, depth + 1));
-                        mapping.insert(i3, idx);
-                    }
-                },
+            for b in Self::idx_to_buckets(&seed, i) {
+                counts[b] += 1;
             }
         }
-        Some(mapping.into_iter().collect_vec())
+        CuckooDatabaseHint {
+            seed,
+            checkpoint_log2,
+            bucket_counts,
+        }
+    }
+
+    fn num_checkpoints(checkpoint_log2: u32) -> usize {
+        Self::NUM_RECORDS.div_ceil(1 << checkpoint_log2)
+    }
+
+    fn check_hint(hint: &CuckooDatabaseHint) -> Result<(), PirError> {
+        if hint.checkpoint_log2 >= usize::BITS
+            || hint.bucket_counts.len()
+                != Self::num_checkpoints(hint.checkpoint_log2) * Self::NUM_BUCKET
+        {
+            return Err(PirError::InvalidDatabaseHint);
+        }
+        Ok(())
+    }
+
+    ///
+    /// The slots of bucket `bucket` holding record `idx`: one per hash function mapping `idx` to
+    /// `bucket`, so the range is empty if `idx` is not stored there. Takes at most
+    /// `2^checkpoint_log2 * NUM_HASH` hash evaluations.
+    ///
+    pub fn bucket_positions(hint: &CuckooDatabaseHint, idx: usize, bucket: usize) -> Range<usize> {
+        let checkpoint = idx >> hint.checkpoint_log2;
+        let before_checkpoint = hint.bucket_counts[checkpoint * Self::NUM_BUCKET + bucket] as usize;
+        let multiplicity = |i: usize| {
+            Self::idx_to_buckets(&hint.seed, i)
+                .into_iter()
+                .filter(|&b| b == bucket)
+                .count()
+        };
+        let start = before_checkpoint
+            + ((checkpoint << hint.checkpoint_log2)..idx)
+                .map(multiplicity)
+                .sum::<usize>();
+        start..start + multiplicity(idx)
+    }
+
+    /// The base RESPIRE fingerprint extended with the cuckoo hashing parameters.
+    fn fingerprint() -> u64 {
+        fingerprint_extend(
+            BaseRespire::params().fingerprint(),
+            &[
+                Self::BATCH_SIZE as u64,
+                Self::NUM_BUCKET as u64,
+                Self::NUM_RECORDS as u64,
+                NUM_HASH as u64,
+            ],
+        )
+    }
+
+    ///
+    /// The buckets record `i` is stored in, one per hash function (they need not be distinct). See
+    /// [`hash_to_buckets`].
+    ///
+    pub fn idx_to_buckets(seed: &CuckooSeed, i: usize) -> [usize; NUM_HASH] {
+        hash_to_buckets(seed, i as u64, Self::NUM_BUCKET)
+    }
+
+    ///
+    /// Returns a vector of (bucket slot index, item index) pairs, and the indices of the items that
+    /// went to the stash.
+    ///
+    fn cuckoo<R: Rng>(
+        seed: &CuckooSeed,
+        items: &[usize],
+        max_depth: usize,
+        rng: &mut R,
+    ) -> Option<CuckooAssignment> {
+        let candidates = items
+            .iter()
+            .map(|&i| Self::idx_to_buckets(seed, i))
+            .collect_vec();
+        cuckoo_assign_with_stash(&candidates, max_depth, STASH_SIZE, rng)
+    }
+
+    ///
+    /// Upper bound on the probability that a batch of distinct records cannot be retrieved, see
+    /// [`cuckoo_failure_bound`].
+    ///
+    pub fn params_cuckoo_failure_bound() -> f64 {
+        cuckoo_failure_bound(Self::BATCH_SIZE, Self::NUM_BUCKET, NUM_HASH, STASH_SIZE)
     }
 
     pub fn params_query_size() -> usize {
-        Self::NUM_BUCKET * BaseRespire::params_query_one_size()
+        Self::NUM_BUCKET * Self::NUM_ROUNDS * BaseRespire::params_query_one_size()
     }
 
     pub fn params_record_size() -> usize {
@@ -317,7 +630,7 @@ impl<
     }
 
     ///
-    /// size, number of full vectors, remainder size
+    /// size, number of full vectors, remainder size (the latter two per round)
     ///
     pub fn params_response_info() -> (usize, usize, usize) {
         let num_ring_elem = Self::NUM_BUCKET.div_ceil(BaseRespire::PACK_RATIO_RESPONSE);
@@ -331,7 +644,7 @@ impl<
             0
         };
         (
-            num_full_vecs * full_vec_size + rem_vec_size,
+            (num_full_vecs * full_vec_size + rem_vec_size) * Self::NUM_ROUNDS,
             num_full_vecs,
             num_rem,
         )
@@ -341,3 +654,195 @@ impl<
         (Self::params_record_size() as f64) / (Self::params_response_info().0 as f64)
     }
 }
+
+///
+/// The `NUM_HASH` candidate buckets (out of `num_bucket`) of item `i`, which need not be distinct.
+/// Hash function `h` is SipHash-1-3 keyed by `seed`, applied to the little endian encodings of `i`
+/// and `h` as 64 bit integers, and reduced mod `num_bucket`.
+///
+pub fn hash_to_buckets<const NUM_HASH: usize>(
+    seed: &CuckooSeed,
+    i: u64,
+    num_bucket: usize,
+) -> [usize; NUM_HASH] {
+    std::array::from_fn(|h| {
+        let mut hasher = SipHasher13::new_with_key(seed);
+        hasher.write(&i.to_le_bytes());
+        hasher.write(&(h as u64).to_le_bytes());
+        (hasher.finish() % num_bucket as u64) as usize
+    })
+}
+
+///
+/// Assigns each item to one of its candidate buckets, with no two items sharing a bucket. Returns a
+/// vector of (bucket slot index, item index) pairs, or `None` if some item was evicted `max_depth`
+/// times. Evictions are chosen with `rng`.
+///
+pub(crate) fn cuckoo_assign<const H: usize, R: Rng>(
+    candidates: &[[usize; H]],
+    max_depth: usize,
+    rng: &mut R,
+) -> Option<Vec<(usize, usize)>> {
+    cuckoo_assign_with_stash(candidates, max_depth, 0, rng).map(|(mapping, _)| mapping)
+}
+
+/// (bucket slot index, item index) pairs, and the indices of the stashed items.
+pub(crate) type CuckooAssignment = (Vec<(usize, usize)>, Vec<usize>);
+
+///
+/// Like [`cuckoo_assign`], but an item evicted `max_depth` times goes to a stash instead, as long
+/// as the stash holds fewer than `max_stash` items. Returns the assignment and the stashed items.
+///
+pub(crate) fn cuckoo_assign_with_stash<const H: usize, R: Rng>(
+    candidates: &[[usize; H]],
+    max_depth: usize,
+    max_stash: usize,
+    rng: &mut R,
+) -> Option<CuckooAssignment> {
+    // Maps bucket slot indices to item indices
+    let mut mapping = HashMap::with_capacity(candidates.len());
+    let mut stash = Vec::new();
+    let mut remaining = Vec::from_iter((0..candidates.len()).map(|idx| (idx, 0usize)));
+    while let Some((idx, depth)) = remaining.pop() {
+        if depth >= max_depth {
+            if stash.len() == max_stash {
+                return None;
+            }
+            stash.push(idx);
+            continue;
+        }
+        let buckets = &candidates[idx];
+        match buckets.iter().find(|b| !mapping.contains_key(*b)) {
+            Some(&b) => {
+                mapping.insert(b, idx);
+            }
+            None => {
+                let b = buckets[rng.gen_range(0..H)];
+                let evicted = mapping.insert(b, idx).unwrap();
+                remaining.push((evicted, depth + 1));
+            }
+        }
+    }
+    Some((mapping.into_iter().collect_vec(), stash))
+}
+
+///
+/// Upper bound on the probability that `batch_size` distinct items, each with `num_hash`
+/// independent uniformly random candidate buckets out of `num_bucket`, cannot all be placed when up
+/// to `stash_size` of them may go to the stash. Assumes insertion finds a maximum matching of items
+/// to buckets whenever it exists (which retrying [`CUCKOO_ATTEMPTS`] times makes very likely).
+///
+/// By Hall's theorem, more than `stash_size` items are left over exactly when some set of `t`
+/// items has all of its candidates inside a set of `t - stash_size - 1` buckets. The bound is the
+/// union bound over all such pairs of sets.
+///
+pub fn cuckoo_failure_bound(
+    batch_size: usize,
+    num_bucket: usize,
+    num_hash: usize,
+    stash_size: usize,
+) -> f64 {
+    let ln_binomial = |n: usize, k: usize| -> f64 {
+        (0..k)
+            .map(|i| ((n - i) as f64).ln() - ((i + 1) as f64).ln())
+            .sum()
+    };
+    let mut total = 0_f64;
+    for t in (stash_size + 2)..=batch_size {
+        let buckets = t - stash_size - 1;
+        if buckets > num_bucket {
+            break;
+        }
+        let ln_term = ln_binomial(batch_size, t)
+            + ln_binomial(num_bucket, buckets)
+            + (num_hash * t) as f64 * (buckets as f64 / num_bucket as f64).ln();
+        total += ln_term.exp();
+    }
+    total.min(1_f64)
+}
+
+///
+/// Fraction of `trials` random batches of `batch_size` distinct items, each with `H` uniformly
+/// random candidate buckets out of `num_bucket`, that could not be placed by the insertion used for
+/// queries (including retries), with a stash of `stash_size` items.
+///
+pub fn cuckoo_failure_rate_simulated<const H: usize>(
+    batch_size: usize,
+    num_bucket: usize,
+    stash_size: usize,
+    trials: usize,
+) -> f64 {
+    let mut rng = thread_rng();
+    let failures = (0..trials)
+        .filter(|_| {
+            let candidates = (0..batch_size)
+                .map(|_| std::array::from_fn::<_, H, _>(|_| rng.gen_range(0..num_bucket)))
+                .collect_vec();
+            (0..CUCKOO_ATTEMPTS).all(|_| {
+                cuckoo_assign_with_stash(&candidates, CUCKOO_MAX_DEPTH, stash_size, &mut rng)
+                    .is_none()
+            })
+        })
+        .count();
+    failures as f64 / trials as f64
+}
+
+///
+/// Answers one query per bucket, where bucket `i` is the database `dbs[i]`.
+///
+pub(crate) fn answer_buckets<BaseRespire: PIR + Respire>(
+    pp: &BaseRespire::PublicParams,
+    dbs: &[SimdDatabase],
+    qs: &[BaseRespire::QueryOne],
+    mut time_stats: Option<&mut Stats<Duration>>,
+) -> Result<Vec<BaseRespire::AnswerOneCompressed>, PirError> {
+    if qs.len() != dbs.len() {
+        return Err(PirError::BatchSizeMismatch {
+            expected: dbs.len(),
+            found: qs.len(),
+        });
+    }
+    let answers = parallel::map_with_stats(qs.len(), time_stats.as_deref_mut(), |i, stats| {
+        BaseRespire::answer_one(pp, &dbs[i], &qs[i], stats)
+    })
+    .into_iter()
+    .collect::<Result<Vec<_>, _>>()?;
+    let chunks = answers
+        .chunks(BaseRespire::RESPONSE_CHUNK_SIZE)
+        .collect_vec();
+    let answers_compressed = parallel::map_with_stats(chunks.len(), time_stats, |i, stats| {
+        BaseRespire::answer_compress_chunk(pp, chunks[i], stats)
+    });
+    Ok(answers_compressed)
+}
+
+///
+/// Extracts the record retrieved from each of the `num_bucket` buckets.
+///
+pub(crate) fn extract_buckets<BaseRespire: PIR + Respire>(
+    qk: &BaseRespire::QueryKey,
+    r: &[BaseRespire::AnswerOneCompressed],
+    num_bucket: usize,
+    mut time_stats: Option<&mut Stats<Duration>>,
+) -> Result<Vec<BaseRespire::RecordBytes>, PirError> {
+    let expected_len = num_bucket.div_ceil(BaseRespire::RESPONSE_CHUNK_SIZE);
+    if r.len() != expected_len {
+        return Err(PirError::BatchSizeMismatch {
+            expected: expected_len,
+            found: r.len(),
+        });
+    }
+    let mut result_by_bucket = Vec::with_capacity(num_bucket);
+    for r_one in r {
+        let extracted = BaseRespire::extract_one(qk, r_one, time_stats.as_deref_mut())?;
+        for record in extracted {
+            if result_by_bucket.len() < num_bucket {
+                result_by_bucket.push(record);
+            }
+        }
+    }
+    if result_by_bucket.len() != num_bucket {
+        return Err(PirError::MalformedResponse);
+    }
+    Ok(result_by_bucket)
+}
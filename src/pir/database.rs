@@ -0,0 +1,328 @@
+//! Storage for encoded databases, either owned in memory or memory-mapped from a file.
+//!
+//! The on-disk format is a fixed [`DB_FILE_HEADER_SIZE`] byte header, followed by `num_parts`
+//! arrays of `part_len` [`SimdVec`]s each (in native byte order), followed by `hint_len` little
+//! endian `u64`s of (scheme specific) hint data:
+//!
+//! | bytes  | field                                                          |
+//! |--------|----------------------------------------------------------------|
+//! | 0..8   | magic (`RSPRDB\0\0`)                                           |
+//! | 8..12  | format version ([`DB_FILE_VERSION`])                           |
+//! | 12..16 | SIMD lane count ([`SIMD_LANES`])                               |
+//! | 16..24 | parameter fingerprint                                          |
+//! | 24..32 | `num_parts`                                                    |
+//! | 32..40 | `part_len`                                                     |
+//! | 40..48 | `hint_len`                                                     |
+//! | 48..52 | byte order mark ([`DB_FILE_BYTE_ORDER_MARK`], native byte order) |
+//! | 52..64 | reserved (zero)                                                |
+//!
+//! All other fields are little endian. The byte order mark tells whether the parts were written in
+//! the byte order of the reading machine; files from a machine of the other byte order are
+//! rejected. The header size is a multiple of the [`SimdVec`] alignment, so the
+//! parts can be used in place when the file is mapped.
+
+use crate::math::simd_utils::*;
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::mem::{align_of, size_of};
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::Arc;
+
+pub const DB_FILE_MAGIC: [u8; 8] = *b"RSPRDB\0\0";
+pub const DB_FILE_VERSION: u32 = 2;
+pub const DB_FILE_BYTE_ORDER_MARK: u32 = 0x0102_0304;
+pub const DB_FILE_HEADER_SIZE: usize = 64;
+
+const _: () = assert!(DB_FILE_HEADER_SIZE.is_multiple_of(align_of::<SimdVec>()));
+
+enum Storage {
+    Owned(Vec<SimdVec>),
+    Mapped {
+        mmap: Arc<Mmap>,
+        offset: usize,
+        len: usize,
+    },
+}
+
+///
+/// An encoded database as a flat array of [`SimdVec`]s. Dereferences to `[SimdVec]` regardless of
+/// whether it is held in memory or mapped read-only from a file.
+///
+pub struct SimdDatabase {
+    storage: Storage,
+}
+
+impl From<Vec<SimdVec>> for SimdDatabase {
+    fn from(vec: Vec<SimdVec>) -> Self {
+        Self {
+            storage: Storage::Owned(vec),
+        }
+    }
+}
+
+impl Deref for SimdDatabase {
+    type Target = [SimdVec];
+    fn deref(&self) -> &[SimdVec] {
+        match &self.storage {
+            Storage::Owned(vec) => vec.as_slice(),
+            Storage::Mapped { mmap, offset, len } => {
+                let ptr = mmap[*offset..].as_ptr() as *const SimdVec;
+                // Safety: `map_db_file` checks that the range is in bounds and that `ptr` is
+                // aligned; `SimdVec` is plain data, so any bit pattern is valid.
+                unsafe { std::slice::from_raw_parts(ptr, *len) }
+            }
+        }
+    }
+}
+
+impl SimdDatabase {
+    pub fn is_mapped(&self) -> bool {
+        matches!(self.storage, Storage::Mapped { .. })
+    }
+
+    ///
+    /// Mutable access to the database. A mapped database is first copied into memory (the file is
+    /// never modified), so changes only persist once it is saved again.
+    ///
+    pub fn to_mut(&mut self) -> &mut [SimdVec] {
+        if self.is_mapped() {
+            self.storage = Storage::Owned(self.to_vec());
+        }
+        match &mut self.storage {
+            Storage::Owned(vec) => vec.as_mut_slice(),
+            Storage::Mapped { .. } => unreachable!(),
+        }
+    }
+}
+
+fn as_bytes(vecs: &[SimdVec]) -> &[u8] {
+    // Safety: `SimdVec` is plain data without padding
+    unsafe { std::slice::from_raw_parts(vecs.as_ptr() as *const u8, std::mem::size_of_val(vecs)) }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+///
+/// Writes the given parts (which must all have the same length) and hint to `path`.
+///
+pub fn write_db_file(
+    path: &Path,
+    fingerprint: u64,
+    parts: &[&[SimdVec]],
+    hint: &[u64],
+) -> io::Result<()> {
+    let part_len = parts.first().map_or(0, |p| p.len());
+    assert!(parts.iter().all(|p| p.len() == part_len));
+
+    let mut header = [0u8; DB_FILE_HEADER_SIZE];
+    header[0..8].copy_from_slice(&DB_FILE_MAGIC);
+    header[8..12].copy_from_slice(&DB_FILE_VERSION.to_le_bytes());
+    header[12..16].copy_from_slice(&(SIMD_LANES as u32).to_le_bytes());
+    header[16..24].copy_from_slice(&fingerprint.to_le_bytes());
+    header[24..32].copy_from_slice(&(parts.len() as u64).to_le_bytes());
+    header[32..40].copy_from_slice(&(part_len as u64).to_le_bytes());
+    header[40..48].copy_from_slice(&(hint.len() as u64).to_le_bytes());
+    header[48..52].copy_from_slice(&DB_FILE_BYTE_ORDER_MARK.to_ne_bytes());
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&header)?;
+    for part in parts {
+        writer.write_all(as_bytes(part))?;
+    }
+    for h in hint {
+        writer.write_all(&h.to_le_bytes())?;
+    }
+    writer.flush()
+}
+
+///
+/// Memory-maps a file written by [`write_db_file`], checking that it was written for the given
+/// parameter fingerprint and the current SIMD layout, and that each part has `part_len` entries.
+/// Returns the parts (backed by the mapping) and a copy of the hint.
+///
+pub fn map_db_file(
+    path: &Path,
+    fingerprint: u64,
+    part_len: usize,
+) -> io::Result<(Vec<SimdDatabase>, Vec<u64>)> {
+    let file = File::open(path)?;
+    // Safety: the file is mapped read-only; it must not be modified while the mapping is in use.
+    let mmap = Arc::new(unsafe { Mmap::map(&file)? });
+    if mmap.len() < DB_FILE_HEADER_SIZE {
+        return Err(invalid_data("database file is truncated".to_string()));
+    }
+    let read_u32 = |at: usize| u32::from_le_bytes(mmap[at..at + 4].try_into().unwrap());
+    let read_u64 = |at: usize| u64::from_le_bytes(mmap[at..at + 8].try_into().unwrap());
+
+    if mmap[0..8] != DB_FILE_MAGIC {
+        return Err(invalid_data("not a database file".to_string()));
+    }
+    if read_u32(8) != DB_FILE_VERSION {
+        return Err(invalid_data(format!(
+            "unsupported database file version {}",
+            read_u32(8)
+        )));
+    }
+    if read_u32(12) as usize != SIMD_LANES {
+        return Err(invalid_data(format!(
+            "database file has {} SIMD lanes, expected {}",
+            read_u32(12),
+            SIMD_LANES
+        )));
+    }
+    if u32::from_ne_bytes(mmap[48..52].try_into().unwrap()) != DB_FILE_BYTE_ORDER_MARK {
+        return Err(invalid_data(
+            "database file was written with a different byte order".to_string(),
+        ));
+    }
+    if read_u64(16) != fingerprint {
+        return Err(invalid_data(format!(
+            "database file params fingerprint {:016x} does not match {:016x}",
+            read_u64(16),
+            fingerprint
+        )));
+    }
+    if read_u64(32) as usize != part_len {
+        return Err(invalid_data(format!(
+            "database file part length {} does not match {}",
+            read_u64(32),
+            part_len
+        )));
+    }
+    // The header is untrusted, so the sizes it implies must not overflow
+    let too_large = || invalid_data("database file header sizes overflow".to_string());
+    let num_parts = usize::try_from(read_u64(24)).map_err(|_| too_large())?;
+    let hint_len = usize::try_from(read_u64(40)).map_err(|_| too_large())?;
+    let part_size = part_len
+        .checked_mul(size_of::<SimdVec>())
+        .ok_or_else(too_large)?;
+    let hint_offset = num_parts
+        .checked_mul(part_size)
+        .and_then(|size| size.checked_add(DB_FILE_HEADER_SIZE))
+        .ok_or_else(too_large)?;
+    let file_size = hint_len
+        .checked_mul(8)
+        .and_then(|size| size.checked_add(hint_offset))
+        .ok_or_else(too_large)?;
+    if mmap.len() != file_size {
+        return Err(invalid_data(format!(
+            "database file has size {}, expected {}",
+            mmap.len(),
+            file_size
+        )));
+    }
+    if !(mmap.as_ptr() as usize).is_multiple_of(align_of::<SimdVec>()) {
+        return Err(invalid_data(
+            "database file mapping is misaligned".to_string(),
+        ));
+    }
+
+    let hint = mmap[hint_offset..]
+        .chunks_exact(8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .collect();
+    let parts = (0..num_parts)
+        .map(|i| {
+            let offset = DB_FILE_HEADER_SIZE + i * part_size;
+            if offset + part_size > mmap.len() {
+                return Err(invalid_data(format!(
+                    "database file part {} lies past the end of the file",
+                    i
+                )));
+            }
+            Ok(SimdDatabase {
+                storage: Storage::Mapped {
+                    mmap: mmap.clone(),
+                    offset,
+                    len: part_len,
+                },
+            })
+        })
+        .collect::<io::Result<_>>()?;
+    Ok((parts, hint))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pir::respire_harness::temp_path;
+
+    #[cfg(target_feature = "avx2")]
+    fn simd_vec(x: u64) -> SimdVec {
+        Aligned32([x, x + 1, x + 2, x + 3])
+    }
+
+    #[cfg(not(target_feature = "avx2"))]
+    fn simd_vec(x: u64) -> SimdVec {
+        x
+    }
+
+    #[test]
+    fn test_db_file_round_trip() {
+        let path = temp_path("db");
+        let parts: Vec<SimdDatabase> = (0..3)
+            .map(|p| SimdDatabase::from((0..10).map(|i| simd_vec(100 * p + i)).collect::<Vec<_>>()))
+            .collect();
+        let part_refs: Vec<&[SimdVec]> = parts.iter().map(|p| &p[..]).collect();
+        let hint = [7, u64::MAX, 0];
+        write_db_file(&path, 0x1234, &part_refs, &hint).unwrap();
+
+        let (loaded, loaded_hint) = map_db_file(&path, 0x1234, 10).unwrap();
+        assert_eq!(loaded_hint, hint);
+        assert_eq!(loaded.len(), parts.len());
+        for (expected, actual) in parts.iter().zip(loaded.iter()) {
+            assert!(actual.is_mapped());
+            assert_eq!(as_bytes(expected), as_bytes(actual));
+        }
+
+        let mut modified = map_db_file(&path, 0x1234, 10).unwrap().0.remove(1);
+        modified.to_mut()[0] = simd_vec(12345);
+        assert!(!modified.is_mapped());
+        assert_eq!(as_bytes(&modified[1..]), as_bytes(&parts[1][1..]));
+        let (reloaded, _) = map_db_file(&path, 0x1234, 10).unwrap();
+        assert_eq!(as_bytes(&reloaded[1]), as_bytes(&parts[1]));
+        drop(reloaded);
+
+        assert!(map_db_file(&path, 0x4321, 10).is_err());
+        assert!(map_db_file(&path, 0x1234, 11).is_err());
+        drop(loaded);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_db_file_rejects_bad_header() {
+        let path = temp_path("db-header");
+        let part: Vec<SimdVec> = (0..10).map(simd_vec).collect();
+        write_db_file(&path, 0x1234, &[&part, &part, &part], &[]).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let map_modified = |at: usize, field: &[u8]| {
+            let mut modified = bytes.clone();
+            modified[at..at + field.len()].copy_from_slice(field);
+            std::fs::write(&path, modified).unwrap();
+            map_db_file(&path, 0x1234, 10)
+                .map(|_| ())
+                .unwrap_err()
+                .kind()
+        };
+
+        // The part count wraps around to 3 parts when multiplied by the part size
+        let wrapping_num_parts = 3_u64 + (1 << 60);
+        assert_eq!(
+            map_modified(24, &wrapping_num_parts.to_le_bytes()),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            map_modified(24, &u64::MAX.to_le_bytes()),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            map_modified(48, &DB_FILE_BYTE_ORDER_MARK.swap_bytes().to_ne_bytes()),
+            io::ErrorKind::InvalidData
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
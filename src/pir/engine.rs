@@ -0,0 +1,457 @@
+//! Runtime dispatch over precompiled RESPIRE parameter sets.
+//!
+//! [`RespireImpl`] is monomorphized over its parameters, so a binary can only serve the parameter
+//! sets it was compiled with. [`PirEngine`] erases those parameters behind an object-safe,
+//! byte-oriented interface (using the formats of [`crate::pir::wire`]), and an [`EngineRegistry`]
+//! maps a [`RespireParamsExpanded`] value, e.g. loaded from a file at startup, to the matching
+//! precompiled engine. A single process can then host databases of several shapes at once.
+//!
+//! [`RespireImpl`]: crate::pir::respire::RespireImpl
+
+use crate::pir::noise_check::{check_noise, NoiseCheck};
+use crate::pir::pir::{PIRRecordBytes, PirError, PIR};
+use crate::pir::respire::{Respire, RespireParamsExpanded};
+use crate::pir::wire::{
+    decode_public_params, decode_query, decode_query_key, decode_response, encode_public_params,
+    encode_query, encode_query_key, encode_response,
+};
+use rand_chacha::ChaCha20Rng;
+use std::any::Any;
+use std::collections::HashMap;
+use std::io;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use zeroize::Zeroizing;
+
+///
+/// A value produced by one engine and only usable with that same engine (i.e. with the same
+/// parameter fingerprint).
+///
+struct EngineHandle {
+    fingerprint: u64,
+    inner: Box<dyn Any + Send + Sync>,
+}
+
+impl EngineHandle {
+    fn new<T: Any + Send + Sync>(fingerprint: u64, value: T) -> Self {
+        Self {
+            fingerprint,
+            inner: Box::new(value),
+        }
+    }
+
+    fn get<T: Any>(&self, fingerprint: u64) -> Result<&T, PirError> {
+        if self.fingerprint != fingerprint {
+            return Err(PirError::ParamsMismatch {
+                expected: fingerprint,
+                found: self.fingerprint,
+            });
+        }
+        Ok(self.inner.downcast_ref().unwrap())
+    }
+
+    fn get_mut<T: Any>(&mut self, fingerprint: u64) -> Result<&mut T, PirError> {
+        if self.fingerprint != fingerprint {
+            return Err(PirError::ParamsMismatch {
+                expected: fingerprint,
+                found: self.fingerprint,
+            });
+        }
+        Ok(self.inner.downcast_mut().unwrap())
+    }
+}
+
+// Each kind of value gets its own handle type, so that e.g. a query key cannot be passed where a
+// database is expected: an engine only has to check the fingerprint, and its downcast cannot fail.
+macro_rules! engine_handle_type {
+    ($(#[$attr: meta])* $name: ident) => {
+        $(#[$attr])*
+        pub struct $name(EngineHandle);
+
+        impl $name {
+            /// The parameter fingerprint of the engine that created this handle.
+            pub fn fingerprint(&self) -> u64 {
+                self.0.fingerprint
+            }
+        }
+    };
+}
+
+engine_handle_type!(
+    /// An encoded (or loaded) database.
+    EngineDatabase
+);
+engine_handle_type!(
+    /// The client's secret query key.
+    EngineQueryKey
+);
+engine_handle_type!(
+    /// Expanded public params, ready to answer queries with.
+    EnginePublicParams
+);
+
+///
+/// Object-safe interface to one precompiled parameter set. Queries, responses and public params are
+/// exchanged as bytes; everything else is an opaque handle
+/// ([`EngineDatabase`], [`EngineQueryKey`] or [`EnginePublicParams`]).
+///
+pub trait PirEngine: Send + Sync {
+    fn params(&self) -> RespireParamsExpanded;
+    fn num_records(&self) -> usize;
+    fn bytes_per_record(&self) -> usize;
+    fn batch_size(&self) -> usize;
+
+    fn encode_db(
+        &self,
+        records_generator: &dyn Fn(usize) -> Vec<u8>,
+    ) -> Result<EngineDatabase, PirError>;
+    fn save_db(&self, db: &EngineDatabase, path: &Path) -> io::Result<()>;
+    fn load_db(&self, path: &Path) -> io::Result<EngineDatabase>;
+    /// See [`PIR::update_records`].
+    fn update_records(
+        &self,
+        db: &mut EngineDatabase,
+        records: &[(usize, Vec<u8>)],
+    ) -> Result<(), PirError>;
+
+    /// Generates a query key and the serialized (seeded) public params to send to the server.
+    fn setup(&self) -> (EngineQueryKey, Vec<u8>);
+    fn load_public_params(&self, bytes: &[u8]) -> Result<EnginePublicParams, PirError>;
+    /// Serializes the query key, so that it can be stored and reused with the same public params.
+    fn save_query_key(&self, qk: &EngineQueryKey) -> Result<Zeroizing<Vec<u8>>, PirError>;
+    fn load_query_key(&self, bytes: &[u8]) -> Result<EngineQueryKey, PirError>;
+
+    fn query(&self, qk: &EngineQueryKey, indices: &[usize]) -> Result<Vec<u8>, PirError>;
+    fn answer(
+        &self,
+        pp: &EnginePublicParams,
+        db: &EngineDatabase,
+        query: &[u8],
+    ) -> Result<Vec<u8>, PirError>;
+    /// Decrypts the response to a query of `num_requested` records.
+    fn extract(
+        &self,
+        qk: &EngineQueryKey,
+        response: &[u8],
+        num_requested: usize,
+    ) -> Result<Vec<Vec<u8>>, PirError>;
+
+    /// See [`check_noise`].
+    fn check_noise(
+        &self,
+        num_queries: usize,
+        rng: &mut ChaCha20Rng,
+    ) -> Result<NoiseCheck, PirError>;
+}
+
+///
+/// The [`PirEngine`] backed by the monomorphized RESPIRE implementation `R`.
+///
+pub struct RespireEngine<R> {
+    phantom: PhantomData<fn() -> R>,
+}
+
+impl<R> RespireEngine<R> {
+    pub fn new() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<R> Default for RespireEngine<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R> PirEngine for RespireEngine<R>
+where
+    R: Respire
+        + PIR<
+            Query = Vec<<R as Respire>::QueryOne>,
+            Response = Vec<<R as Respire>::AnswerOneCompressed>,
+            DatabaseHint = (),
+            State = usize,
+        >,
+    R::QueryKey: Send + Sync + 'static,
+    R::PublicParams: Send + Sync + 'static,
+{
+    fn params(&self) -> RespireParamsExpanded {
+        R::params()
+    }
+
+    fn num_records(&self) -> usize {
+        R::NUM_RECORDS
+    }
+
+    fn bytes_per_record(&self) -> usize {
+        R::BYTES_PER_RECORD
+    }
+
+    fn batch_size(&self) -> usize {
+        R::BATCH_SIZE
+    }
+
+    fn encode_db(
+        &self,
+        records_generator: &dyn Fn(usize) -> Vec<u8>,
+    ) -> Result<EngineDatabase, PirError> {
+        // `encode_db` cannot fail, so remember the first mismatched record and fill in a zero
+        // record instead
+        let error = Mutex::new(None);
+        let (db, ()) = R::encode_db(
+            |i| {
+                let record = records_generator(i);
+                R::RecordBytes::from_bytes(&record).unwrap_or_else(|| {
+                    error
+                        .lock()
+                        .unwrap()
+                        .get_or_insert(PirError::RecordSizeMismatch {
+                            expected: R::BYTES_PER_RECORD,
+                            found: record.len(),
+                        });
+                    R::RecordBytes::from_bytes(&vec![0_u8; R::BYTES_PER_RECORD]).unwrap()
+                })
+            },
+            None,
+        );
+        if let Some(e) = error.into_inner().unwrap() {
+            return Err(e);
+        }
+        Ok(EngineDatabase(EngineHandle::new(
+            R::params().fingerprint(),
+            db,
+        )))
+    }
+
+    fn save_db(&self, db: &EngineDatabase, path: &Path) -> io::Result<()> {
+        let db =
+            db.0.get(R::params().fingerprint())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        R::save_db(db, &(), path)
+    }
+
+    fn load_db(&self, path: &Path) -> io::Result<EngineDatabase> {
+        let (db, ()) = R::load_db(path)?;
+        Ok(EngineDatabase(EngineHandle::new(
+            R::params().fingerprint(),
+            db,
+        )))
+    }
+
+    fn update_records(
+        &self,
+        db: &mut EngineDatabase,
+        records: &[(usize, Vec<u8>)],
+    ) -> Result<(), PirError> {
+        let records = records
+            .iter()
+            .map(|(idx, record)| {
+                let record =
+                    R::RecordBytes::from_bytes(record).ok_or(PirError::RecordSizeMismatch {
+                        expected: R::BYTES_PER_RECORD,
+                        found: record.len(),
+                    })?;
+                Ok((*idx, record))
+            })
+            .collect::<Result<Vec<_>, PirError>>()?;
+        R::update_records(
+            db.0.get_mut(R::params().fingerprint())?,
+            &(),
+            &records,
+            None,
+        )
+    }
+
+    fn setup(&self) -> (EngineQueryKey, Vec<u8>) {
+        let (qk, pp_seeded) = R::setup_seeded(None);
+        (
+            EngineQueryKey(EngineHandle::new(R::params().fingerprint(), qk)),
+            encode_public_params::<R>(&pp_seeded),
+        )
+    }
+
+    fn load_public_params(&self, bytes: &[u8]) -> Result<EnginePublicParams, PirError> {
+        let pp_seeded = decode_public_params::<R>(bytes)?;
+        Ok(EnginePublicParams(EngineHandle::new(
+            R::params().fingerprint(),
+            R::public_params_expand(&pp_seeded),
+        )))
+    }
+
+    fn save_query_key(&self, qk: &EngineQueryKey) -> Result<Zeroizing<Vec<u8>>, PirError> {
+        Ok(encode_query_key::<R>(qk.0.get(R::params().fingerprint())?))
+    }
+
+    fn load_query_key(&self, bytes: &[u8]) -> Result<EngineQueryKey, PirError> {
+        Ok(EngineQueryKey(EngineHandle::new(
+            R::params().fingerprint(),
+            decode_query_key::<R>(bytes)?,
+        )))
+    }
+
+    fn query(&self, qk: &EngineQueryKey, indices: &[usize]) -> Result<Vec<u8>, PirError> {
+        let (q, _) = R::query(qk.0.get(R::params().fingerprint())?, indices, &(), None)?;
+        Ok(encode_query::<R>(&q))
+    }
+
+    fn answer(
+        &self,
+        pp: &EnginePublicParams,
+        db: &EngineDatabase,
+        query: &[u8],
+    ) -> Result<Vec<u8>, PirError> {
+        let fingerprint = R::params().fingerprint();
+        let q = decode_query::<R>(query)?;
+        let r = R::answer(pp.0.get(fingerprint)?, db.0.get(fingerprint)?, &q, None)?;
+        Ok(encode_response::<R>(&r))
+    }
+
+    fn extract(
+        &self,
+        qk: &EngineQueryKey,
+        response: &[u8],
+        num_requested: usize,
+    ) -> Result<Vec<Vec<u8>>, PirError> {
+        let r = decode_response::<R>(response)?;
+        let records = R::extract(
+            qk.0.get(R::params().fingerprint())?,
+            &r,
+            &num_requested,
+            None,
+        )?;
+        Ok(records.iter().map(|r| r.as_bytes().to_vec()).collect())
+    }
+
+    fn check_noise(
+        &self,
+        num_queries: usize,
+        rng: &mut ChaCha20Rng,
+    ) -> Result<NoiseCheck, PirError> {
+        check_noise::<R, _>(num_queries, rng)
+    }
+}
+
+///
+/// The set of parameter sets compiled into this binary, keyed by parameter fingerprint. Use
+/// [`engine_registry!`](crate::engine_registry) to build one from a list of parameter constants.
+///
+#[derive(Default)]
+pub struct EngineRegistry {
+    engines: HashMap<u64, Arc<dyn PirEngine>>,
+}
+
+impl EngineRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<R>(&mut self)
+    where
+        RespireEngine<R>: PirEngine + 'static,
+    {
+        let engine = RespireEngine::<R>::new();
+        self.engines
+            .insert(engine.params().fingerprint(), Arc::new(engine));
+    }
+
+    /// Looks up the engine for the given params, if it was compiled in.
+    pub fn get(&self, params: &RespireParamsExpanded) -> Option<Arc<dyn PirEngine>> {
+        self.engines.get(&params.fingerprint()).cloned()
+    }
+
+    pub fn available_params(&self) -> Vec<RespireParamsExpanded> {
+        self.engines.values().map(|e| e.params()).collect()
+    }
+}
+
+///
+/// Reads a TOML-serialized [`RespireParamsExpanded`] from `path`.
+///
+pub fn load_params(path: &Path) -> io::Result<RespireParamsExpanded> {
+    let contents = std::fs::read_to_string(path)?;
+    toml::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[macro_export]
+macro_rules! engine_registry {
+    ($($params: expr),* $(,)?) => {{
+        let mut registry = $crate::pir::engine::EngineRegistry::new();
+        $(registry.register::<$crate::respire!($params)>();)*
+        registry
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pir::respire_harness::{FactoryParams, RESPIRE_SMALL_PARAMS, RESPIRE_TEST_PARAMS};
+
+    #[test]
+    fn test_registry_lookup() {
+        let registry = engine_registry!(RESPIRE_TEST_PARAMS);
+        let engine = registry.get(&RESPIRE_TEST_PARAMS).unwrap();
+        assert_eq!(engine.params(), RESPIRE_TEST_PARAMS);
+
+        let other = FactoryParams::single_record_256(10, 10).expand().expand();
+        assert!(registry.get(&other).is_none());
+
+        let serialized = toml::to_string(&RESPIRE_TEST_PARAMS).unwrap();
+        let parsed: RespireParamsExpanded = toml::from_str(&serialized).unwrap();
+        assert!(registry.get(&parsed).is_some());
+    }
+
+    #[test]
+    fn test_public_params_through_engine() {
+        let registry = engine_registry!(RESPIRE_TEST_PARAMS);
+        let engine = registry.get(&RESPIRE_TEST_PARAMS).unwrap();
+        let (qk, pp_bytes) = engine.setup();
+        assert_eq!(qk.fingerprint(), RESPIRE_TEST_PARAMS.fingerprint());
+        let pp = engine.load_public_params(&pp_bytes).unwrap();
+        assert_eq!(pp.fingerprint(), RESPIRE_TEST_PARAMS.fingerprint());
+        assert!(engine
+            .load_public_params(&pp_bytes[..pp_bytes.len() - 1])
+            .is_err());
+    }
+
+    #[test]
+    fn test_query_key_through_engine() {
+        let registry = engine_registry!(RESPIRE_TEST_PARAMS);
+        let engine = registry.get(&RESPIRE_TEST_PARAMS).unwrap();
+        let (qk, _) = engine.setup();
+        let bytes = engine.save_query_key(&qk).unwrap();
+        let loaded = engine.load_query_key(&bytes).unwrap();
+        assert_eq!(loaded.fingerprint(), RESPIRE_TEST_PARAMS.fingerprint());
+        assert_eq!(engine.save_query_key(&loaded).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_answer_through_engine() {
+        let registry = engine_registry!(RESPIRE_SMALL_PARAMS);
+        let engine = registry.get(&RESPIRE_SMALL_PARAMS).unwrap();
+        let record = |i: usize| vec![i as u8; engine.bytes_per_record()];
+
+        assert_eq!(
+            engine
+                .encode_db(&|i| if i == 3 { vec![0] } else { record(i) })
+                .err(),
+            Some(PirError::RecordSizeMismatch {
+                expected: engine.bytes_per_record(),
+                found: 1,
+            })
+        );
+        let db = engine.encode_db(&record).unwrap();
+
+        let (qk, pp_bytes) = engine.setup();
+        let pp = engine.load_public_params(&pp_bytes).unwrap();
+        let idx = engine.num_records() - 1;
+        let query = engine.query(&qk, &[idx]).unwrap();
+        let response = engine.answer(&pp, &db, &query).unwrap();
+        assert_eq!(
+            engine.extract(&qk, &response, 1).unwrap(),
+            vec![record(idx)]
+        );
+    }
+}
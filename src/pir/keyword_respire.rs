@@ -0,0 +1,417 @@
+//! Keyword PIR: retrieve values by key rather than by dense index.
+//!
+//! Keys are arbitrary byte strings. Each key is hashed, with SipHash keyed by a seed that the
+//! server picks when encoding, to a 64-bit tag and a 64-bit digest; the digest is mapped to
+//! `NUM_HASH` candidate buckets as in [`CuckooRespireImpl`](crate::pir::cuckoo_respire::CuckooRespireImpl)
+//! (see [`hash_to_buckets`]). Every entry is stored in all of its candidate buckets, each bucket is
+//! a `BaseRespire` database, and a batch of keys is assigned to distinct buckets by cuckoo hashing.
+//!
+//! Each slot holds the key's tag followed by its value. The slot of a key in a bucket is given by a
+//! perfect hash function: the entries of a bucket are split into groups by tag, and each group has
+//! a displacement such that hashing the tags of the group with it puts every entry of the bucket in
+//! its own slot. The database hint is the seed and the displacements (about
+//! `32 / ENTRIES_PER_GROUP` bits per stored entry). Every key, present or not, maps to some slot,
+//! so the hint does not tell which keys are in the database, and the server cannot tell a query
+//! for a missing key apart from one for a present key. On extraction the client compares the
+//! retrieved tag with the key's tag and reports a mismatch as "not found".
+
+use crate::pir::cuckoo_respire::{
+    answer_buckets, cuckoo_assign, extract_buckets, hash_to_buckets, CuckooSeed, CUCKOO_ATTEMPTS,
+    CUCKOO_MAX_DEPTH,
+};
+use crate::pir::database::SimdDatabase;
+use crate::pir::pir::{PIRRecordBytes, PirError, Stats, PIR};
+use crate::pir::respire::Respire;
+use itertools::Itertools;
+use log::info;
+use rand::{thread_rng, CryptoRng, Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use siphasher::sip::SipHasher13;
+use siphasher::sip128::{Hasher128, SipHasher13 as SipHasher13_128};
+use std::hash::Hasher;
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+/// Number of bytes at the start of each slot that hold the key tag.
+pub const KEY_TAG_BYTES: usize = 8;
+
+/// Average number of entries of a bucket that share a displacement.
+pub const ENTRIES_PER_GROUP: usize = 4;
+
+/// Largest displacement tried for a group before encoding fails.
+const MAX_DISPLACEMENT: u32 = 1 << 24;
+
+///
+/// What a client needs to locate keys: the hash seed, and for each bucket the displacements of its
+/// groups (see the module documentation).
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeywordDatabaseHint {
+    pub seed: CuckooSeed,
+    /// `displacements[b][g]` is the displacement of group `g` of bucket `b`
+    displacements: Vec<Vec<u32>>,
+}
+
+///
+/// Client state between `query` and `extract`.
+///
+pub struct KeywordQueryState {
+    /// (bucket index, batch index) pairs
+    cuckoo_mapping: Vec<(usize, usize)>,
+    tags: Vec<u64>,
+}
+
+pub struct KeywordRespireImpl<
+    const BATCH_SIZE: usize,
+    const NUM_BUCKET: usize,
+    BaseRespire: PIR + Respire,
+    const NUM_HASH: usize = 3,
+> {
+    phantom: PhantomData<BaseRespire>,
+}
+
+impl<
+        const BATCH_SIZE: usize,
+        const NUM_BUCKET: usize,
+        BaseRespire: PIR + Respire,
+        const NUM_HASH: usize,
+    > KeywordRespireImpl<BATCH_SIZE, NUM_BUCKET, BaseRespire, NUM_HASH>
+{
+    pub const BATCH_SIZE: usize = BATCH_SIZE;
+    pub const NUM_BUCKET: usize = NUM_BUCKET;
+    /// Number of bytes in each value.
+    pub const VALUE_BYTES: usize = BaseRespire::BYTES_PER_RECORD - KEY_TAG_BYTES;
+
+    ///
+    /// Encodes the given (key, value) entries with a random hash seed. Every value must have
+    /// exactly [`Self::VALUE_BYTES`] bytes, and keys must be distinct.
+    ///
+    pub fn encode_db<K: AsRef<[u8]>, V: AsRef<[u8]>>(
+        entries: &[(K, V)],
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<(Vec<SimdDatabase>, KeywordDatabaseHint), PirError> {
+        Self::encode_db_with_seed(thread_rng().gen(), entries, time_stats)
+    }
+
+    ///
+    /// Encodes the database with the given hash seed (see [`Self::encode_db`], which picks a random
+    /// seed).
+    ///
+    pub fn encode_db_with_seed<K: AsRef<[u8]>, V: AsRef<[u8]>>(
+        seed: CuckooSeed,
+        entries: &[(K, V)],
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<(Vec<SimdDatabase>, KeywordDatabaseHint), PirError> {
+        let begin = Instant::now();
+        let mut buckets: Vec<Vec<(u64, &[u8])>> = vec![Vec::new(); Self::NUM_BUCKET];
+        for (key, value) in entries {
+            let value = value.as_ref();
+            if value.len() != Self::VALUE_BYTES {
+                return Err(PirError::RecordSizeMismatch {
+                    expected: Self::VALUE_BYTES,
+                    found: value.len(),
+                });
+            }
+            let (candidates, tag) = Self::hash_key(&seed, key.as_ref());
+            for b in candidates.into_iter().unique() {
+                buckets[b].push((tag, value));
+            }
+        }
+
+        let mut result = Vec::with_capacity(Self::NUM_BUCKET);
+        let mut displacements = Vec::with_capacity(Self::NUM_BUCKET);
+        for (b_idx, bucket) in buckets.into_iter().enumerate() {
+            if bucket.len() > BaseRespire::DB_SIZE {
+                return Err(PirError::BucketOverflow {
+                    bucket: b_idx,
+                    size: bucket.len(),
+                    capacity: BaseRespire::DB_SIZE,
+                });
+            }
+            let tags = bucket.iter().map(|(tag, _)| *tag).collect_vec();
+            if tags
+                .iter()
+                .sorted_unstable()
+                .tuple_windows()
+                .any(|(a, b)| a == b)
+            {
+                return Err(PirError::DuplicateKey);
+            }
+            info!("Encoding bucket {} of {}...", b_idx + 1, Self::NUM_BUCKET);
+            let (bucket_displacements, slots) = Self::bucket_layout(&seed, &tags)
+                .ok_or(PirError::BucketLayoutFailed { bucket: b_idx })?;
+            let slot_generator = |i: usize| {
+                let mut slot = vec![0_u8; BaseRespire::BYTES_PER_RECORD];
+                if let Some(entry_idx) = slots[i] {
+                    let (tag, value) = bucket[entry_idx];
+                    slot[..KEY_TAG_BYTES].copy_from_slice(&tag.to_le_bytes());
+                    slot[KEY_TAG_BYTES..].copy_from_slice(value);
+                }
+                BaseRespire::RecordBytes::from_bytes(&slot).unwrap()
+            };
+            result.push(BaseRespire::encode_db(slot_generator, None).0);
+            displacements.push(bucket_displacements);
+        }
+
+        let end = Instant::now();
+        if let Some(time_stats) = time_stats {
+            time_stats.add("encode", end - begin);
+        }
+        Ok((
+            result,
+            KeywordDatabaseHint {
+                seed,
+                displacements,
+            },
+        ))
+    }
+
+    pub fn setup(
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> (BaseRespire::QueryKey, BaseRespire::PublicParams) {
+        BaseRespire::setup(time_stats)
+    }
+
+    pub fn query<K: AsRef<[u8]>>(
+        qk: &BaseRespire::QueryKey,
+        keys: &[K],
+        hint: &KeywordDatabaseHint,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<(Vec<BaseRespire::QueryOne>, KeywordQueryState), PirError> {
+        Self::query_with_rng(qk, keys, hint, &mut ChaCha20Rng::from_entropy(), time_stats)
+    }
+
+    ///
+    /// Like [`Self::query`], but drawing all randomness (of the cuckoo insertion and of the
+    /// encryption) from `rng`.
+    ///
+    pub fn query_with_rng<K: AsRef<[u8]>, R: RngCore + CryptoRng>(
+        qk: &BaseRespire::QueryKey,
+        keys: &[K],
+        hint: &KeywordDatabaseHint,
+        rng: &mut R,
+        mut time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<(Vec<BaseRespire::QueryOne>, KeywordQueryState), PirError> {
+        let cuckoo_begin = Instant::now();
+        if keys.len() != Self::BATCH_SIZE {
+            return Err(PirError::BatchSizeMismatch {
+                expected: Self::BATCH_SIZE,
+                found: keys.len(),
+            });
+        }
+        if hint.displacements.len() != Self::NUM_BUCKET {
+            return Err(PirError::InvalidDatabaseHint);
+        }
+        let (candidates, tags): (Vec<_>, Vec<_>) = keys
+            .iter()
+            .map(|key| Self::hash_key(&hint.seed, key.as_ref()))
+            .unzip();
+        let cuckoo_mapping = (0..CUCKOO_ATTEMPTS)
+            .find_map(|_| cuckoo_assign(&candidates, CUCKOO_MAX_DEPTH, rng))
+            .ok_or(PirError::CuckooInsertionFailed)?;
+
+        // Buckets without a key of the batch query slot 0
+        let mut slots = vec![0usize; Self::NUM_BUCKET];
+        for (bucket_idx, batch_idx) in cuckoo_mapping.iter().copied() {
+            slots[bucket_idx] =
+                Self::key_slot(&hint.seed, &hint.displacements[bucket_idx], tags[batch_idx]);
+        }
+        let cuckoo_end = Instant::now();
+        if let Some(time_stats) = time_stats.as_deref_mut() {
+            time_stats.add("query_cuckoo", cuckoo_end - cuckoo_begin);
+        }
+
+        let q = slots
+            .iter()
+            .copied()
+            .map(|slot| BaseRespire::query_one_with_rng(qk, slot, rng, time_stats.as_deref_mut()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((
+            q,
+            KeywordQueryState {
+                cuckoo_mapping,
+                tags,
+            },
+        ))
+    }
+
+    pub fn answer(
+        pp: &BaseRespire::PublicParams,
+        dbs: &[SimdDatabase],
+        qs: &[BaseRespire::QueryOne],
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<Vec<BaseRespire::AnswerOneCompressed>, PirError> {
+        if qs.len() != Self::NUM_BUCKET {
+            return Err(PirError::BatchSizeMismatch {
+                expected: Self::NUM_BUCKET,
+                found: qs.len(),
+            });
+        }
+        answer_buckets::<BaseRespire>(pp, dbs, qs, time_stats)
+    }
+
+    ///
+    /// Returns the value of each queried key, or `None` if the key is not in the database.
+    ///
+    pub fn extract(
+        qk: &BaseRespire::QueryKey,
+        r: &[BaseRespire::AnswerOneCompressed],
+        state: &KeywordQueryState,
+        mut time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<Vec<Option<Vec<u8>>>, PirError> {
+        let result_by_bucket =
+            extract_buckets::<BaseRespire>(qk, r, Self::NUM_BUCKET, time_stats.as_deref_mut())?;
+
+        let uncuckoo_begin = Instant::now();
+        let mut result = vec![None; Self::BATCH_SIZE];
+        for (bucket_idx, batch_idx) in state.cuckoo_mapping.iter().copied() {
+            let slot = result_by_bucket[bucket_idx].as_bytes();
+            let tag = u64::from_le_bytes(slot[..KEY_TAG_BYTES].try_into().unwrap());
+            if tag == state.tags[batch_idx] {
+                result[batch_idx] = Some(slot[KEY_TAG_BYTES..].to_vec());
+            }
+        }
+        let uncuckoo_end = Instant::now();
+        if let Some(time_stats) = time_stats {
+            time_stats.add("extract_uncuckoo", uncuckoo_end - uncuckoo_begin);
+        }
+        Ok(result)
+    }
+
+    ///
+    /// The `NUM_HASH` candidate buckets of `key`, and its tag. The key is hashed with SipHash-1-3
+    /// (128 bit output) keyed by `seed`; the first half is passed to [`hash_to_buckets`] and the
+    /// second half is the tag. Tags are never 0, so that an empty slot never matches a key.
+    ///
+    pub fn hash_key(seed: &CuckooSeed, key: &[u8]) -> ([usize; NUM_HASH], u64) {
+        let mut hasher = SipHasher13_128::new_with_key(seed);
+        hasher.write(key);
+        let hashed = hasher.finish128();
+        let tag = hashed.h2.max(1);
+        (hash_to_buckets(seed, hashed.h1, Self::NUM_BUCKET), tag)
+    }
+
+    ///
+    /// The slot of the entry with tag `tag` in a bucket with the given group displacements. Any
+    /// tag maps to some slot; slot 0 for an empty bucket.
+    ///
+    fn key_slot(seed: &CuckooSeed, displacements: &[u32], tag: u64) -> usize {
+        if displacements.is_empty() {
+            return 0;
+        }
+        let group = (tag % displacements.len() as u64) as usize;
+        Self::displaced_slot(seed, tag, displacements[group])
+    }
+
+    ///
+    /// Slot `displacement` of tag `tag`: SipHash-1-3 keyed by `seed` applied to the little endian
+    /// encodings of `tag` (64 bits) and `displacement` (32 bits), reduced mod the bucket size.
+    ///
+    fn displaced_slot(seed: &CuckooSeed, tag: u64, displacement: u32) -> usize {
+        let mut hasher = SipHasher13::new_with_key(seed);
+        hasher.write(&tag.to_le_bytes());
+        hasher.write(&displacement.to_le_bytes());
+        (hasher.finish() % BaseRespire::DB_SIZE as u64) as usize
+    }
+
+    ///
+    /// Finds displacements placing the entries with the given (distinct) tags in distinct slots of
+    /// a bucket. Groups are placed largest first, each with the smallest displacement that puts
+    /// all of its entries in free slots. Returns the displacements, and the entry index in each
+    /// slot, or `None` if some group could not be placed.
+    ///
+    fn bucket_layout(seed: &CuckooSeed, tags: &[u64]) -> Option<(Vec<u32>, Vec<Option<usize>>)> {
+        let num_groups = tags.len().div_ceil(ENTRIES_PER_GROUP);
+        let mut groups = vec![Vec::new(); num_groups];
+        for (entry_idx, tag) in tags.iter().enumerate() {
+            groups[(tag % num_groups as u64) as usize].push(entry_idx);
+        }
+        let mut displacements = vec![0_u32; num_groups];
+        let mut slots = vec![None; BaseRespire::DB_SIZE];
+        let mut group_slots = Vec::with_capacity(ENTRIES_PER_GROUP);
+        for group in (0..num_groups).sorted_by_key(|&g| std::cmp::Reverse(groups[g].len())) {
+            let placed = (0..=MAX_DISPLACEMENT).find(|&displacement| {
+                group_slots.clear();
+                for &entry_idx in &groups[group] {
+                    let slot = Self::displaced_slot(seed, tags[entry_idx], displacement);
+                    if slots[slot].is_some() || group_slots.contains(&slot) {
+                        return false;
+                    }
+                    group_slots.push(slot);
+                }
+                true
+            })?;
+            displacements[group] = placed;
+            for (&entry_idx, &slot) in groups[group].iter().zip(&group_slots) {
+                slots[slot] = Some(entry_idx);
+            }
+        }
+        Some((displacements, slots))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pir::respire_harness::RespireSmallBatch;
+
+    type KeywordPIR = KeywordRespireImpl<2, 4, RespireSmallBatch>;
+
+    fn entry(i: u8) -> ([u8; 32], Vec<u8>) {
+        ([i; 32], vec![i; KeywordPIR::VALUE_BYTES])
+    }
+
+    #[test]
+    fn test_keyword_lookup() {
+        let entries = (0..10).map(entry).collect_vec();
+        let (db, hint) = KeywordPIR::encode_db(&entries, None).unwrap();
+        let (qk, pp) = KeywordPIR::setup(None);
+
+        let keys = [entries[3].0, [100_u8; 32]];
+        let (q, state) = KeywordPIR::query(&qk, &keys, &hint, None).unwrap();
+        let r = KeywordPIR::answer(&pp, &db, &q, None).unwrap();
+        let values = KeywordPIR::extract(&qk, &r, &state, None).unwrap();
+        assert_eq!(values, vec![Some(entries[3].1.clone()), None]);
+    }
+
+    #[test]
+    fn test_keyword_lookup_two_hashes() {
+        type KeywordPIR2 = KeywordRespireImpl<2, 4, RespireSmallBatch, 2>;
+        let entries = (0..10).map(entry).collect_vec();
+        let (db, hint) = KeywordPIR2::encode_db_with_seed([7; 16], &entries, None).unwrap();
+        // One displacement per group of entries, rather than a tag per entry
+        for bucket in &hint.displacements {
+            assert!(bucket.len() <= entries.len().div_ceil(ENTRIES_PER_GROUP));
+        }
+        let (qk, pp) = KeywordPIR2::setup(None);
+
+        let keys = [entries[0].0, entries[9].0];
+        let (q, state) = KeywordPIR2::query(&qk, &keys, &hint, None).unwrap();
+        let r = KeywordPIR2::answer(&pp, &db, &q, None).unwrap();
+        let values = KeywordPIR2::extract(&qk, &r, &state, None).unwrap();
+        assert_eq!(
+            values,
+            vec![Some(entries[0].1.clone()), Some(entries[9].1.clone())]
+        );
+    }
+
+    #[test]
+    fn test_keyword_invalid_entries() {
+        assert_eq!(
+            KeywordPIR::encode_db(&[entry(1), entry(1)], None).err(),
+            Some(PirError::DuplicateKey)
+        );
+        assert_eq!(
+            KeywordPIR::encode_db(&[([1_u8; 32], vec![0_u8; 3])], None).err(),
+            Some(PirError::RecordSizeMismatch {
+                expected: KeywordPIR::VALUE_BYTES,
+                found: 3
+            })
+        );
+        let too_many = (0..=255).map(entry).collect_vec();
+        assert!(matches!(
+            KeywordPIR::encode_db(&too_many, None),
+            Err(PirError::BucketOverflow { .. })
+        ));
+    }
+}
@@ -1,7 +1,18 @@
 //! PIR (Private Information Retrieval) constructs.
 pub mod cuckoo_respire;
+pub mod database;
+pub mod engine;
+pub mod keyword_respire;
+pub mod net;
 pub mod noise;
+pub mod noise_check;
+pub mod parallel;
+pub mod param_search;
+pub mod param_store;
 pub mod pir;
+pub mod record_source;
 pub mod respire;
 pub mod respire_harness;
 pub mod respire_harness_32;
+pub mod varlen;
+pub mod wire;
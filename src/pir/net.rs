@@ -0,0 +1,613 @@
+//! Length-prefixed TCP protocol between `respire-server` and `respire-client`.
+//!
+//! Every frame, in either direction, starts with a fixed [`FRAME_HEADER_SIZE`] byte header:
+//!
+//! | bytes | field                                  |
+//! |-------|----------------------------------------|
+//! | 0     | frame kind ([`FrameKind`])             |
+//! | 1..5  | payload length, little endian          |
+//!
+//! A request payload is the client's [`ClientId`] (8 bytes, little endian) followed by a message in
+//! the format of [`crate::pir::wire`]: seeded public params for [`FrameKind::Register`], a query for
+//! [`FrameKind::Query`]. The server replies to every request, in order, with either
+//! [`FrameKind::Ok`] (whose payload is empty resp. the encoded response) or [`FrameKind::Error`]
+//! (whose payload is a UTF-8 error message). A connection may carry any number of requests.
+//!
+//! Each side rejects frames longer than the largest message it expects for the parameters (see
+//! [`max_request_size`] and [`max_reply_size`]), and reads payloads incrementally, so a peer
+//! cannot make it allocate more than it actually sends.
+//!
+//! The client id is not authenticated: any peer can register public params under any id,
+//! overwriting those of the client that uses it, which makes that client's later responses
+//! undecryptable. Ids should be hard to guess (the client picks a random one by default), and the
+//! server should only be reachable by trusted clients.
+
+use crate::pir::param_store::{ClientId, ParamStore, ParamStoreError};
+use crate::pir::pir::PIRRecordBytes;
+use crate::pir::pir::{PirError, Stats, PIR};
+use crate::pir::record_source::synthetic_record;
+use crate::pir::respire::Respire;
+use crate::pir::wire::{
+    self, decode_query, decode_response, encode_public_params, encode_query, encode_response,
+};
+use clap::Parser;
+use itertools::Itertools;
+use log::{info, warn};
+use rand::random;
+use std::fmt;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::marker::PhantomData;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+pub const FRAME_HEADER_SIZE: usize = 5;
+
+/// Largest payload of any frame.
+pub const MAX_FRAME_SIZE: usize = 1 << 30;
+
+/// Largest error message payload.
+pub const MAX_ERROR_SIZE: usize = 1 << 12;
+
+/// Number of bytes a payload buffer starts with; it then grows with the bytes actually received.
+const READ_CHUNK_SIZE: usize = 1 << 16;
+
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrameKind {
+    Register = 1,
+    Query = 2,
+    Ok = 0x80,
+    Error = 0x81,
+}
+
+impl TryFrom<u8> for FrameKind {
+    type Error = io::Error;
+
+    fn try_from(value: u8) -> Result<Self, io::Error> {
+        match value {
+            1 => Ok(FrameKind::Register),
+            2 => Ok(FrameKind::Query),
+            0x80 => Ok(FrameKind::Ok),
+            0x81 => Ok(FrameKind::Error),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown frame kind {}", value),
+            )),
+        }
+    }
+}
+
+///
+/// Writes a single frame whose payload is the concatenation of `parts`.
+///
+pub fn write_frame<W: Write>(w: &mut W, kind: FrameKind, parts: &[&[u8]]) -> io::Result<()> {
+    let len: usize = parts.iter().map(|p| p.len()).sum();
+    if len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "frame of {} bytes exceeds the maximum of {}",
+                len, MAX_FRAME_SIZE
+            ),
+        ));
+    }
+    let mut header = [0_u8; FRAME_HEADER_SIZE];
+    header[0] = kind as u8;
+    header[1..].copy_from_slice(&(len as u32).to_le_bytes());
+    w.write_all(&header)?;
+    for part in parts {
+        w.write_all(part)?;
+    }
+    w.flush()
+}
+
+///
+/// Largest request payload a server for `R` accepts: a client id followed by the seeded public
+/// params or a query for a full batch.
+///
+pub fn max_request_size<R: Respire>() -> usize {
+    let public_params = wire::HEADER_SIZE + R::params_public_param_size();
+    let query = wire::HEADER_SIZE + R::BATCH_SIZE * R::params_query_one_size();
+    8 + public_params.max(query)
+}
+
+///
+/// Largest reply payload a client for `R` accepts: the response to a full batch, or an error
+/// message.
+///
+pub fn max_reply_size<R: Respire>() -> usize {
+    let num_responses = R::BATCH_SIZE.div_ceil(R::RESPONSE_CHUNK_SIZE);
+    let response = wire::HEADER_SIZE + num_responses * (1 + R::params_response_one_size(R::N_VEC));
+    response.max(MAX_ERROR_SIZE)
+}
+
+///
+/// Reads a single frame with a payload of at most `max_len` bytes. Returns `None` if the peer
+/// closed the connection before the next frame.
+///
+pub fn read_frame<R: Read>(r: &mut R, max_len: usize) -> io::Result<Option<(FrameKind, Vec<u8>)>> {
+    let mut header = [0_u8; FRAME_HEADER_SIZE];
+    match r.read_exact(&mut header[..1]) {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    r.read_exact(&mut header[1..])?;
+    let kind = FrameKind::try_from(header[0])?;
+    let len = u32::from_le_bytes(header[1..].try_into().unwrap()) as usize;
+    if len > max_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {} bytes exceeds the maximum of {}", len, max_len),
+        ));
+    }
+    let mut payload = Vec::with_capacity(len.min(READ_CHUNK_SIZE));
+    r.take(len as u64).read_to_end(&mut payload)?;
+    if payload.len() != len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
+    Ok(Some((kind, payload)))
+}
+
+fn split_client_id(payload: &[u8]) -> Result<(ClientId, &[u8]), io::Error> {
+    if payload.len() < 8 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "request is missing the client id",
+        ));
+    }
+    let (id, rest) = payload.split_at(8);
+    Ok((ClientId::from_le_bytes(id.try_into().unwrap()), rest))
+}
+
+#[derive(Debug)]
+pub enum NetError {
+    Io(io::Error),
+    /// The server rejected the request with the given message.
+    Remote(String),
+    Pir(PirError),
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetError::Io(e) => write!(f, "connection error: {}", e),
+            NetError::Remote(msg) => write!(f, "server error: {}", msg),
+            NetError::Pir(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for NetError {}
+
+impl From<io::Error> for NetError {
+    fn from(e: io::Error) -> Self {
+        NetError::Io(e)
+    }
+}
+
+impl From<PirError> for NetError {
+    fn from(e: PirError) -> Self {
+        NetError::Pir(e)
+    }
+}
+
+///
+/// Answers the requests of any number of clients against a single database, keeping their
+/// public params in a [`ParamStore`]. Clients are identified by their unauthenticated id only, so
+/// any peer can replace the public params of another client by registering under its id (see the
+/// module documentation).
+///
+pub struct PirServer<R: Respire> {
+    store: ParamStore<R>,
+    db: R::Database,
+}
+
+impl<R> PirServer<R>
+where
+    R: Respire
+        + PIR<
+            Query = Vec<<R as Respire>::QueryOne>,
+            Response = Vec<<R as Respire>::AnswerOneCompressed>,
+        >,
+    R::PublicParams: Send,
+{
+    pub fn new(store: ParamStore<R>, db: R::Database) -> Self {
+        Self { store, db }
+    }
+
+    ///
+    /// Accepts connections on `listener` forever, serving each on its own thread. Failures to
+    /// accept a connection are logged and do not stop the server.
+    ///
+    pub fn serve(&self, listener: &TcpListener) {
+        std::thread::scope(|s| {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        warn!("failed to accept connection: {}", e);
+                        continue;
+                    }
+                };
+                s.spawn(move || {
+                    let peer = stream.peer_addr().ok();
+                    if let Err(e) = self.serve_connection(stream) {
+                        warn!("connection from {:?} failed: {}", peer, e);
+                    }
+                });
+            }
+        })
+    }
+
+    ///
+    /// Serves requests on `stream` until the client disconnects.
+    ///
+    pub fn serve_connection(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+        while let Some((kind, payload)) = read_frame(&mut reader, max_request_size::<R>())? {
+            match self.handle(kind, &payload)? {
+                Ok(reply) => write_frame(&mut writer, FrameKind::Ok, &[&reply])?,
+                Err(e) => {
+                    let msg = e.to_string();
+                    let msg = &msg.as_bytes()[..msg.len().min(MAX_ERROR_SIZE)];
+                    write_frame(&mut writer, FrameKind::Error, &[msg])?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Handles a single request frame. Malformed frames are connection errors (`Err`), while
+    /// requests the server cannot satisfy are reported back to the client (`Ok(Err(_))`).
+    ///
+    fn handle(
+        &self,
+        kind: FrameKind,
+        payload: &[u8],
+    ) -> io::Result<Result<Vec<u8>, ParamStoreError>> {
+        let (client, message) = split_client_id(payload)?;
+        let begin = Instant::now();
+        let result = match kind {
+            FrameKind::Register => self.store.register(client, message).map(|()| Vec::new()),
+            FrameKind::Query => self.answer(client, message),
+            FrameKind::Ok | FrameKind::Error => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unexpected {:?} frame from client", kind),
+                ))
+            }
+        };
+        info!(
+            "{:?} from client {:016x}: {} in {:?}",
+            kind,
+            client,
+            if result.is_ok() { "ok" } else { "failed" },
+            Instant::now() - begin
+        );
+        Ok(result)
+    }
+
+    fn answer(&self, client: ClientId, query: &[u8]) -> Result<Vec<u8>, ParamStoreError> {
+        let q = decode_query::<R>(query)?;
+        let r = self.store.answer::<R>(client, &self.db, &q, None)?;
+        Ok(encode_response::<R>(&r))
+    }
+}
+
+///
+/// A connection to a [`PirServer`], on behalf of a single client.
+///
+pub struct PirClient<R> {
+    client: ClientId,
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+    phantom: PhantomData<fn() -> R>,
+}
+
+impl<R> PirClient<R>
+where
+    R: Respire
+        + PIR<
+            Query = Vec<<R as Respire>::QueryOne>,
+            Response = Vec<<R as Respire>::AnswerOneCompressed>,
+        >,
+{
+    pub fn connect<A: ToSocketAddrs>(addr: A, client: ClientId) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            client,
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+            phantom: PhantomData,
+        })
+    }
+
+    ///
+    /// Uploads the client's public params, replacing any registered before under the same id (by
+    /// this client or any other peer).
+    ///
+    pub fn register(&mut self, pp: &R::PublicParamsSeeded) -> Result<(), NetError> {
+        self.request(FrameKind::Register, &encode_public_params::<R>(pp))?;
+        Ok(())
+    }
+
+    ///
+    /// Sends a query and waits for the server's response. If given, `time_stats` records the time
+    /// spent encoding the query, waiting for the server, and decoding the response.
+    ///
+    pub fn query(
+        &mut self,
+        q: &<R as PIR>::Query,
+        mut time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<<R as PIR>::Response, NetError> {
+        let i0 = Instant::now();
+        let bytes = encode_query::<R>(q);
+        let i1 = Instant::now();
+        let reply = self.request(FrameKind::Query, &bytes)?;
+        let i2 = Instant::now();
+        let r = decode_response::<R>(&reply).map_err(PirError::from)?;
+        let i3 = Instant::now();
+        if let Some(ref mut stats) = time_stats {
+            stats.add("query_encode", i1 - i0);
+            stats.add("round_trip", i2 - i1);
+            stats.add("response_decode", i3 - i2);
+        }
+        Ok(r)
+    }
+
+    fn request(&mut self, kind: FrameKind, message: &[u8]) -> Result<Vec<u8>, NetError> {
+        write_frame(
+            &mut self.writer,
+            kind,
+            &[&self.client.to_le_bytes(), message],
+        )?;
+        match read_frame(&mut self.reader, max_reply_size::<R>())? {
+            Some((FrameKind::Ok, reply)) => Ok(reply),
+            Some((FrameKind::Error, msg)) => {
+                Err(NetError::Remote(String::from_utf8_lossy(&msg).into_owned()))
+            }
+            Some((kind, _)) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unexpected {:?} frame from server", kind),
+            )
+            .into()),
+            None => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! generate_server_main {
+    ($name: path) => {
+        fn main() {
+            $crate::pir::net::server_main::<$name>();
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! generate_client_main {
+    ($name: path) => {
+        fn main() {
+            $crate::pir::net::client_main::<$name>();
+        }
+    };
+}
+
+#[derive(Parser, Debug)]
+struct ServerArgs {
+    #[arg(long, default_value = "127.0.0.1:7878")]
+    listen: String,
+    /// Load the encoded database from this file, or save it there if it does not exist yet
+    #[arg(long)]
+    db_file: Option<PathBuf>,
+    /// Directory holding the registered public params of every client
+    #[arg(long, default_value = "respire-params")]
+    params_dir: PathBuf,
+    /// Number of clients whose expanded public params are kept in memory
+    #[arg(long, default_value_t = 16)]
+    max_resident: usize,
+}
+
+///
+/// Serves the synthetic database of the benchmarks over TCP.
+///
+pub fn server_main<R>()
+where
+    R: Respire
+        + PIR<
+            Query = Vec<<R as Respire>::QueryOne>,
+            Response = Vec<<R as Respire>::AnswerOneCompressed>,
+        >,
+    R::PublicParams: Send,
+{
+    env_logger::init();
+    let args = ServerArgs::parse();
+    R::print_summary();
+
+    let begin = Instant::now();
+    let (db, _) = match args.db_file.as_deref() {
+        Some(path) if path.exists() => {
+            eprintln!("Loading DB from {}", path.display());
+            R::load_db(path).expect("failed to load DB file")
+        }
+        db_file => {
+            let record = |i: usize| {
+                R::RecordBytes::from_bytes(&synthetic_record(i, R::BYTES_PER_RECORD)).unwrap()
+            };
+            let encoded = R::encode_db(record, None);
+            if let Some(path) = db_file {
+                eprintln!("Saving DB to {}", path.display());
+                R::save_db(&encoded.0, &encoded.1, path).expect("failed to save DB file");
+            }
+            encoded
+        }
+    };
+    eprintln!("DB ready in {:?}", Instant::now() - begin);
+
+    let store = ParamStore::<R>::new(&args.params_dir, args.max_resident)
+        .expect("failed to open param store");
+    let listener = TcpListener::bind(&args.listen).expect("failed to bind");
+    eprintln!("Listening on {}", listener.local_addr().unwrap());
+    PirServer::<R>::new(store, db).serve(&listener);
+}
+
+#[derive(Parser, Debug)]
+struct ClientArgs {
+    /// Indices of the records to retrieve, in batches of up to the batch size
+    #[arg(required = true)]
+    indices: Vec<usize>,
+    #[arg(long, default_value = "127.0.0.1:7878")]
+    server: String,
+    /// Client id to register the public params under (random if not given)
+    #[arg(long)]
+    client_id: Option<ClientId>,
+}
+
+///
+/// Retrieves records from a [`server_main`] server, printing each record and the time spent in
+/// every phase, including the network round trip.
+///
+pub fn client_main<R>()
+where
+    R: Respire
+        + PIR<
+            Query = Vec<<R as Respire>::QueryOne>,
+            Response = Vec<<R as Respire>::AnswerOneCompressed>,
+            DatabaseHint = (),
+        >,
+{
+    env_logger::init();
+    let args = ClientArgs::parse();
+    if let Some(idx) = args.indices.iter().find(|&&idx| idx >= R::NUM_RECORDS) {
+        eprintln!(
+            "Index {} is out of range (the database has {} records)",
+            idx,
+            R::NUM_RECORDS
+        );
+        std::process::exit(1);
+    }
+
+    let client_id = args.client_id.unwrap_or_else(random);
+    let mut client =
+        PirClient::<R>::connect(&args.server, client_id).expect("failed to connect to server");
+
+    let mut init_times = Stats::new();
+    let (qk, pp) = R::setup_seeded(Some(&mut init_times));
+    let begin = Instant::now();
+    client
+        .register(&pp)
+        .expect("failed to register public params");
+    init_times.add("register", Instant::now() - begin);
+    eprintln!("Registered as client {:016x}", client_id);
+    for (stat, value) in init_times.as_vec() {
+        eprintln!("    {}: {:?}", stat, value);
+    }
+
+    for indices in args.indices.chunks(R::BATCH_SIZE) {
+        let mut trial_times = Stats::new();
+        let (q, state) = R::query(&qk, indices, &(), Some(&mut trial_times)).unwrap();
+        let r = client
+            .query(&q, Some(&mut trial_times))
+            .expect("query failed");
+        let extracted = R::extract(&qk, &r, &state, Some(&mut trial_times)).unwrap();
+        for (idx, record) in indices.iter().zip(extracted) {
+            println!(
+                "{}: {}",
+                idx,
+                record
+                    .as_bytes()
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .join("")
+            );
+        }
+        eprintln!("Trial times:");
+        for (stat, value) in trial_times.as_vec() {
+            eprintln!("    {}: {:?}", stat, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pir::pir::PIRRecordBytes;
+    use crate::pir::record_source::synthetic_record;
+    use crate::pir::respire_harness::{temp_path, RespireSmall};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_frame_round_trip() {
+        let mut buf = Vec::new();
+        write_frame(&mut buf, FrameKind::Query, &[b"ab", b"", b"cde"]).unwrap();
+        write_frame(&mut buf, FrameKind::Ok, &[]).unwrap();
+        let mut r = buf.as_slice();
+        assert_eq!(
+            read_frame(&mut r, 5).unwrap(),
+            Some((FrameKind::Query, b"abcde".to_vec()))
+        );
+        assert_eq!(
+            read_frame(&mut r, 5).unwrap(),
+            Some((FrameKind::Ok, vec![]))
+        );
+        assert_eq!(read_frame(&mut r, 5).unwrap(), None);
+
+        // Truncated payloads and unknown kinds are errors, not a clean end of stream
+        assert!(read_frame(&mut &buf[..6], 5).is_err());
+        assert!(read_frame(&mut &[7_u8, 0, 0, 0, 0][..], 5).is_err());
+
+        // Longer payloads than expected are rejected before reading them
+        assert_eq!(
+            read_frame(&mut buf.as_slice(), 4).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        let huge = [FrameKind::Query as u8, 0xff, 0xff, 0xff, 0x3f];
+        assert_eq!(
+            read_frame(&mut &huge[..], MAX_FRAME_SIZE)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn test_client_server_localhost() {
+        let dir = temp_path("net");
+        let store = ParamStore::<RespireSmall>::new(&dir, 4).unwrap();
+        let record = |i: usize| {
+            <RespireSmall as PIR>::RecordBytes::from_bytes(&synthetic_record(
+                i,
+                RespireSmall::BYTES_PER_RECORD,
+            ))
+            .unwrap()
+        };
+        let (db, hint) = RespireSmall::encode_db(record, None);
+        let server = Arc::new(PirServer::<RespireSmall>::new(store, db));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || server.serve(&listener));
+
+        let mut client = PirClient::<RespireSmall>::connect(addr, 42).unwrap();
+        let (qk, pp) = RespireSmall::setup_seeded(None);
+        let idxs = vec![11; RespireSmall::BATCH_SIZE];
+        let (q, state) = RespireSmall::query(&qk, &idxs, &hint, None).unwrap();
+
+        // Queries of unregistered clients are rejected, but the connection stays usable
+        assert!(matches!(client.query(&q, None), Err(NetError::Remote(_))));
+        client.register(&pp).unwrap();
+        let mut stats = Stats::new();
+        let r = client.query(&q, Some(&mut stats)).unwrap();
+        assert!(stats.as_vec().iter().any(|(name, _)| *name == "round_trip"));
+        let extracted = RespireSmall::extract(&qk, &r, &state, None).unwrap();
+        assert!(extracted
+            .iter()
+            .all(|r| r.as_bytes() == record(11).as_bytes()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
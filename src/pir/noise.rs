@@ -1,3 +1,4 @@
+use std::fmt;
 use std::ops::{Add, Mul};
 
 #[derive(Clone, Copy, Debug)]
@@ -38,6 +39,11 @@ impl SubGaussianNoise {
     pub fn variance(self) -> f64 {
         self.variance
     }
+
+    /// log2 of the subgaussian width, taking the variance to be the square of the width.
+    pub fn bits(self) -> f64 {
+        self.variance.log2() / 2_f64
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -147,3 +153,235 @@ impl Mul<SubGaussianNoise> for BoundedNoise {
         } * Independent(self.cols as f64)
     }
 }
+
+/// The phases of answering a query after which the noise is estimated and measured.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum NoisePhase {
+    /// The RLWE ciphertexts output by query expansion
+    QueryExpansion,
+    /// The RLWE ciphertexts output by query expansion that are converted to GSW
+    QueryExpansionGsw,
+    /// The GSW ciphertexts converted from RLWE
+    RlweToGsw,
+    FirstDim,
+    Fold,
+    Rotate,
+    Project,
+    /// The projected ciphertexts of a response packed into one ring element
+    RingPacking,
+    Vectorize,
+    /// The response after modulus switching, in units of `Q3`
+    Compress,
+}
+
+impl NoisePhase {
+    /// All phases, in the order they happen.
+    pub const ALL: [NoisePhase; 10] = [
+        NoisePhase::QueryExpansion,
+        NoisePhase::QueryExpansionGsw,
+        NoisePhase::RlweToGsw,
+        NoisePhase::FirstDim,
+        NoisePhase::Fold,
+        NoisePhase::Rotate,
+        NoisePhase::Project,
+        NoisePhase::RingPacking,
+        NoisePhase::Vectorize,
+        NoisePhase::Compress,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NoisePhase::QueryExpansion => "query expansion",
+            NoisePhase::QueryExpansionGsw => "query expansion (GSW)",
+            NoisePhase::RlweToGsw => "RLWE to GSW",
+            NoisePhase::FirstDim => "first dim",
+            NoisePhase::Fold => "fold",
+            NoisePhase::Rotate => "rotate",
+            NoisePhase::Project => "project",
+            NoisePhase::RingPacking => "ring packing",
+            NoisePhase::Vectorize => "vectorize",
+            NoisePhase::Compress => "compress",
+        }
+    }
+}
+
+/// Estimated noise after one phase.
+#[derive(Clone, Copy, Debug)]
+pub struct PhaseEstimate {
+    pub phase: NoisePhase,
+    pub noise: SubGaussianNoise,
+    /// Bits between the subgaussian width and the width at which decoding fails, for phases that
+    /// encrypt a message scaled up from `Z_P`
+    pub margin_bits: Option<f64>,
+}
+
+impl PhaseEstimate {
+    /// log2 of the subgaussian width of the noise.
+    pub fn bits(&self) -> f64 {
+        self.noise.bits()
+    }
+}
+
+/// Estimated noise of answering a query, phase by phase, and the resulting error rate.
+#[derive(Clone, Debug)]
+pub struct NoiseReport {
+    /// One estimate per phase, in the order of [`NoisePhase::ALL`]
+    pub phases: Vec<PhaseEstimate>,
+    /// Estimated probability that a retrieved record is incorrect
+    pub error_rate: f64,
+}
+
+impl NoiseReport {
+    pub fn new() -> Self {
+        Self {
+            phases: Vec::with_capacity(NoisePhase::ALL.len()),
+            error_rate: 0_f64,
+        }
+    }
+
+    /// Appends the estimate of the next phase, where decoding fails at noise `threshold` if any.
+    pub fn push(&mut self, phase: NoisePhase, noise: SubGaussianNoise, threshold: Option<f64>) {
+        self.phases.push(PhaseEstimate {
+            phase,
+            noise,
+            margin_bits: threshold.map(|t| t.log2() - noise.bits()),
+        });
+    }
+
+    pub fn phase(&self, phase: NoisePhase) -> Option<&PhaseEstimate> {
+        self.phases.iter().find(|estimate| estimate.phase == phase)
+    }
+
+    /// Margin of the final (compressed) response.
+    pub fn margin_bits(&self) -> Option<f64> {
+        self.phases.last().and_then(|estimate| estimate.margin_bits)
+    }
+}
+
+impl Default for NoiseReport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for NoiseReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for estimate in self.phases.iter() {
+            write!(f, "{}: {:.3}", estimate.phase.name(), estimate.bits())?;
+            if let Some(margin) = estimate.margin_bits {
+                write!(f, " ({:.3} of margin)", margin)?;
+            }
+            writeln!(f)?;
+        }
+        write!(f, "Error rate: 2^({})", self.error_rate.log2())
+    }
+}
+
+/// The measured noise of one intermediate ciphertext.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoiseMeasurement {
+    pub phase: NoisePhase,
+    /// Index of the query in the batch, or of the response chunk for the phases from ring
+    /// packing on
+    pub query: usize,
+    /// Index of the ciphertext among those of the same phase and query
+    pub ciphertext: usize,
+    /// log2 of the subgaussian width
+    pub bits: f64,
+}
+
+///
+/// Debug oracle that measures the noise of every intermediate ciphertext while a batch is
+/// answered, by decrypting them with the client's query key. This is only meant for tests and
+/// tools such as [`crate::pir::noise_check`]: a server answering real queries never holds the
+/// query key.
+///
+/// Knowing the queried indices, the probe also measures ciphertexts whose message is not a
+/// multiple of the scaling factor, such as the query expansion outputs that become GSW
+/// ciphertexts.
+///
+pub struct NoiseProbe<'a, K> {
+    qk: &'a K,
+    indices: Vec<usize>,
+    measurements: Vec<NoiseMeasurement>,
+}
+
+impl<'a, K> NoiseProbe<'a, K> {
+    /// A probe for the queries made with `qk` for the records `indices`, in order. Queries beyond
+    /// `indices` are taken to be the dummy queries for record 0 that pad a batch.
+    pub fn new(qk: &'a K, indices: &[usize]) -> Self {
+        Self {
+            qk,
+            indices: indices.to_vec(),
+            measurements: Vec::new(),
+        }
+    }
+
+    pub fn query_key(&self) -> &'a K {
+        self.qk
+    }
+
+    /// The record index of the query at position `query` of the batch.
+    pub fn index(&self, query: usize) -> usize {
+        self.indices.get(query).copied().unwrap_or(0)
+    }
+
+    pub fn record(&mut self, phase: NoisePhase, query: usize, ciphertext: usize, bits: f64) {
+        self.measurements.push(NoiseMeasurement {
+            phase,
+            query,
+            ciphertext,
+            bits,
+        });
+    }
+
+    /// All measurements, in the order they were taken.
+    pub fn measurements(&self) -> &[NoiseMeasurement] {
+        &self.measurements
+    }
+
+    /// The largest noise measured in `phase`, as log2 of the subgaussian width.
+    pub fn max_bits(&self, phase: NoisePhase) -> Option<f64> {
+        self.measurements
+            .iter()
+            .filter(|m| m.phase == phase)
+            .map(|m| m.bits)
+            .reduce(f64::max)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pir::respire_harness::RESPIRE_TEST_PARAMS;
+
+    #[test]
+    fn test_noise_algebra() {
+        let e = SubGaussianNoise::new(4_f64, 16);
+        assert_eq!((e + e).variance(), 8_f64);
+        assert_eq!((e * Scale(3_f64)).variance(), 36_f64);
+        assert_eq!((e * Independent(3_f64)).variance(), 12_f64);
+        // A row of 4 values bounded by 2, times a column of independent noise
+        let product = BoundedNoise::new_matrix(2_f64, 16, 1, 4) * e.with_dimension(4, 1);
+        assert_eq!(product.variance(), 4_f64 * 16_f64 * 4_f64 * 4_f64);
+        assert_eq!(e.bits(), 1_f64);
+    }
+
+    #[test]
+    fn test_noise_report() {
+        let report = RESPIRE_TEST_PARAMS.noise_report();
+        assert_eq!(
+            report.phases.iter().map(|e| e.phase).collect::<Vec<_>>(),
+            NoisePhase::ALL
+        );
+        assert_eq!(report.error_rate, RESPIRE_TEST_PARAMS.error_rate_estimate());
+        assert!(report.margin_bits().unwrap() > 0_f64);
+        // Noise only grows through the phases on the same ciphertext
+        let bits = |phase| report.phase(phase).unwrap().bits();
+        assert!(bits(NoisePhase::QueryExpansion) <= bits(NoisePhase::FirstDim));
+        assert!(bits(NoisePhase::FirstDim) <= bits(NoisePhase::Fold));
+        assert!(bits(NoisePhase::Fold) <= bits(NoisePhase::Rotate));
+        assert!(bits(NoisePhase::Rotate) <= bits(NoisePhase::Vectorize));
+        assert!(NoisePhase::ALL.iter().all(|&p| report.phase(p).is_some()));
+    }
+}
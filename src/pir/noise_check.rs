@@ -0,0 +1,293 @@
+//! Monte Carlo validation of the analytic noise model of
+//! [`RespireParamsExpanded::noise_report`].
+//!
+//! [`check_noise`] runs queries against a synthetic database of random records, measures the noise
+//! of every intermediate ciphertext of answering them with a [`NoiseProbe`] (see
+//! [`Respire::answer_probed`]), and compares the noisiest ciphertext of each phase with the estimate
+//! for that phase. Estimates are meant to be upper bounds, so a phase whose measured noise exceeds
+//! its estimate is flagged.
+//!
+//! [`RespireParamsExpanded::noise_report`]: crate::pir::respire::RespireParamsExpanded::noise_report
+
+use crate::pir::engine::{load_params, EngineRegistry};
+use crate::pir::noise::{NoisePhase, NoiseProbe};
+use crate::pir::pir::{PIRRecordBytes, PirError, PIR};
+use crate::pir::respire::Respire;
+use clap::Parser;
+use itertools::Itertools;
+use rand::{CryptoRng, Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Measured noise of one phase over all queries, against its estimate. Noise is given as log2 of
+/// the subgaussian width.
+#[derive(Clone, Debug)]
+pub struct PhaseCheck {
+    pub phase: NoisePhase,
+    pub estimated_bits: f64,
+    /// The noise of all queries together, i.e. the mean variance of the noisiest ciphertext of each
+    pub measured_mean_bits: f64,
+    /// The noise of the noisiest query
+    pub measured_max_bits: f64,
+}
+
+impl PhaseCheck {
+    /// Whether the measured noise of some query exceeds the estimate.
+    pub fn exceeds_estimate(&self) -> bool {
+        self.measured_max_bits > self.estimated_bits
+    }
+}
+
+/// Outcome of [`check_noise`].
+#[derive(Clone, Debug)]
+pub struct NoiseCheck {
+    pub num_queries: usize,
+    /// One check per phase, in the order of [`NoisePhase::ALL`]
+    pub phases: Vec<PhaseCheck>,
+}
+
+impl NoiseCheck {
+    /// The phases whose measured noise exceeds the estimate.
+    pub fn flagged(&self) -> impl Iterator<Item = &PhaseCheck> {
+        self.phases.iter().filter(|check| check.exceeds_estimate())
+    }
+}
+
+impl fmt::Display for NoiseCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Noise (log2 subgaussian width) over {} queries:",
+            self.num_queries
+        )?;
+        writeln!(
+            f,
+            "    {:<22} {:>10} {:>10} {:>10}",
+            "phase", "estimate", "mean", "max"
+        )?;
+        for check in self.phases.iter() {
+            writeln!(
+                f,
+                "    {:<22} {:>10.3} {:>10.3} {:>10.3}{}",
+                check.phase.name(),
+                check.estimated_bits,
+                check.measured_mean_bits,
+                check.measured_max_bits,
+                if check.exceeds_estimate() {
+                    "  EXCEEDS ESTIMATE"
+                } else {
+                    ""
+                }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A database of random records, with a query key and expanded public params to query it.
+fn random_setup<ThePIR: Respire, R: RngCore + CryptoRng>(
+    rng: &mut R,
+) -> (
+    Vec<<ThePIR as PIR>::RecordBytes>,
+    ThePIR::Database,
+    ThePIR::QueryKey,
+    ThePIR::PublicParams,
+) {
+    let records = (0..ThePIR::NUM_RECORDS)
+        .map(|_| {
+            let mut bytes = vec![0_u8; ThePIR::BYTES_PER_RECORD];
+            rng.fill_bytes(&mut bytes);
+            <ThePIR as PIR>::RecordBytes::from_bytes(&bytes).unwrap()
+        })
+        .collect_vec();
+    let (db, _) = ThePIR::encode_db(|i| records[i].clone(), None);
+    let (qk, pp_seeded) = ThePIR::setup_seeded_with_rng(rng, None);
+    let pp = ThePIR::public_params_expand(&pp_seeded);
+    (records, db, qk, pp)
+}
+
+///
+/// Runs `num_queries` queries for random indices on a database of random records, and compares
+/// the measured noise of each phase with its estimate. All randomness is drawn from `rng`.
+///
+pub fn check_noise<ThePIR: Respire, R: RngCore + CryptoRng>(
+    num_queries: usize,
+    rng: &mut R,
+) -> Result<NoiseCheck, PirError> {
+    let (_, db, qk, pp) = random_setup::<ThePIR, _>(rng);
+
+    let mut measured: Vec<Vec<f64>> = vec![Vec::with_capacity(num_queries); NoisePhase::ALL.len()];
+    for _ in 0..num_queries {
+        let idx = rng.gen_range(0..ThePIR::NUM_RECORDS);
+        let q = ThePIR::query_one_with_rng(&qk, idx, rng, None)?;
+        let mut probe = NoiseProbe::new(&qk, &[idx]);
+        ThePIR::answer_probed(&pp, &db, &[q], &mut probe, None)?;
+        for (phase, bits) in NoisePhase::ALL.iter().zip(measured.iter_mut()) {
+            bits.push(probe.max_bits(*phase).unwrap());
+        }
+    }
+
+    let report = ThePIR::params().noise_report();
+    let phases = NoisePhase::ALL
+        .into_iter()
+        .zip(measured)
+        .map(|(phase, bits)| {
+            // Average the variances rather than their logarithms
+            let mean_variance =
+                bits.iter().map(|b| (2_f64 * b).exp2()).sum::<f64>() / bits.len() as f64;
+            PhaseCheck {
+                phase,
+                estimated_bits: report.phase(phase).unwrap().bits(),
+                measured_mean_bits: mean_variance.log2() / 2_f64,
+                measured_max_bits: bits.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            }
+        })
+        .collect();
+
+    Ok(NoiseCheck {
+        num_queries,
+        phases,
+    })
+}
+
+#[macro_export]
+macro_rules! generate_noise_check_main {
+    ($($params: expr),* $(,)?) => {
+        fn main() {
+            $crate::pir::noise_check::noise_check_main(&$crate::engine_registry!($($params),*));
+        }
+    };
+}
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// Number of queries to measure
+    num_queries: usize,
+    /// TOML file with the parameters to check; all precompiled parameter sets if omitted
+    #[arg(long)]
+    params: Option<PathBuf>,
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+pub fn noise_check_main(registry: &EngineRegistry) {
+    env_logger::init();
+    let args = Args::parse();
+    let mut rng = match args.seed {
+        Some(seed) => ChaCha20Rng::seed_from_u64(seed),
+        None => ChaCha20Rng::from_entropy(),
+    };
+
+    let params = match args.params {
+        Some(path) => vec![load_params(&path).expect("failed to load params")],
+        None => registry
+            .available_params()
+            .into_iter()
+            .sorted_by_key(|p| p.fingerprint())
+            .collect(),
+    };
+    let mut any_flagged = false;
+    for p in params {
+        let Some(engine) = registry.get(&p) else {
+            eprintln!(
+                "Parameters {:016x} were not compiled into this binary; available: {}",
+                p.fingerprint(),
+                registry
+                    .available_params()
+                    .iter()
+                    .map(|p| format!("{:016x}", p.fingerprint()))
+                    .join(", ")
+            );
+            std::process::exit(2);
+        };
+        eprintln!(
+            "Parameters {:016x}: {} records of {} bytes",
+            p.fingerprint(),
+            engine.num_records(),
+            engine.bytes_per_record()
+        );
+        let check = engine
+            .check_noise(args.num_queries, &mut rng)
+            .expect("query failed");
+        eprint!("{}", check);
+        if check.flagged().next().is_some() {
+            eprintln!(
+                "Measured noise exceeds the estimate in: {}",
+                check.flagged().map(|c| c.phase.name()).join(", ")
+            );
+            any_flagged = true;
+        }
+    }
+    if any_flagged {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pir::respire_harness::{RespireSmall, RESPIRE_SMALL_PARAMS};
+
+    #[test]
+    fn test_check_noise() {
+        let check = check_noise::<RespireSmall, _>(2, &mut ChaCha20Rng::seed_from_u64(0)).unwrap();
+        assert_eq!(
+            check.phases.iter().map(|c| c.phase).collect_vec(),
+            NoisePhase::ALL
+        );
+        for c in check.phases.iter() {
+            assert!(c.measured_mean_bits.is_finite());
+            assert!(c.measured_mean_bits <= c.measured_max_bits + 1e-9);
+        }
+        assert_eq!(check.flagged().count(), 0, "{}", check);
+    }
+
+    #[test]
+    fn test_noise_probe() {
+        let mut rng = ChaCha20Rng::seed_from_u64(1);
+        let (records, db, qk, pp) = random_setup::<RespireSmall, _>(&mut rng);
+        let idx = 5;
+        let q = RespireSmall::query_one_with_rng(&qk, idx, &mut rng, None).unwrap();
+
+        let mut probe = NoiseProbe::new(&qk, &[idx]);
+        let response =
+            RespireSmall::answer_probed(&pp, &db, std::slice::from_ref(&q), &mut probe, None)
+                .unwrap();
+        let extracted = RespireSmall::extract(&qk, &response, &1, None).unwrap();
+        assert_eq!(extracted[0].as_bytes(), records[idx].as_bytes());
+
+        let count = |phase| {
+            probe
+                .measurements()
+                .iter()
+                .filter(|m| m.phase == phase)
+                .count()
+        };
+        assert_eq!(count(NoisePhase::QueryExpansion), RespireSmall::RLWE_COUNT);
+        assert_eq!(
+            count(NoisePhase::QueryExpansionGsw),
+            RespireSmall::GSW_COUNT
+        );
+        assert_eq!(
+            count(NoisePhase::RlweToGsw),
+            RespireSmall::GSW_FOLD_COUNT + RespireSmall::GSW_ROT_COUNT
+        );
+        assert_eq!(count(NoisePhase::Fold), (1 << RespireSmall::NU2) - 1);
+        assert_eq!(count(NoisePhase::Rotate), RespireSmall::NU3);
+        let log_q1 = (RESPIRE_SMALL_PARAMS.Q1 as f64).log2();
+        for m in probe.measurements() {
+            assert!(m.bits.is_finite() && m.bits < log_q1 - 8_f64, "{:?}", m);
+        }
+
+        // The GSW noise is measured against the messages of the probed index, which differ for
+        // another index
+        let other_idx = idx ^ 1;
+        let mut probe_other = NoiseProbe::new(&qk, &[other_idx]);
+        RespireSmall::answer_probed(&pp, &db, &[q], &mut probe_other, None).unwrap();
+        assert!(
+            probe_other.max_bits(NoisePhase::RlweToGsw).unwrap()
+                > probe.max_bits(NoisePhase::RlweToGsw).unwrap() + 8_f64
+        );
+    }
+}
@@ -0,0 +1,127 @@
+//! Multithreading for the server's answer path.
+//!
+//! With the `parallel` cargo feature, the helpers here run on the global rayon thread pool;
+//! without it they run sequentially on the calling thread. Callers are written the same way in both
+//! cases. Each unit of work records into its own [`Stats`], which are merged into the caller's stats
+//! afterwards, and (in parallel mode) also attributed to the worker thread that ran it, so that
+//! [`Stats::per_thread`] shows how the work was balanced.
+
+use crate::pir::pir::Stats;
+use std::time::Duration;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+///
+/// Computes `f(0), ..., f(n - 1)` (in parallel with the `parallel` feature).
+///
+#[cfg(feature = "parallel")]
+pub fn map<R: Send>(n: usize, f: impl Fn(usize) -> R + Sync + Send) -> Vec<R> {
+    (0..n).into_par_iter().map(f).collect()
+}
+
+///
+/// Computes `f(0), ..., f(n - 1)` (in parallel with the `parallel` feature).
+///
+#[cfg(not(feature = "parallel"))]
+pub fn map<R: Send>(n: usize, f: impl Fn(usize) -> R + Sync + Send) -> Vec<R> {
+    (0..n).map(f).collect()
+}
+
+///
+/// Like [`map`], but passes each call its own stats to record into. These are merged into
+/// `time_stats`; in parallel mode they are also recorded under the worker thread that made the call.
+///
+pub fn map_with_stats<R: Send>(
+    n: usize,
+    time_stats: Option<&mut Stats<Duration>>,
+    f: impl Fn(usize, Option<&mut Stats<Duration>>) -> R + Sync + Send,
+) -> Vec<R> {
+    let Some(time_stats) = time_stats else {
+        return map(n, |i| f(i, None));
+    };
+    let results = map(n, |i| {
+        let mut stats = Stats::new();
+        let result = f(i, Some(&mut stats));
+        (result, current_thread(), stats)
+    });
+    results
+        .into_iter()
+        .map(|(result, thread, stats)| {
+            match thread {
+                Some(thread) => time_stats.merge_from_thread(thread, &stats),
+                None => time_stats.merge(&stats),
+            }
+            result
+        })
+        .collect()
+}
+
+#[cfg(feature = "parallel")]
+fn current_thread() -> Option<usize> {
+    rayon::current_thread_index()
+}
+
+#[cfg(not(feature = "parallel"))]
+fn current_thread() -> Option<usize> {
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_map_with_stats() {
+        let mut stats = Stats::new();
+        let result = map_with_stats(100, Some(&mut stats), |i, stats| {
+            stats.unwrap().add("work", Duration::from_nanos(i as u64));
+            2 * i
+        });
+        assert_eq!(result, (0..100).map(|i| 2 * i).collect::<Vec<_>>());
+        assert_eq!(stats.as_vec(), vec![("work", Duration::from_nanos(4950))]);
+
+        let per_thread_total: Duration = stats
+            .per_thread()
+            .iter()
+            .flat_map(|thread_stats| thread_stats.as_vec())
+            .map(|(_, value)| value)
+            .sum();
+        if cfg!(feature = "parallel") {
+            assert_eq!(per_thread_total, Duration::from_nanos(4950));
+        } else {
+            assert!(stats.per_thread().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_map_with_stats_nested() {
+        let mut stats = Stats::new();
+        map_with_stats(10, Some(&mut stats), |i, stats| {
+            let stats = stats.unwrap();
+            stats.add("outer", Duration::from_nanos(i as u64));
+            map_with_stats(10, Some(stats), |j, stats| {
+                stats.unwrap().add("inner", Duration::from_nanos(j as u64));
+            });
+        });
+        assert_eq!(
+            stats.as_vec(),
+            vec![
+                ("outer", Duration::from_nanos(45)),
+                ("inner", Duration::from_nanos(450))
+            ]
+        );
+
+        let mut per_thread_totals: HashMap<&str, Duration> = HashMap::new();
+        for (name, value) in stats.per_thread().iter().flat_map(|t| t.as_vec()) {
+            *per_thread_totals.entry(name).or_default() += value;
+        }
+        if cfg!(feature = "parallel") {
+            assert_eq!(per_thread_totals["outer"], Duration::from_nanos(45));
+            assert_eq!(per_thread_totals["inner"], Duration::from_nanos(450));
+        } else {
+            assert!(stats.per_thread().is_empty());
+        }
+    }
+}
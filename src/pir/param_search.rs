@@ -0,0 +1,402 @@
+//! Automatic parameter selection for a given database shape.
+//!
+//! The search enumerates the parameters that trade off sizes against noise (record encoding,
+//! compression modulus, database dimensions, vector packing, and automorphism gadget lengths),
+//! keeps the candidates whose [`RespireParamsExpanded::error_rate_estimate`] meets the target, and
+//! returns the one with the lowest weighted cost. Parameters that determine security (`Q1`, `D1`
+//! and the main error widths) are held fixed at the values used by [`FactoryParams::expand`], and the
+//! compression modulus is only ever paired with an error width from [`COMPRESS_CHOICES`].
+
+use crate::math::utils::{ceil_log, floor_log};
+use crate::pir::cuckoo_respire::cuckoo_failure_bound;
+use crate::pir::respire::{RespireParams, RespireParamsExpanded};
+use crate::pir::respire_harness::FactoryParams;
+use log::info;
+
+/// A compression (second) modulus together with a secret/error width that makes it secure.
+#[allow(non_snake_case)]
+#[derive(Clone, Copy, Debug)]
+pub struct CompressChoice {
+    pub D2: usize,
+    pub Q2: u64,
+    pub WIDTH_COMPRESS_MILLIONTHS: u64,
+}
+
+/// Compression parameters that have been checked with the lattice estimator.
+pub const COMPRESS_CHOICES: &[CompressChoice] = &[
+    CompressChoice {
+        D2: 512,
+        Q2: 12289,
+        WIDTH_COMPRESS_MILLIONTHS: 4_000_000,
+    },
+    CompressChoice {
+        D2: 512,
+        Q2: 61441,
+        WIDTH_COMPRESS_MILLIONTHS: 9_200_000,
+    },
+    CompressChoice {
+        D2: 512,
+        Q2: 1032193,
+        WIDTH_COMPRESS_MILLIONTHS: 46_000_000,
+    },
+    CompressChoice {
+        D2: 512,
+        Q2: 2056193,
+        WIDTH_COMPRESS_MILLIONTHS: 70_000_000,
+    },
+    CompressChoice {
+        D2: 512,
+        Q2: 16760833,
+        WIDTH_COMPRESS_MILLIONTHS: 253_600_000,
+    },
+    CompressChoice {
+        D2: 2048,
+        Q2: 249857,
+        WIDTH_COMPRESS_MILLIONTHS: 2_001_000,
+    },
+];
+
+/// Ring dimension fixed by [`FactoryParams::expand`]
+const D1: usize = 2048;
+/// Plaintext moduli to consider; larger `P` is never correct with the fixed `Q1`.
+const P_CHOICES: &[u64] = &[2, 4, 16];
+const MAX_N_VEC: usize = 16;
+const MAX_T_PROJ_SHORT: usize = 8;
+const MAX_T_PROJ_LONG: usize = 32;
+const MAX_Q3_FACTOR_LOG: usize = 6;
+/// How many extra database dimension bits to try beyond the minimum (batch mode only).
+const EXTRA_NU_BATCH: usize = 3;
+/// Largest bucket count to consider, as a multiple of the batch size (batch mode only).
+const MAX_BUCKET_FACTOR: usize = 64;
+
+///
+/// Relative weights of the cost components. Sizes are in bytes and server time is in estimated
+/// 64-bit multiply-adds (see [`estimate_server_ops`]).
+///
+#[derive(Clone, Copy, Debug)]
+pub struct CostWeights {
+    pub query: f64,
+    pub response: f64,
+    pub public_params: f64,
+    pub server_time: f64,
+}
+
+impl Default for CostWeights {
+    fn default() -> Self {
+        Self {
+            query: 1.0,
+            response: 1.0,
+            public_params: 0.1,
+            server_time: 1e-6,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SearchGoal {
+    pub num_records: usize,
+    pub bytes_per_record: usize,
+    pub batch_size: usize,
+    /// Upper bound on the estimated probability that a single retrieved record is incorrect
+    pub max_error_rate: f64,
+    /// Number of cuckoo hash functions (batch mode only)
+    pub num_hash: usize,
+    /// Upper bound on [`cuckoo_failure_bound`], i.e. on the probability that a batch cannot be
+    /// placed in the buckets (batch mode only)
+    pub max_cuckoo_failure_rate: f64,
+    pub weights: CostWeights,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SearchResult {
+    pub params: RespireParams,
+    /// `Some(n)` if the parameters are for cuckoo-hashed batch retrieval, i.e. for
+    /// `CuckooRespireImpl<batch_size, n, num_records, respire!(params.expand()), num_hash>`. In that
+    /// case `params.BATCH_SIZE == n`.
+    pub num_buckets: Option<usize>,
+    pub error_rate: f64,
+    /// Noise margin of the compressed response (see [`NoiseReport::margin_bits`])
+    ///
+    /// [`NoiseReport::margin_bits`]: crate::pir::noise::NoiseReport::margin_bits
+    pub margin_bits: f64,
+    pub query_size: usize,
+    pub response_size: usize,
+    pub public_param_size: usize,
+    pub server_ops: f64,
+    pub cost: f64,
+}
+
+///
+/// A coarse estimate of the server work per query, in 64-bit multiply-adds. Counts the first
+/// dimension scan, the folding external products and the query expansion automorphisms (each
+/// NTT counted as `D log D`), for every bucket.
+///
+pub fn estimate_server_ops(params: &RespireParamsExpanded) -> f64 {
+    let d = params.D1 as f64;
+    let ntt = d * (params.D1 as f64).log2();
+    let first_dim = 2_f64 * d * (params.packed_dim1_size() * params.packed_dim2_size()) as f64;
+    let external_product = params.M_GSW as f64 * (2_f64 * d + ntt);
+    let fold = params.packed_dim2_size() as f64 * external_product;
+    let automorph = |t: usize| t as f64 * (2_f64 * d + ntt);
+    let expand = 2_f64 * params.rlwe_count() as f64 * automorph(params.T_PROJ_SHORT)
+        + 2_f64 * params.gsw_count().next_power_of_two() as f64 * automorph(params.T_PROJ_LONG);
+    params.BATCH_SIZE as f64 * (first_dim + fold + expand)
+}
+
+///
+/// The smallest bucket count for which a batch of `goal.batch_size` records can be placed in the
+/// buckets with probability at least `1 - goal.max_cuckoo_failure_rate`, by
+/// [`cuckoo_failure_bound`].
+///
+fn min_num_buckets(goal: &SearchGoal) -> Option<usize> {
+    (goal.batch_size + 1..=MAX_BUCKET_FACTOR * goal.batch_size).find(|&b| {
+        cuckoo_failure_bound(goal.batch_size, b, goal.num_hash, 0) <= goal.max_cuckoo_failure_rate
+    })
+}
+
+///
+/// Chernoff (and union) bound on the probability that hashing every record into
+/// `goal.num_hash` of `num_buckets` buckets puts more than `capacity` records in some bucket.
+///
+fn bucket_overflow_bound(goal: &SearchGoal, num_buckets: usize, capacity: usize) -> f64 {
+    let mean = (goal.num_hash * goal.num_records) as f64 / num_buckets as f64;
+    let ratio = capacity as f64 / mean;
+    if ratio <= 1_f64 {
+        return 1_f64;
+    }
+    let ln_tail = -mean * (ratio * ratio.ln() - ratio + 1_f64);
+    (num_buckets as f64 * ln_tail.exp()).min(1_f64)
+}
+
+///
+/// The smallest bucket count, starting from `min_buckets`, for which no bucket of `capacity`
+/// slots is likely to overflow, with the same target probability as cuckoo hashing failures.
+///
+fn num_buckets_for(goal: &SearchGoal, min_buckets: usize, capacity: usize) -> Option<usize> {
+    (min_buckets..=max_num_buckets(goal, min_buckets))
+        .find(|&b| bucket_overflow_bound(goal, b, capacity) <= goal.max_cuckoo_failure_rate)
+}
+
+fn max_num_buckets(goal: &SearchGoal, min_buckets: usize) -> usize {
+    min_buckets.max(goal.num_hash * goal.batch_size)
+}
+
+///
+/// Scores a single candidate, or returns `None` if it does not meet the goal.
+///
+pub fn evaluate(
+    goal: &SearchGoal,
+    params: &RespireParams,
+    num_buckets: Option<usize>,
+) -> Option<SearchResult> {
+    let expanded = params.expand();
+    if expanded.record_one_size() < goal.bytes_per_record {
+        return None;
+    }
+    match num_buckets {
+        None if goal.batch_size != 1 || expanded.db_size() < goal.num_records => return None,
+        Some(n) if n != expanded.BATCH_SIZE => return None,
+        _ => {}
+    }
+
+    let noise = expanded.noise_report();
+    let error_rate = noise.error_rate;
+    if error_rate.is_nan() || error_rate > goal.max_error_rate {
+        return None;
+    }
+
+    let query_size = expanded.BATCH_SIZE * expanded.query_one_size();
+    let response_size = {
+        let num_ring_elem = expanded.BATCH_SIZE.div_ceil(expanded.pack_ratio_response());
+        let num_rem = num_ring_elem % expanded.N_VEC;
+        (num_ring_elem / expanded.N_VEC) * expanded.response_one_size(expanded.N_VEC)
+            + if num_rem > 0 {
+                expanded.response_one_size(num_rem)
+            } else {
+                0
+            }
+    };
+    let public_param_size = expanded.public_param_size();
+    let server_ops = estimate_server_ops(&expanded);
+
+    let w = &goal.weights;
+    let cost = w.query * query_size as f64
+        + w.response * response_size as f64
+        + w.public_params * public_param_size as f64
+        + w.server_time * server_ops;
+
+    Some(SearchResult {
+        params: *params,
+        num_buckets,
+        error_rate,
+        margin_bits: noise.margin_bits().unwrap_or(f64::NAN),
+        query_size,
+        response_size,
+        public_param_size,
+        server_ops,
+        cost,
+    })
+}
+
+///
+/// Searches for the lowest cost parameters meeting `goal`. Returns `None` if no candidate in the
+/// search space is correct with the required probability.
+///
+pub fn search_params(goal: &SearchGoal) -> Option<SearchResult> {
+    assert!(goal.batch_size >= 1);
+    let mut best: Option<SearchResult> = None;
+    let mut num_evaluated = 0usize;
+    let mut consider = |params: &RespireParams, num_buckets: Option<usize>| {
+        num_evaluated += 1;
+        let result = evaluate(goal, params, num_buckets);
+        if let Some(result) = result {
+            if best.is_none_or(|b| result.cost < b.cost) {
+                best = Some(result);
+            }
+        }
+        result.is_some()
+    };
+
+    for p in P_CHOICES.iter().copied() {
+        // Smallest record encoding that fits
+        let log_p = floor_log(2, p);
+        let d3 = (8 * goal.bytes_per_record)
+            .div_ceil(log_p)
+            .next_power_of_two();
+        if d3 > D1 {
+            continue;
+        }
+        let pack_ratio_db = D1 / d3;
+
+        for compress in COMPRESS_CHOICES.iter().filter(|c| c.D2 >= d3) {
+            let pack_ratio_response = compress.D2 / d3;
+
+            // (NU1 + NU2, bucket count) pairs to try
+            let mut shapes = Vec::new();
+            if goal.batch_size == 1 {
+                let nu = ceil_log(2, goal.num_records.div_ceil(pack_ratio_db).max(4) as u64);
+                shapes.push((nu, None));
+            } else if let Some(min_buckets) = min_num_buckets(goal) {
+                let min_bucket_records =
+                    (goal.num_hash * goal.num_records).div_ceil(max_num_buckets(goal, min_buckets));
+                let min_nu = ceil_log(2, min_bucket_records.div_ceil(pack_ratio_db).max(4) as u64);
+                for nu in min_nu..=min_nu + EXTRA_NU_BATCH {
+                    let capacity = (1 << nu) * pack_ratio_db;
+                    if let Some(b) = num_buckets_for(goal, min_buckets, capacity) {
+                        shapes.push((nu, Some(b)));
+                    }
+                }
+            }
+
+            for (nu, num_buckets) in shapes {
+                let batch = num_buckets.unwrap_or(1);
+                let max_n_vec = batch.div_ceil(pack_ratio_response).min(MAX_N_VEC);
+                for nu1 in 1..nu {
+                    for n_vec in 1..=max_n_vec {
+                        for q3_factor_log in 1..=MAX_Q3_FACTOR_LOG {
+                            let q3 = p << q3_factor_log;
+                            if q3 >= compress.Q2 {
+                                continue;
+                            }
+                            for t_proj_short in 2..=MAX_T_PROJ_SHORT {
+                                // The error decreases with T_PROJ_LONG, so the first passing value is
+                                // the cheapest one
+                                for t_proj_long in t_proj_short..=MAX_T_PROJ_LONG {
+                                    let params = FactoryParams {
+                                        BATCH_SIZE: batch,
+                                        N_VEC: n_vec,
+                                        P: p,
+                                        D3: d3,
+                                        NU1: nu1,
+                                        NU2: nu - nu1,
+                                        Q3: q3,
+                                        Q2: compress.Q2,
+                                        D2: compress.D2,
+                                        WIDTH_COMPRESS_MILLIONTHS: compress
+                                            .WIDTH_COMPRESS_MILLIONTHS,
+                                        T_PROJ_SHORT: t_proj_short,
+                                        T_PROJ_LONG: t_proj_long,
+                                    }
+                                    .expand();
+                                    if consider(&params, num_buckets) {
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    info!("Parameter search evaluated {} candidates", num_evaluated);
+    best
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_search_single_record() {
+        let goal = SearchGoal {
+            num_records: 1 << 20,
+            bytes_per_record: 256,
+            batch_size: 1,
+            max_error_rate: 2_f64.powi(-40),
+            num_hash: 3,
+            max_cuckoo_failure_rate: 2_f64.powi(-20),
+            weights: CostWeights::default(),
+        };
+        let result = search_params(&goal).unwrap();
+        let expanded = result.params.expand();
+        assert!(expanded.db_size() >= goal.num_records);
+        assert!(expanded.record_one_size() >= goal.bytes_per_record);
+        assert!(result.error_rate <= goal.max_error_rate);
+        assert_eq!(result.num_buckets, None);
+
+        // The hand-tuned parameters for this shape are in the search space, so the result can be
+        // no worse
+        let handmade = evaluate(
+            &goal,
+            &FactoryParams::single_record_256(9, 9).expand(),
+            None,
+        );
+        assert!(result.cost <= handmade.unwrap().cost);
+    }
+
+    #[test]
+    fn test_search_batch() {
+        let goal = SearchGoal {
+            num_records: 1 << 20,
+            bytes_per_record: 256,
+            batch_size: 4,
+            max_error_rate: 2_f64.powi(-40),
+            num_hash: 3,
+            max_cuckoo_failure_rate: 2_f64.powi(-20),
+            weights: CostWeights::default(),
+        };
+        let result = search_params(&goal).unwrap();
+        let num_buckets = result.num_buckets.unwrap();
+        assert!(num_buckets > goal.batch_size);
+        assert_eq!(result.params.BATCH_SIZE, num_buckets);
+        assert!(goal.num_hash * goal.num_records <= num_buckets * result.params.expand().db_size());
+        assert!(
+            cuckoo_failure_bound(goal.batch_size, num_buckets, goal.num_hash, 0)
+                <= goal.max_cuckoo_failure_rate
+        );
+        assert!(
+            cuckoo_failure_bound(goal.batch_size, num_buckets - 1, goal.num_hash, 0)
+                > goal.max_cuckoo_failure_rate
+        );
+
+        // Fewer hash functions need more buckets for the same failure rate
+        let two_hash = search_params(&SearchGoal {
+            num_hash: 2,
+            ..goal
+        })
+        .unwrap();
+        assert!(two_hash.num_buckets.unwrap() > num_buckets);
+    }
+}
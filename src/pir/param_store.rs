@@ -0,0 +1,282 @@
+//! Server-side storage of each client's public params.
+//!
+//! Clients upload their (seeded) public params once, in the wire format of
+//! [`encode_public_params`](crate::pir::wire::encode_public_params), and afterwards refer to them by
+//! a [`ClientId`]. Every upload is validated against the parameter fingerprint and written to
+//! `<dir>/<client id>.pp` right away. At most `max_resident` expanded params are kept in memory;
+//! when more are needed, the least recently used ones are dropped and later re-expanded from disk.
+
+use crate::pir::pir::{PirError, Stats, PIR};
+use crate::pir::respire::Respire;
+use crate::pir::wire::{decode_public_params, WireError};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub type ClientId = u64;
+
+#[derive(Debug)]
+pub enum ParamStoreError {
+    /// No public params were registered for the client.
+    UnknownClient(ClientId),
+    /// The uploaded (or stored) public params could not be decoded.
+    Pir(PirError),
+    Io(io::Error),
+}
+
+impl fmt::Display for ParamStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamStoreError::UnknownClient(client) => write!(f, "unknown client {:016x}", client),
+            ParamStoreError::Pir(e) => write!(f, "{}", e),
+            ParamStoreError::Io(e) => write!(f, "param store I/O error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ParamStoreError {}
+
+impl From<PirError> for ParamStoreError {
+    fn from(e: PirError) -> Self {
+        ParamStoreError::Pir(e)
+    }
+}
+
+impl From<WireError> for ParamStoreError {
+    fn from(e: WireError) -> Self {
+        ParamStoreError::Pir(e.into())
+    }
+}
+
+impl From<io::Error> for ParamStoreError {
+    fn from(e: io::Error) -> Self {
+        ParamStoreError::Io(e)
+    }
+}
+
+struct Resident<PP> {
+    by_client: HashMap<ClientId, (Arc<PP>, u64)>,
+    /// Clients by last use, least recent first
+    by_last_use: BTreeMap<u64, ClientId>,
+    clock: u64,
+}
+
+impl<PP> Resident<PP> {
+    fn touch(&mut self, client: ClientId) -> Option<Arc<PP>> {
+        self.clock += 1;
+        let (pp, last_use) = self.by_client.get_mut(&client)?;
+        self.by_last_use.remove(last_use);
+        *last_use = self.clock;
+        self.by_last_use.insert(self.clock, client);
+        Some(pp.clone())
+    }
+
+    fn insert(&mut self, client: ClientId, pp: Arc<PP>, max_resident: usize) {
+        self.remove(client);
+        while self.by_client.len() >= max_resident {
+            let Some((_, evicted)) = self.by_last_use.pop_first() else {
+                break;
+            };
+            self.by_client.remove(&evicted);
+        }
+        if max_resident > 0 {
+            self.clock += 1;
+            self.by_client.insert(client, (pp, self.clock));
+            self.by_last_use.insert(self.clock, client);
+        }
+    }
+
+    fn remove(&mut self, client: ClientId) {
+        if let Some((_, last_use)) = self.by_client.remove(&client) {
+            self.by_last_use.remove(&last_use);
+        }
+    }
+}
+
+///
+/// Registry of the public params of every client of a `R` server.
+///
+pub struct ParamStore<R: Respire> {
+    dir: PathBuf,
+    max_resident: usize,
+    resident: Mutex<Resident<R::PublicParams>>,
+}
+
+impl<R: Respire> ParamStore<R> {
+    ///
+    /// Opens (or creates) the store in `dir`, keeping at most `max_resident` expanded public params
+    /// in memory. Params registered by earlier stores in the same directory remain available.
+    ///
+    pub fn new(dir: impl Into<PathBuf>, max_resident: usize) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            max_resident,
+            resident: Mutex::new(Resident {
+                by_client: HashMap::new(),
+                by_last_use: BTreeMap::new(),
+                clock: 0,
+            }),
+        })
+    }
+
+    ///
+    /// Validates and stores the serialized public params of `client`, replacing any previous ones.
+    ///
+    pub fn register(&self, client: ClientId, bytes: &[u8]) -> Result<(), ParamStoreError> {
+        let pp = R::public_params_expand(&decode_public_params::<R>(bytes)?);
+        // Write and rename, so that a crash never leaves a partially written file behind
+        let path = self.path(client);
+        let tmp_path = path.with_extension("pp.tmp");
+        fs::write(&tmp_path, bytes)?;
+        fs::rename(&tmp_path, &path)?;
+        self.resident
+            .lock()
+            .unwrap()
+            .insert(client, Arc::new(pp), self.max_resident);
+        Ok(())
+    }
+
+    ///
+    /// The expanded public params of `client`, loading them from disk if they are not in memory.
+    ///
+    pub fn get(&self, client: ClientId) -> Result<Arc<R::PublicParams>, ParamStoreError> {
+        if let Some(pp) = self.resident.lock().unwrap().touch(client) {
+            return Ok(pp);
+        }
+        let bytes = match fs::read(self.path(client)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(ParamStoreError::UnknownClient(client))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let pp = Arc::new(R::public_params_expand(&decode_public_params::<R>(&bytes)?));
+        self.resident
+            .lock()
+            .unwrap()
+            .insert(client, pp.clone(), self.max_resident);
+        Ok(pp)
+    }
+
+    pub fn contains(&self, client: ClientId) -> bool {
+        self.path(client).exists()
+    }
+
+    ///
+    /// Deletes the public params of `client`. Does nothing if there are none.
+    ///
+    pub fn remove(&self, client: ClientId) -> io::Result<()> {
+        self.resident.lock().unwrap().remove(client);
+        match fs::remove_file(self.path(client)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Number of expanded public params currently in memory.
+    pub fn num_resident(&self) -> usize {
+        self.resident.lock().unwrap().by_client.len()
+    }
+
+    ///
+    /// Answers a query of `client` with its registered public params. `T` is any scheme built on
+    /// `R`'s public params, e.g. `R` itself or a [`CuckooRespireImpl`] over it.
+    ///
+    /// [`CuckooRespireImpl`]: crate::pir::cuckoo_respire::CuckooRespireImpl
+    ///
+    pub fn answer<T: PIR<PublicParams = R::PublicParams>>(
+        &self,
+        client: ClientId,
+        db: &T::Database,
+        query: &T::Query,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<T::Response, ParamStoreError> {
+        let pp = self.get(client)?;
+        Ok(T::answer(&pp, db, query, time_stats)?)
+    }
+
+    fn path(&self, client: ClientId) -> PathBuf {
+        self.dir.join(format!("{:016x}.pp", client))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pir::pir::PIRRecordBytes;
+    use crate::pir::respire_harness::{temp_path, RespireSmall};
+    use crate::pir::wire::encode_public_params;
+
+    #[test]
+    fn test_param_store_lru() {
+        let dir = temp_path("param-store-lru");
+        let store = ParamStore::<RespireSmall>::new(&dir, 1).unwrap();
+        let (_, pp1) = RespireSmall::setup_seeded(None);
+        let (_, pp2) = RespireSmall::setup_seeded(None);
+        store
+            .register(1, &encode_public_params::<RespireSmall>(&pp1))
+            .unwrap();
+        store
+            .register(2, &encode_public_params::<RespireSmall>(&pp2))
+            .unwrap();
+        assert_eq!(store.num_resident(), 1);
+
+        // Client 1 was evicted, and is reloaded from disk
+        assert!(*store.get(1).unwrap() == RespireSmall::public_params_expand(&pp1));
+        assert!(*store.get(2).unwrap() == RespireSmall::public_params_expand(&pp2));
+        assert_eq!(store.num_resident(), 1);
+
+        // Params persist across stores
+        let reopened = ParamStore::<RespireSmall>::new(&dir, 4).unwrap();
+        assert!(*reopened.get(1).unwrap() == RespireSmall::public_params_expand(&pp1));
+        reopened.remove(1).unwrap();
+        assert!(!reopened.contains(1));
+        assert!(matches!(
+            reopened.get(1),
+            Err(ParamStoreError::UnknownClient(1))
+        ));
+
+        let mut wrong_params = encode_public_params::<RespireSmall>(&pp1);
+        wrong_params[2] ^= 1;
+        assert!(matches!(
+            store.register(3, &wrong_params),
+            Err(ParamStoreError::Pir(PirError::ParamsMismatch { .. }))
+        ));
+        assert!(!store.contains(3));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_param_store_answer() {
+        let dir = temp_path("param-store-answer");
+        let store = ParamStore::<RespireSmall>::new(&dir, 1).unwrap();
+        let (qk, pp) = RespireSmall::setup_seeded(None);
+        store
+            .register(7, &encode_public_params::<RespireSmall>(&pp))
+            .unwrap();
+
+        let record = |i: usize| {
+            let bytes = vec![i as u8; RespireSmall::BYTES_PER_RECORD];
+            <RespireSmall as PIR>::RecordBytes::from_bytes(&bytes).unwrap()
+        };
+        let (db, hint) = RespireSmall::encode_db(record, None);
+        let idxs = vec![3; RespireSmall::BATCH_SIZE];
+        let (q, state) = RespireSmall::query(&qk, &idxs, &hint, None).unwrap();
+        let r = store.answer::<RespireSmall>(7, &db, &q, None).unwrap();
+        let extracted = RespireSmall::extract(&qk, &r, &state, None).unwrap();
+        assert!(extracted
+            .iter()
+            .all(|r| r.as_bytes() == record(3).as_bytes()));
+        assert!(matches!(
+            store.answer::<RespireSmall>(8, &db, &q, None),
+            Err(ParamStoreError::UnknownClient(8))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
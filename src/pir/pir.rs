@@ -1,6 +1,10 @@
+use crate::pir::wire::WireError;
 use serde::{de::DeserializeOwned, Serialize};
 use std::collections::HashMap;
-use std::ops::AddAssign;
+use std::fmt;
+use std::io;
+use std::ops::{AddAssign, Sub};
+use std::path::Path;
 use std::time::Duration;
 
 pub trait PIRRecordBytes: Clone + Default {
@@ -11,6 +15,7 @@ pub trait PIRRecordBytes: Clone + Default {
 pub struct Stats<T: AddAssign<T> + Copy + Default> {
     order: Vec<&'static str>,
     stats: HashMap<&'static str, T>,
+    per_thread: Vec<Stats<T>>,
 }
 
 impl<T: AddAssign<T> + Copy + Default> Stats<T> {
@@ -29,9 +34,63 @@ impl<T: AddAssign<T> + Copy + Default> Stats<T> {
         Self {
             order: Vec::new(),
             stats: HashMap::new(),
+            per_thread: Vec::new(),
         }
     }
 
+    /// Adds all of `other`'s stats (including its per-thread breakdown) to `self`.
+    pub fn merge(&mut self, other: &Stats<T>) {
+        for (name, value) in other.as_vec() {
+            self.add(name, value);
+        }
+        for (thread, thread_stats) in other.per_thread.iter().enumerate() {
+            self.thread_mut(thread).merge(thread_stats);
+        }
+    }
+
+    ///
+    /// Adds `other` to `self`, and also to the breakdown for worker thread `thread`. Whatever
+    /// `other` already attributes to nested worker threads is kept there, and only the rest is
+    /// attributed to `thread`.
+    ///
+    pub fn merge_from_thread(&mut self, thread: usize, other: &Stats<T>)
+    where
+        T: Sub<Output = T> + PartialEq,
+    {
+        self.merge(other);
+        let mut nested: HashMap<&'static str, T> = HashMap::new();
+        for (name, value) in other.per_thread.iter().flat_map(|t| t.as_vec()) {
+            *nested.entry(name).or_default() += value;
+        }
+        let thread_stats = self.thread_mut(thread);
+        for (name, value) in other.as_vec() {
+            match nested.get(name) {
+                None => thread_stats.add(name, value),
+                Some(&nested_value) => {
+                    let own = value - nested_value;
+                    if own != T::default() {
+                        thread_stats.add(name, own);
+                    }
+                }
+            }
+        }
+    }
+
+    ///
+    /// The stats recorded by each worker thread, indexed by thread. Empty unless some work was
+    /// done in parallel (see [`crate::pir::parallel`]).
+    ///
+    pub fn per_thread(&self) -> &[Stats<T>] {
+        &self.per_thread
+    }
+
+    fn thread_mut(&mut self, thread: usize) -> &mut Stats<T> {
+        if self.per_thread.len() <= thread {
+            self.per_thread.resize_with(thread + 1, Stats::new);
+        }
+        &mut self.per_thread[thread]
+    }
+
     pub fn as_vec(&self) -> Vec<(&'static str, T)> {
         let mut result = Vec::with_capacity(self.order.len());
         for name in self.order.iter().copied() {
@@ -41,6 +100,108 @@ impl<T: AddAssign<T> + Copy + Default> Stats<T> {
     }
 }
 
+///
+/// Errors from processing queries and responses. Everything derived from the other party's input is
+/// reported through this type rather than by panicking.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PirError {
+    /// A requested record index is not smaller than the number of records.
+    IndexOutOfRange { idx: usize, num_records: usize },
+    /// A batch (of indices, queries or responses) has the wrong number of elements.
+    BatchSizeMismatch { expected: usize, found: usize },
+    /// A query element does not have the shape required by the parameters.
+    MalformedQuery,
+    /// A response element does not have the shape required by the parameters.
+    MalformedResponse,
+    /// The database hint does not describe the requested records.
+    InvalidDatabaseHint,
+    /// The batch could not be assigned to buckets by cuckoo hashing.
+    CuckooInsertionFailed,
+    /// The input was produced for a different parameter set.
+    ParamsMismatch { expected: u64, found: u64 },
+    /// A record does not have the number of bytes required by the parameters.
+    RecordSizeMismatch { expected: usize, found: usize },
+    /// More records hash to a bucket than it can hold.
+    BucketOverflow {
+        bucket: usize,
+        size: usize,
+        capacity: usize,
+    },
+    /// The same key was given for several records.
+    DuplicateKey,
+    /// No slot assignment was found for the keys of a bucket.
+    BucketLayoutFailed { bucket: usize },
+    /// A record is longer than the parameters can retrieve in a single query.
+    RecordTooLarge { len: usize, max: usize },
+    /// The records need more slots than the database has.
+    DatabaseFull { slots: usize, capacity: usize },
+    /// A serialized message could not be decoded.
+    Wire(WireError),
+}
+
+impl fmt::Display for PirError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PirError::IndexOutOfRange { idx, num_records } => write!(
+                f,
+                "record index {} out of range ({} records)",
+                idx, num_records
+            ),
+            PirError::BatchSizeMismatch { expected, found } => {
+                write!(f, "batch has {} elements, expected {}", found, expected)
+            }
+            PirError::MalformedQuery => write!(f, "malformed query"),
+            PirError::MalformedResponse => write!(f, "malformed response"),
+            PirError::InvalidDatabaseHint => write!(f, "database hint does not match the query"),
+            PirError::CuckooInsertionFailed => write!(f, "cuckoo hashing failed for the batch"),
+            PirError::ParamsMismatch { expected, found } => write!(
+                f,
+                "params fingerprint mismatch (expected {:016x}, found {:016x})",
+                expected, found
+            ),
+            PirError::RecordSizeMismatch { expected, found } => {
+                write!(f, "record has {} bytes, expected {}", found, expected)
+            }
+            PirError::BucketOverflow {
+                bucket,
+                size,
+                capacity,
+            } => write!(
+                f,
+                "bucket {} has {} records, but can only hold {}",
+                bucket, size, capacity
+            ),
+            PirError::DuplicateKey => write!(f, "duplicate key"),
+            PirError::BucketLayoutFailed { bucket } => {
+                write!(f, "could not find slots for the keys of bucket {}", bucket)
+            }
+            PirError::RecordTooLarge { len, max } => {
+                write!(f, "record has {} bytes, but at most {} fit", len, max)
+            }
+            PirError::DatabaseFull { slots, capacity } => write!(
+                f,
+                "records need {} slots, but the database only has {}",
+                slots, capacity
+            ),
+            PirError::Wire(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PirError {}
+
+impl From<WireError> for PirError {
+    fn from(e: WireError) -> Self {
+        match e {
+            WireError::ParamsMismatch { expected, found } => {
+                PirError::ParamsMismatch { expected, found }
+            }
+            e => PirError::Wire(e),
+        }
+    }
+}
+
 pub trait SerdeRound: Serialize + DeserializeOwned {}
 impl<T: Serialize + DeserializeOwned> SerdeRound for T {}
 
@@ -66,24 +227,37 @@ pub trait PIR {
         records_generator: F,
         time_stats: Option<&mut Stats<Duration>>,
     ) -> (Self::Database, Self::DatabaseHint);
+    /// Writes an encoded database and its hint to `path`, so it can be reloaded with
+    /// [`PIR::load_db`] instead of being encoded again.
+    fn save_db(db: &Self::Database, db_hint: &Self::DatabaseHint, path: &Path) -> io::Result<()>;
+    /// Memory-maps a database written by [`PIR::save_db`] for the same parameters.
+    fn load_db(path: &Path) -> io::Result<(Self::Database, Self::DatabaseHint)>;
+    /// Replaces the given records of an encoded database, re-encoding only the parts of `db` that
+    /// depend on them. The result is the same as encoding the updated records from scratch. If an
+    /// index appears more than once, the last update wins.
+    fn update_records(
+        db: &mut Self::Database,
+        db_hint: &Self::DatabaseHint,
+        records: &[(usize, Self::RecordBytes)],
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<(), PirError>;
     fn setup(time_stats: Option<&mut Stats<Duration>>) -> (Self::QueryKey, Self::PublicParams);
     fn query(
         qk: &Self::QueryKey,
         idx: &[usize],
         db_hint: &Self::DatabaseHint,
         time_stats: Option<&mut Stats<Duration>>,
-    ) -> (Self::Query, Self::State);
+    ) -> Result<(Self::Query, Self::State), PirError>;
     fn answer(
         pp: &Self::PublicParams,
         db: &Self::Database,
         q: &Self::Query,
-        qk: Option<&Self::QueryKey>,
         time_stats: Option<&mut Stats<Duration>>,
-    ) -> Self::Response;
+    ) -> Result<Self::Response, PirError>;
     fn extract(
         qk: &Self::QueryKey,
         r: &Self::Response,
         st: &Self::State,
         time_stats: Option<&mut Stats<Duration>>,
-    ) -> Vec<Self::RecordBytes>;
+    ) -> Result<Vec<Self::RecordBytes>, PirError>;
 }
@@ -0,0 +1,444 @@
+//! Sources of raw records to encode into a database.
+//!
+//! A [`RecordSource`] provides records by index, reading each one only when the encoder asks for
+//! it, so databases larger than memory can be encoded from disk. Records can have any length: they
+//! are padded with zeros or truncated to the record size of the parameters by [`fit_record`], and
+//! indices past the end of the source are encoded as all-zero records.
+
+use crate::pir::pir::{PIRRecordBytes, Stats, PIR};
+use log::{info, warn};
+use memmap2::Mmap;
+use serde::Deserialize;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub trait RecordSource {
+    fn num_records(&self) -> usize;
+    /// The raw bytes of record `i`, for `i < self.num_records()`.
+    fn read_record(&self, i: usize) -> io::Result<Vec<u8>>;
+}
+
+///
+/// The `i`th record of the synthetic database used by the benchmarks, `len` bytes long.
+///
+pub fn synthetic_record(i: usize, len: usize) -> Vec<u8> {
+    let mut record = vec![0_u8; len];
+    record[0] = (i % 256) as u8;
+    record[1] = ((i / 256) % 256) as u8;
+    record[2] = 42_u8;
+    record[3] = 0_u8;
+    record[4] = (i % 100) as u8;
+    record[5] = ((i / 100) % 100) as u8;
+    record[6] = ((i / 100 / 100) % 100) as u8;
+    record[7] = ((i / 100 / 100 / 100) % 100) as u8;
+    // for i in 8..256 {
+    //     record[i] = random();
+    // }
+    record
+}
+
+///
+/// The records of [`synthetic_record`].
+///
+pub struct SyntheticRecords {
+    pub num_records: usize,
+    pub record_size: usize,
+}
+
+impl RecordSource for SyntheticRecords {
+    fn num_records(&self) -> usize {
+        self.num_records
+    }
+
+    fn read_record(&self, i: usize) -> io::Result<Vec<u8>> {
+        Ok(synthetic_record(i, self.record_size))
+    }
+}
+
+///
+/// A file of back-to-back records of `record_size` bytes each.
+///
+pub struct FlatFileRecords {
+    mmap: Mmap,
+    record_size: usize,
+}
+
+impl FlatFileRecords {
+    pub fn open(path: &Path, record_size: usize) -> io::Result<Self> {
+        if record_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "record size must be positive",
+            ));
+        }
+        let file = File::open(path)?;
+        // Safety: the file must not be modified while mapped, as with `load_db`
+        let mmap = unsafe { Mmap::map(&file)? };
+        if mmap.len() % record_size != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "file size {} is not a multiple of the record size {}",
+                    mmap.len(),
+                    record_size
+                ),
+            ));
+        }
+        Ok(Self { mmap, record_size })
+    }
+}
+
+impl RecordSource for FlatFileRecords {
+    fn num_records(&self) -> usize {
+        self.mmap.len() / self.record_size
+    }
+
+    fn read_record(&self, i: usize) -> io::Result<Vec<u8>> {
+        Ok(self.mmap[i * self.record_size..(i + 1) * self.record_size].to_vec())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KeyValueFormat {
+    /// `key,value` lines. The key ends at the first comma, and there is no quoting or header.
+    Csv,
+    /// `{"key": "...", "value": "..."}` lines, with an optional `"encoding"` field that says how
+    /// the key and value strings encode bytes: `"utf8"` (the default) for the UTF-8 bytes of the
+    /// strings, or `"hex"` for hexadecimal, which can represent any bytes. Other fields are
+    /// ignored.
+    Jsonl,
+}
+
+///
+/// A text file with one key→value entry per line. Record `i` is the value on the `i`th non-empty
+/// line; the keys are available for keyword PIR with [`KeyValueRecords::entries`].
+///
+pub struct KeyValueRecords {
+    mmap: Mmap,
+    format: KeyValueFormat,
+    /// Byte range of each entry's line, without the line terminator
+    lines: Vec<(usize, usize)>,
+}
+
+impl KeyValueRecords {
+    ///
+    /// Opens the file and indexes its lines. Every line is parsed once here, so that malformed
+    /// files are rejected before encoding starts.
+    ///
+    pub fn open(path: &Path, format: KeyValueFormat) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // Safety: the file must not be modified while mapped, as with `load_db`
+        let mmap = unsafe { Mmap::map(&file)? };
+        let mut result = Self {
+            mmap,
+            format,
+            lines: Vec::new(),
+        };
+        let mut begin = 0;
+        for (line_idx, line) in result.mmap.split(|&b| b == b'\n').enumerate() {
+            let end = begin + line.strip_suffix(b"\r").unwrap_or(line).len();
+            if end > begin {
+                result.lines.push((begin, end));
+                result.entry_at(result.lines.len() - 1).map_err(|e| {
+                    io::Error::new(e.kind(), format!("line {}: {}", line_idx + 1, e))
+                })?;
+            }
+            begin += line.len() + 1;
+        }
+        Ok(result)
+    }
+
+    ///
+    /// The (key, value) entry of every record.
+    ///
+    pub fn entries(&self) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        (0..self.lines.len()).map(|i| self.entry_at(i)).collect()
+    }
+
+    fn entry_at(&self, i: usize) -> io::Result<(Vec<u8>, Vec<u8>)> {
+        let (begin, end) = self.lines[i];
+        let line = &self.mmap[begin..end];
+        match self.format {
+            KeyValueFormat::Csv => {
+                let comma = line.iter().position(|&b| b == b',').ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "missing ',' separator")
+                })?;
+                Ok((line[..comma].to_vec(), line[comma + 1..].to_vec()))
+            }
+            KeyValueFormat::Jsonl => parse_json_entry(line),
+        }
+    }
+}
+
+///
+/// A line of a [`KeyValueFormat::Jsonl`] file.
+///
+#[derive(Deserialize)]
+struct JsonEntry {
+    key: String,
+    value: String,
+    #[serde(default)]
+    encoding: JsonEncoding,
+}
+
+#[derive(Copy, Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum JsonEncoding {
+    #[default]
+    Utf8,
+    Hex,
+}
+
+impl JsonEncoding {
+    fn decode(self, field: &str, text: String) -> io::Result<Vec<u8>> {
+        match self {
+            JsonEncoding::Utf8 => Ok(text.into_bytes()),
+            JsonEncoding::Hex => hex::decode(text).map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", field, e))
+            }),
+        }
+    }
+}
+
+///
+/// Parses a JSON object with `key` and `value` fields (see [`KeyValueFormat::Jsonl`]).
+///
+fn parse_json_entry(line: &[u8]) -> io::Result<(Vec<u8>, Vec<u8>)> {
+    let entry: JsonEntry = serde_json::from_slice(line)?;
+    Ok((
+        entry.encoding.decode("key", entry.key)?,
+        entry.encoding.decode("value", entry.value)?,
+    ))
+}
+
+impl RecordSource for KeyValueRecords {
+    fn num_records(&self) -> usize {
+        self.lines.len()
+    }
+
+    fn read_record(&self, i: usize) -> io::Result<Vec<u8>> {
+        Ok(self.entry_at(i)?.1)
+    }
+}
+
+///
+/// A directory of files, each holding one record. Records are ordered by file name.
+///
+pub struct DirectoryRecords {
+    paths: Vec<PathBuf>,
+}
+
+impl DirectoryRecords {
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                paths.push(entry.path());
+            }
+        }
+        paths.sort();
+        Ok(Self { paths })
+    }
+}
+
+impl RecordSource for DirectoryRecords {
+    fn num_records(&self) -> usize {
+        self.paths.len()
+    }
+
+    fn read_record(&self, i: usize) -> io::Result<Vec<u8>> {
+        fs::read(&self.paths[i])
+    }
+}
+
+///
+/// Opens `path` as a directory of blobs, a CSV or JSONL key→value file (by extension), or
+/// otherwise as a flat file of `record_size` byte records. Fails if the source has no records.
+///
+pub fn open_record_source(path: &Path, record_size: usize) -> io::Result<Box<dyn RecordSource>> {
+    let source: Box<dyn RecordSource> = if path.is_dir() {
+        Box::new(DirectoryRecords::open(path)?)
+    } else {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("csv") => Box::new(KeyValueRecords::open(path, KeyValueFormat::Csv)?),
+            Some("jsonl") => Box::new(KeyValueRecords::open(path, KeyValueFormat::Jsonl)?),
+            _ => Box::new(FlatFileRecords::open(path, record_size)?),
+        }
+    };
+    if source.num_records() == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} has no records", path.display()),
+        ));
+    }
+    Ok(source)
+}
+
+///
+/// Pads `record` with zeros, or truncates it, to `len` bytes.
+///
+pub fn fit_record(mut record: Vec<u8>, len: usize) -> Vec<u8> {
+    record.resize(len, 0);
+    record
+}
+
+///
+/// Record `i` of `source`, fitted to the record size of `ThePIR`. Indices past the end of the
+/// source are all-zero records.
+///
+pub fn read_pir_record<ThePIR: PIR>(
+    source: &dyn RecordSource,
+    i: usize,
+) -> io::Result<ThePIR::RecordBytes> {
+    let bytes = if i < source.num_records() {
+        fit_record(source.read_record(i)?, ThePIR::BYTES_PER_RECORD)
+    } else {
+        vec![0_u8; ThePIR::BYTES_PER_RECORD]
+    };
+    Ok(ThePIR::RecordBytes::from_bytes(&bytes).unwrap())
+}
+
+///
+/// Encodes the records of `source`, reading each one as the encoder reaches it. Fails if the
+/// source has more records than `ThePIR` holds, or if a record cannot be read.
+///
+pub fn encode_db_from_source<ThePIR: PIR>(
+    source: &dyn RecordSource,
+    time_stats: Option<&mut Stats<Duration>>,
+) -> io::Result<(ThePIR::Database, ThePIR::DatabaseHint)> {
+    if source.num_records() > ThePIR::NUM_RECORDS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "source has {} records, but the parameters hold at most {}",
+                source.num_records(),
+                ThePIR::NUM_RECORDS
+            ),
+        ));
+    }
+
+    // `encode_db` cannot fail, so remember the first read error and fill in a zero record instead
+    let error = Mutex::new(None);
+    let truncated = AtomicUsize::new(0);
+    let records_generator = |i: usize| {
+        if i >= source.num_records() {
+            return ThePIR::RecordBytes::from_bytes(&vec![0_u8; ThePIR::BYTES_PER_RECORD]).unwrap();
+        }
+        let bytes = source.read_record(i).unwrap_or_else(|e| {
+            error.lock().unwrap().get_or_insert(e);
+            Vec::new()
+        });
+        if bytes.len() > ThePIR::BYTES_PER_RECORD {
+            truncated.fetch_add(1, Ordering::Relaxed);
+        }
+        ThePIR::RecordBytes::from_bytes(&fit_record(bytes, ThePIR::BYTES_PER_RECORD)).unwrap()
+    };
+    info!(
+        "Encoding {} records from source ({} empty)",
+        source.num_records(),
+        ThePIR::NUM_RECORDS - source.num_records()
+    );
+    let encoded = ThePIR::encode_db(records_generator, time_stats);
+    if let Some(e) = error.into_inner().unwrap() {
+        return Err(e);
+    }
+    let truncated = truncated.into_inner();
+    if truncated > 0 {
+        warn!(
+            "{} records were truncated to {} bytes",
+            truncated,
+            ThePIR::BYTES_PER_RECORD
+        );
+    }
+    Ok(encoded)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pir::respire_harness::{temp_path, RespireSmall};
+
+    #[test]
+    fn test_key_value_records() {
+        let path = temp_path("records.csv");
+        fs::write(&path, "a,1\r\nbb,two,2\n\nccc,\n").unwrap();
+        let csv = KeyValueRecords::open(&path, KeyValueFormat::Csv).unwrap();
+        assert_eq!(csv.num_records(), 3);
+        assert_eq!(csv.read_record(1).unwrap(), b"two,2");
+        assert_eq!(csv.entries().unwrap()[2], (b"ccc".to_vec(), vec![]));
+        fs::write(&path, "a,1\nb\n").unwrap();
+        assert!(KeyValueRecords::open(&path, KeyValueFormat::Csv).is_err());
+        fs::write(&path, "\n\n").unwrap();
+        assert!(open_record_source(&path, 1).is_err());
+        fs::remove_file(&path).unwrap();
+
+        let path = temp_path("records.jsonl");
+        fs::write(
+            &path,
+            "{\"key\": \"k1\", \"value\": \"v1\"}\n{ \"value\":\"v\\u00e9\\\"\", \"key\": \"k2\" }\n",
+        )
+        .unwrap();
+        let jsonl = open_record_source(&path, 1).unwrap();
+        assert_eq!(jsonl.num_records(), 2);
+        assert_eq!(jsonl.read_record(1).unwrap(), "vé\"".as_bytes());
+        fs::write(
+            &path,
+            "{\"key\": \"6b\", \"value\": \"00ff0a\", \"encoding\": \"hex\"}\n",
+        )
+        .unwrap();
+        let jsonl = KeyValueRecords::open(&path, KeyValueFormat::Jsonl).unwrap();
+        assert_eq!(
+            jsonl.entries().unwrap(),
+            vec![(b"k".to_vec(), vec![0, 0xff, 0x0a])]
+        );
+        for bad in [
+            "{\"key\": \"k1\"}",
+            "{\"key\": \"k1\", \"value\": 1}",
+            "{\"key\": \"k1\", \"value\": \"v\\u+123\"}",
+            "{\"key\": \"k1\", \"value\": \"0g\", \"encoding\": \"hex\"}",
+            "{\"key\": \"k1\", \"value\": \"v\", \"encoding\": \"base32\"}",
+            "{\"key\": \"k1\", \"value\": \"v\"} x",
+        ] {
+            fs::write(&path, bad).unwrap();
+            assert!(open_record_source(&path, 1).is_err(), "{}", bad);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_encode_db_from_source() {
+        let dir = temp_path("records-dir");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("0"), b"short").unwrap();
+        fs::write(
+            dir.join("1"),
+            vec![7_u8; 2 * RespireSmall::BYTES_PER_RECORD],
+        )
+        .unwrap();
+        let source = open_record_source(&dir, RespireSmall::BYTES_PER_RECORD).unwrap();
+        assert_eq!(source.num_records(), 2);
+
+        let (db, hint) = encode_db_from_source::<RespireSmall>(source.as_ref(), None).unwrap();
+        let (qk, pp) = RespireSmall::setup(None);
+        for idx in [0, 1, 2] {
+            let idxs = vec![idx; RespireSmall::BATCH_SIZE];
+            let (q, state) = RespireSmall::query(&qk, &idxs, &hint, None).unwrap();
+            let r = RespireSmall::answer(&pp, &db, &q, None).unwrap();
+            let extracted = RespireSmall::extract(&qk, &r, &state, None).unwrap();
+            let expected = read_pir_record::<RespireSmall>(source.as_ref(), idx).unwrap();
+            assert_eq!(extracted[0].as_bytes(), expected.as_bytes());
+        }
+        assert_eq!(
+            &read_pir_record::<RespireSmall>(source.as_ref(), 0)
+                .unwrap()
+                .as_bytes()[..6],
+            b"short\0"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,15 +1,20 @@
 use bitvec::prelude::*;
 use itertools::Itertools;
-use log::Level::Info;
-use log::{info, log_enabled};
+use log::info;
 use std::cmp::{max, min};
+use std::collections::BTreeMap;
 use std::f64::consts::PI;
+use std::io;
+use std::path::Path;
 use std::time::{Duration, Instant};
 
-use rand::{Rng, SeedableRng};
+use rand::{CryptoRng, Rng, RngCore, SeedableRng};
 use rand_chacha::ChaCha20Rng;
 use rand_distr::num_traits::clamp;
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
 
+use crate::math::discrete_gaussian::{CdtSampler, DiscreteGaussian};
 use crate::math::gadget::{
     base_from_len, build_gadget, gadget_inverse, gadget_inverse_scalar, RingElementDecomposable,
 };
@@ -24,7 +29,17 @@ use crate::math::ring_elem::{NormedRingElement, RingElement};
 use crate::math::utils::{ceil_log, floor_log, mod_inverse, reverse_bits_fast};
 
 use crate::math::simd_utils::*;
-use crate::pir::pir::{PIRRecordBytes, SerdeRound, Stats, PIR};
+use crate::pir::database::{map_db_file, write_db_file, SimdDatabase};
+use crate::pir::noise::{
+    BoundedNoise, Independent, NoisePhase, NoiseProbe, NoiseReport, Scale, SubGaussianNoise,
+};
+use crate::pir::parallel;
+use crate::pir::pir::{PIRRecordBytes, PirError, SerdeRound, Stats, PIR};
+use crate::pir::wire::{pack_coeffs, pack_small_coeffs, small_coeff_width, BitCursor, WireError};
+
+/// Number of chunks the first dimension outputs are split into for the worker threads.
+#[cfg(feature = "parallel")]
+const FIRST_DIM_CHUNKS: usize = 64;
 
 pub struct RespireImpl<
     const Q1: u64,
@@ -64,6 +79,7 @@ pub struct RespireImpl<
 > {}
 
 #[allow(non_snake_case)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RespireParams {
     pub Q1A: u64,
     pub Q1B: u64,
@@ -140,7 +156,7 @@ impl RespireParams {
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RespireParamsExpanded {
     pub Q1: u64,
     pub Q1A: u64,
@@ -178,6 +194,370 @@ pub struct RespireParamsExpanded {
     pub BYTES_PER_RECORD: usize,
 }
 
+impl RespireParamsExpanded {
+    ///
+    /// A stable 64-bit fingerprint (FNV-1a over the little-endian parameter values) of the full
+    /// parameter set. Used to detect client/server parameter mismatches in serialized data.
+    ///
+    pub fn fingerprint(&self) -> u64 {
+        let values = [
+            self.Q1,
+            self.Q1A,
+            self.Q1B,
+            self.D1 as u64,
+            self.Z_GSW,
+            self.T_GSW as u64,
+            self.M_GSW as u64,
+            self.Z_PROJ_SHORT,
+            self.T_PROJ_SHORT as u64,
+            self.Z_PROJ_LONG,
+            self.T_PROJ_LONG as u64,
+            self.Z_RLWE_TO_GSW,
+            self.T_RLWE_TO_GSW as u64,
+            self.M_RLWE_TO_GSW as u64,
+            self.Z_VECTORIZE,
+            self.T_VECTORIZE as u64,
+            self.BATCH_SIZE as u64,
+            self.N_VEC as u64,
+            self.ERROR_WIDTH_MILLIONTHS,
+            self.ERROR_WIDTH_VEC_MILLIONTHS,
+            self.ERROR_WIDTH_COMPRESS_MILLIONTHS,
+            self.SECRET_BOUND,
+            self.SECRET_WIDTH_VEC_MILLIONTHS,
+            self.SECRET_WIDTH_COMPRESS_MILLIONTHS,
+            self.P,
+            self.D3 as u64,
+            self.NU1 as u64,
+            self.NU2 as u64,
+            self.Q3,
+            self.Q2,
+            self.D2 as u64,
+            self.T_COMPRESS as u64,
+            self.Z_COMPRESS,
+            self.BYTES_PER_RECORD as u64,
+        ];
+        fingerprint_extend(FINGERPRINT_INIT, &values)
+    }
+}
+
+impl RespireParamsExpanded {
+    //
+    // Derived constants, matching the associated constants of `Respire`
+    //
+
+    pub const fn packed_dim1_size(&self) -> usize {
+        2_usize.pow(self.NU1 as u32)
+    }
+
+    pub const fn packed_dim2_size(&self) -> usize {
+        2_usize.pow(self.NU2 as u32)
+    }
+
+    pub const fn pack_ratio_db(&self) -> usize {
+        self.D1 / self.D3
+    }
+
+    pub const fn pack_ratio_response(&self) -> usize {
+        self.D2 / self.D3
+    }
+
+    pub const fn db_size(&self) -> usize {
+        self.packed_dim1_size() * self.packed_dim2_size() * self.pack_ratio_db()
+    }
+
+    pub const fn nu3(&self) -> usize {
+        ceil_log(2, (self.D1 / self.D3) as u64)
+    }
+
+    pub const fn rlwe_count(&self) -> usize {
+        1 << self.NU1
+    }
+
+    pub const fn gsw_count(&self) -> usize {
+        (self.NU2 + self.nu3()) * self.T_GSW
+    }
+
+    pub const fn public_param_seed_count(&self) -> usize {
+        2 * floor_log(2, self.D1 as u64) + 2 + self.N_VEC
+    }
+
+    //
+    // Size and error estimates, so that candidate parameter sets can be evaluated at runtime
+    // without instantiating `RespireImpl` for them
+    //
+
+    pub fn error_rate_estimate(&self) -> f64 {
+        self.noise_report().error_rate
+    }
+
+    pub fn public_param_size(&self) -> usize {
+        self.public_param_size_impl(false)
+    }
+
+    pub fn query_one_size(&self) -> usize {
+        // Two 32 byte seeds, followed by the bit-packed coefficients
+        2 * 32 + ((self.rlwe_count() + self.gsw_count()) * ceil_log(2, self.Q1)).div_ceil(8)
+    }
+
+    pub fn query_key_size(&self) -> usize {
+        // Secret coefficients are bit-packed with just enough bits for their range
+        let width_encode = small_coeff_width(self.SECRET_BOUND);
+        let width_vec = small_coeff_width(DiscreteGaussian::max_value(
+            self.SECRET_WIDTH_VEC_MILLIONTHS,
+        ));
+        let width_small = small_coeff_width(DiscreteGaussian::max_value(
+            self.SECRET_WIDTH_COMPRESS_MILLIONTHS,
+        ));
+        (self.D1 * width_encode + self.N_VEC * (self.D1 * width_vec + self.D2 * width_small))
+            .div_ceil(8)
+    }
+
+    pub fn record_one_size(&self) -> usize {
+        let log_p = floor_log(2, self.P);
+        self.D3 * log_p / 8
+    }
+
+    pub fn response_one_size(&self, trunc_len: usize) -> usize {
+        // Technically we can do ceil(d * (log(q2) + len * log(q1)) by packing into a single large integer.
+        // But for simplicity each IntMod<Q3> / IntMod<Q2> is bit-packed individually (see `crate::pir::wire`).
+        let log_q1 = ceil_log(2, self.Q3);
+        let log_q2 = ceil_log(2, self.Q2);
+        ((self.D2 as f64) * (log_q2 as f64 + (trunc_len as f64) * log_q1 as f64) / 8_f64).ceil()
+            as usize
+    }
+
+    ///
+    /// The noise estimated after each phase of answering a query, from which
+    /// [`RespireParamsExpanded::error_rate_estimate`] is derived.
+    ///
+    pub fn noise_report(&self) -> NoiseReport {
+        let Self {
+            Q1,
+            D1,
+            Z_GSW,
+            T_GSW,
+            Z_PROJ_SHORT,
+            T_PROJ_SHORT,
+            Z_PROJ_LONG,
+            T_PROJ_LONG,
+            Z_RLWE_TO_GSW,
+            T_RLWE_TO_GSW,
+            Z_VECTORIZE,
+            T_VECTORIZE,
+            BATCH_SIZE,
+            N_VEC,
+            ERROR_WIDTH_MILLIONTHS,
+            ERROR_WIDTH_VEC_MILLIONTHS,
+            ERROR_WIDTH_COMPRESS_MILLIONTHS,
+            SECRET_BOUND,
+            SECRET_WIDTH_VEC_MILLIONTHS,
+            P,
+            D3,
+            NU2,
+            Q3,
+            Q2,
+            T_COMPRESS,
+            Z_COMPRESS,
+            ..
+        } = *self;
+        // We use square subgaussian widths as variances
+        let degree = D1 as u64;
+        let width_noise = |width_millionths: u64| {
+            SubGaussianNoise::new(((width_millionths as f64) / 1_000_000_f64).powi(2), degree)
+        };
+        let error = width_noise(ERROR_WIDTH_MILLIONTHS);
+        let error_vec = width_noise(ERROR_WIDTH_VEC_MILLIONTHS);
+        let error_switch = width_noise(ERROR_WIDTH_COMPRESS_MILLIONTHS);
+        let secret_vec = width_noise(SECRET_WIDTH_VEC_MILLIONTHS);
+        // let secret_switch = width_noise(SECRET_WIDTH_COMPRESS_MILLIONTHS);
+
+        let log_d: usize = ceil_log(2, D1 as u64);
+
+        // Bound on a gadget decomposition into t digits of base z, one digit per column
+        let gadget = |t: usize, z: u64| -> BoundedNoise {
+            assert!(z >= 2);
+
+            let z_factor = match z {
+                2 => {
+                    // With probability <= 2^(-48.421), a random binary gadget will have <= 1200 equal 1
+                    // N(log(1 - sum(binomial(2048, x) * (1/2)^2048, x, 0, 1200), 2)) = -48.4216972197488
+                    const BINARY_FACTOR: f64 = 1200_f64 / 2048_f64;
+                    BINARY_FACTOR
+                }
+                // 3 => {
+                //     // With probability <= 2^(-48.506), a random ternary gadget will have <= 1528 coefficients equal +- 1
+                //     // N(log(1 - sum(binomial(2048, x) * (2/3)^x * (1/3)^(2048-x), x, 0, 1528), 2)) = -48.5067228501629
+                //     const TERNARY_FACTOR: f64 = 1528_f64 / 2048_f64;
+                //     TERNARY_FACTOR
+                // }
+                _ => {
+                    // TODO noise: verify this factor is right
+                    // const CHERNOFF_FACTOR: f64 = 0.6_f64;
+                    const CHERNOFF_FACTOR: f64 = 1.0_f64;
+                    ((z / 2) as f64).powi(2) * CHERNOFF_FACTOR
+                }
+            };
+
+            BoundedNoise::new_matrix(z_factor.sqrt(), degree, 1, t)
+        };
+        // Noise of the inner product of a gadget decomposition with `t` fresh ciphertexts
+        let gadget_noise =
+            |t: usize, z: u64, e: SubGaussianNoise| gadget(t, z) * e.with_dimension(t, 1);
+
+        let select_noise = |e_gsw: SubGaussianNoise, e_reg: SubGaussianNoise, depth: usize| {
+            // m = 2t
+            e_reg + gadget_noise(2 * T_GSW, Z_GSW, e_gsw) * Independent(depth as f64)
+        };
+
+        let proj_noise = |e: SubGaussianNoise, t_auto: usize, z_auto: u64, depth: usize| {
+            let ct = (4usize.pow(depth as u32) - 1) / 3;
+            e + gadget_noise(t_auto, z_auto, error) * Independent(ct as f64)
+        };
+
+        let mut report = NoiseReport::new();
+        // Decoding fails once the noise reaches half the scaling factor
+        let q1_threshold = 0.5_f64 * (Q1 / P) as f64;
+
+        // Query expansion
+        let e_reg = proj_noise(error, T_PROJ_SHORT, Z_PROJ_SHORT, log_d);
+        report.push(NoisePhase::QueryExpansion, e_reg, Some(q1_threshold));
+        let e_gsw_raw = proj_noise(error, T_PROJ_LONG, Z_PROJ_LONG, log_d);
+        report.push(NoisePhase::QueryExpansionGsw, e_gsw_raw, None);
+
+        // RLWE to GSW
+        let e_gsw = BoundedNoise::new(SECRET_BOUND as f64, degree) * e_gsw_raw
+            // m = 2t
+            + gadget_noise(2 * T_RLWE_TO_GSW, Z_RLWE_TO_GSW, error);
+        report.push(NoisePhase::RlweToGsw, e_gsw, None);
+
+        // First dimension (NU1)
+        let e_firstdim =
+            BoundedNoise::new_matrix((P / 2) as f64, degree, 1, self.packed_dim1_size())
+                * e_reg.with_dimension(self.packed_dim1_size(), 1);
+        report.push(NoisePhase::FirstDim, e_firstdim, Some(q1_threshold));
+
+        // Folding (NU2)
+        let e_fold = select_noise(e_gsw, e_firstdim, NU2);
+        report.push(NoisePhase::Fold, e_fold, Some(q1_threshold));
+
+        // Rotating (NU3)
+        let e_rot = select_noise(e_gsw, e_fold, self.nu3());
+        report.push(NoisePhase::Rotate, e_rot, Some(q1_threshold));
+
+        // Proj/select (NU3) + ring packing
+        let e_proj_component = proj_noise(
+            SubGaussianNoise::new(0_f64, degree),
+            T_PROJ_LONG,
+            Z_PROJ_LONG,
+            self.nu3(),
+        );
+        report.push(
+            NoisePhase::Project,
+            e_rot + e_proj_component,
+            Some(q1_threshold),
+        );
+        let ring_num_records = min(BATCH_SIZE, self.pack_ratio_response());
+        let e_pack_ring = e_rot + e_proj_component * Independent(ring_num_records as f64);
+        report.push(NoisePhase::RingPacking, e_pack_ring, Some(q1_threshold));
+
+        // Vector packing
+        let vec_num_elems = min(BATCH_SIZE.div_ceil(self.pack_ratio_response()), N_VEC);
+        let e_pack_vec = e_pack_ring
+            + gadget_noise(T_VECTORIZE, Z_VECTORIZE, error_vec) * Independent(vec_num_elems as f64);
+        report.push(NoisePhase::Vectorize, e_pack_vec, Some(q1_threshold));
+
+        // Switching, in units of Q3
+        assert_eq!(Z_COMPRESS, 2);
+        let e_subg_preswitch = e_pack_vec * Independent(8_f64) * Scale(Q3 as f64 / Q1 as f64);
+        let e_subg_gadget = (secret_vec * BoundedNoise::new(1_f64, degree)
+            + gadget_noise(T_COMPRESS, Z_COMPRESS, error_switch * Scale(2_f64)))
+            * Scale(Q3 as f64 / (2_f64 * Q2 as f64));
+        let e_subg = e_subg_preswitch + e_subg_gadget;
+        let e_round = (2f64 + Q3 as f64 / Q1 as f64 * (Q1 % P) as f64) / 2f64;
+        let q3_threshold = 0.5_f64 * (Q3 / P) as f64;
+        // The rounding term is bounded by e_round, so it is subgaussian with width sqrt(2 pi) e_round
+        let e_switch = e_subg + SubGaussianNoise::new(2_f64 * PI * e_round.powi(2), degree);
+        report.push(NoisePhase::Compress, e_switch, Some(q3_threshold));
+
+        let error_rate = 2_f64
+            * (D3 as f64)
+            * f64::exp(-PI * (q3_threshold - e_round).powi(2) / e_subg.variance());
+        report.error_rate = clamp(error_rate, 0_f64, 1_f64);
+        report
+    }
+
+    fn public_param_size_impl(&self, verbose: bool) -> usize {
+        let Self {
+            Q1,
+            D1,
+            T_PROJ_SHORT,
+            T_PROJ_LONG,
+            T_RLWE_TO_GSW,
+            T_VECTORIZE,
+            N_VEC,
+            Q2,
+            T_COMPRESS,
+            ..
+        } = *self;
+        macro_rules! note {
+            ($($arg: tt)*) => {
+                if verbose {
+                    info!($($arg)*)
+                }
+            };
+        }
+        let automorph_elems = floor_log(2, D1 as u64) * (T_PROJ_SHORT + T_PROJ_LONG);
+        let reg_to_gsw_elems = 2 * T_RLWE_TO_GSW;
+        let scal_to_vec_elems = N_VEC * N_VEC * T_VECTORIZE;
+        let q_elem_bits = D1 * ceil_log(2, Q1);
+
+        let compress_elems = N_VEC * T_COMPRESS;
+        let q2_elem_bits = D1 * ceil_log(2, Q2);
+
+        // The uniformly random components of the public params are replaced by 32 byte PRG seeds,
+        // see `public_params_to_bytes`.
+        let seed_size = 32 * self.public_param_seed_count();
+
+        note!(
+            "automorph pp: {:.3} KiB",
+            (automorph_elems * q_elem_bits / 8) as f64 / 1024_f64
+        );
+        note!(
+            "rlwe to GSW pp: {:.3} KiB",
+            (reg_to_gsw_elems * q_elem_bits / 8) as f64 / 1024_f64
+        );
+        note!(
+            "scal to vec pp: {:.3} KiB",
+            (scal_to_vec_elems * q_elem_bits / 8) as f64 / 1024_f64
+        );
+        note!(
+            "compress pp: {:.3} KiB",
+            (compress_elems * q2_elem_bits / 8) as f64 / 1024_f64
+        );
+        note!("pp seeds: {:.3} KiB", seed_size as f64 / 1024_f64);
+        seed_size
+            + ((automorph_elems + reg_to_gsw_elems + scal_to_vec_elems) * q_elem_bits
+                + compress_elems * q2_elem_bits)
+                .div_ceil(8)
+    }
+}
+
+/// Initial state for [`fingerprint_extend`] (the FNV-1a offset basis).
+pub const FINGERPRINT_INIT: u64 = 0xcbf29ce484222325;
+
+///
+/// Continues an FNV-1a hash over the little-endian bytes of `values`. Schemes built on top of
+/// RESPIRE use this to fold their own parameters into [`RespireParamsExpanded::fingerprint`].
+///
+pub fn fingerprint_extend(mut hash: u64, values: &[u64]) -> u64 {
+    const FNV_PRIME: u64 = 0x100000001b3;
+    for byte in values.iter().flat_map(|v| v.to_le_bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
 #[macro_export]
 macro_rules! respire {
     ($params: expr) => {
@@ -372,7 +752,37 @@ macro_rules! respire_impl {
     };
 }
 
-pub trait Respire: PIR {
+///
+/// The client's secret keys. They are overwritten with zeros when dropped, and are not shown by
+/// `Debug`.
+///
+#[derive(Clone, PartialEq, Eq)]
+pub struct RespireQueryKey<S: Zeroize, V: Zeroize, C: Zeroize> {
+    /// RLWE/GSW secret
+    pub s_encode: S,
+    /// Vector RLWE secret
+    pub s_vec: V,
+    /// Compression target secret
+    pub s_small: C,
+}
+
+impl<S: Zeroize, V: Zeroize, C: Zeroize> Drop for RespireQueryKey<S, V, C> {
+    fn drop(&mut self) {
+        self.s_encode.zeroize();
+        self.s_vec.zeroize();
+        self.s_small.zeroize();
+    }
+}
+
+impl<S: Zeroize, V: Zeroize, C: Zeroize> std::fmt::Debug for RespireQueryKey<S, V, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RespireQueryKey { .. }")
+    }
+}
+
+pub trait Respire:
+    PIR<Database = SimdDatabase, DatabaseHint = (), QueryKey: Sync, PublicParams: Sync>
+{
     // Type aliases
     type RingP;
     type RingQ1;
@@ -394,16 +804,23 @@ pub trait Respire: PIR {
     type VecRLWEEncoding;
     type VecRLWEEncodingSmallTruncated: SerdeRound;
 
+    // Public params with the uniformly random components replaced by PRG seeds
+    type AutoParamsSeeded<const T: usize>;
+    type RLWEToGSWParamsSeeded;
+    type CompressParamsSeeded;
+    type VectorizeParamsSeeded;
+    type PublicParamsSeeded;
+
     // A single record
     type Record;
     // Packed records from a single response, after compression
     type RecordPackedSmall;
     // Packed records from a single response, before compression
     type RecordPacked;
-    type QueryOne: SerdeRound;
+    type QueryOne: SerdeRound + Sync;
     type QueryOneExpanded;
-    type AnswerOne: SerdeRound;
-    type AnswerOneCompressed: SerdeRound;
+    type AnswerOne: SerdeRound + Send + Sync;
+    type AnswerOneCompressed: SerdeRound + Send;
 
     // Constants
     const PACKED_DIM1_SIZE: usize;
@@ -423,25 +840,97 @@ pub trait Respire: PIR {
     const GSW_ROT_COUNT: usize;
     const GSW_COUNT: usize;
     const GSW_EXPAND_ITERS: usize;
+    const PUBLIC_PARAM_SEED_COUNT: usize;
+
+    fn setup_seeded(
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> (
+        <Self as PIR>::QueryKey,
+        <Self as Respire>::PublicParamsSeeded,
+    ) {
+        Self::setup_seeded_with_rng(&mut ChaCha20Rng::from_entropy(), time_stats)
+    }
+
+    ///
+    /// Like [`Respire::setup_seeded`], but drawing all randomness (secrets, errors, and the seeds
+    /// of the public params) from `rng`.
+    ///
+    fn setup_seeded_with_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> (
+        <Self as PIR>::QueryKey,
+        <Self as Respire>::PublicParamsSeeded,
+    );
+
+    ///
+    /// Deterministically derives the query key and public params from a 32 byte master seed. The
+    /// same seed always gives the same keys, so the seed must be kept as secret as the query key.
+    ///
+    fn setup_from_seed(
+        seed: [u8; 32],
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> (<Self as PIR>::QueryKey, <Self as PIR>::PublicParams) {
+        let (qk, pp_seeded) =
+            Self::setup_seeded_with_rng(&mut ChaCha20Rng::from_seed(seed), time_stats);
+        (qk, Self::public_params_expand(&pp_seeded))
+    }
+
+    fn public_params_expand(
+        pp: &<Self as Respire>::PublicParamsSeeded,
+    ) -> <Self as PIR>::PublicParams;
 
     fn query_one(
         qk: &<Self as PIR>::QueryKey,
         idx: usize,
         time_stats: Option<&mut Stats<Duration>>,
-    ) -> <Self as Respire>::QueryOne;
+    ) -> Result<<Self as Respire>::QueryOne, PirError> {
+        Self::query_one_with_rng(qk, idx, &mut ChaCha20Rng::from_entropy(), time_stats)
+    }
+
+    ///
+    /// Like [`Respire::query_one`], but drawing the encryption randomness from `rng`.
+    ///
+    fn query_one_with_rng<R: RngCore + CryptoRng>(
+        qk: &<Self as PIR>::QueryKey,
+        idx: usize,
+        rng: &mut R,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<<Self as Respire>::QueryOne, PirError>;
+
+    ///
+    /// Like [`PIR::query`], but drawing the encryption randomness of every query from `rng`.
+    ///
+    fn query_with_rng<R: RngCore + CryptoRng>(
+        qk: &<Self as PIR>::QueryKey,
+        indices: &[usize],
+        rng: &mut R,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<(<Self as PIR>::Query, <Self as PIR>::State), PirError>;
     fn answer_one(
         pp: &<Self as PIR>::PublicParams,
         db: &<Self as PIR>::Database,
         q: &<Self as Respire>::QueryOne,
-        qk: Option<&<Self as PIR>::QueryKey>,
         time_stats: Option<&mut Stats<Duration>>,
-    ) -> <Self as Respire>::AnswerOne;
+    ) -> Result<<Self as Respire>::AnswerOne, PirError>;
     fn answer_compress_chunk(
         pp: &<Self as PIR>::PublicParams,
         chunk: &[<Self as Respire>::AnswerOne],
-        qk: Option<&<Self as PIR>::QueryKey>,
         time_stats: Option<&mut Stats<Duration>>,
     ) -> <Self as Respire>::AnswerOneCompressed;
+
+    ///
+    /// Like [`PIR::answer`], but recording the noise of every intermediate ciphertext in `probe`.
+    /// The queries are answered sequentially. This is a debugging aid for tests, see
+    /// [`NoiseProbe`].
+    ///
+    fn answer_probed(
+        pp: &<Self as PIR>::PublicParams,
+        db: &<Self as PIR>::Database,
+        qs: &[<Self as Respire>::QueryOne],
+        probe: &mut NoiseProbe<<Self as PIR>::QueryKey>,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<<Self as PIR>::Response, PirError>;
     fn answer_compress_vec(
         pp: &<Self as PIR>::PublicParams,
         vec: &<Self as Respire>::VecRLWEEncoding,
@@ -451,7 +940,23 @@ pub trait Respire: PIR {
         qk: &<Self as PIR>::QueryKey,
         r: &<Self as Respire>::AnswerOneCompressed,
         time_stats: Option<&mut Stats<Duration>>,
-    ) -> Vec<<Self as PIR>::RecordBytes>;
+    ) -> Result<Vec<<Self as PIR>::RecordBytes>, PirError>;
+
+    // Wire format, see `crate::pir::wire`
+    fn query_one_to_bytes(q: &<Self as Respire>::QueryOne) -> Vec<u8>;
+    fn query_one_from_bytes(bytes: &[u8]) -> Result<<Self as Respire>::QueryOne, WireError>;
+    fn response_one_len(r: &<Self as Respire>::AnswerOneCompressed) -> usize;
+    fn response_one_to_bytes(r: &<Self as Respire>::AnswerOneCompressed) -> Vec<u8>;
+    fn response_one_from_bytes(
+        bytes: &[u8],
+        len: usize,
+    ) -> Result<<Self as Respire>::AnswerOneCompressed, WireError>;
+    fn public_params_to_bytes(pp: &<Self as Respire>::PublicParamsSeeded) -> Vec<u8>;
+    fn public_params_from_bytes(
+        bytes: &[u8],
+    ) -> Result<<Self as Respire>::PublicParamsSeeded, WireError>;
+    fn query_key_to_bytes(qk: &<Self as PIR>::QueryKey) -> Zeroizing<Vec<u8>>;
+    fn query_key_from_bytes(bytes: &[u8]) -> Result<<Self as PIR>::QueryKey, WireError>;
 
     fn params() -> RespireParamsExpanded;
     fn params_error_rate_estimate() -> f64;
@@ -459,6 +964,7 @@ pub trait Respire: PIR {
     fn params_query_one_size() -> usize;
     fn params_record_one_size() -> usize;
     fn params_response_one_size(trunc_len: usize) -> usize;
+    fn params_query_key_size() -> usize;
 }
 
 #[repr(transparent)]
@@ -488,11 +994,11 @@ impl<const LEN: usize> PIRRecordBytes for RecordBytesImpl<LEN> {
 
 respire_impl!(PIR, {
     // Associated types
-    type QueryKey = (
+    type QueryKey = RespireQueryKey<
         <Self as Respire>::EncodingSecret,
         <Self as Respire>::VecEncodingSecret,
         <Self as Respire>::VecEncodingSecretQ2Small,
-    );
+    >;
     type PublicParams = (
         (
             Vec<<Self as Respire>::AutoParamsShort>,
@@ -509,9 +1015,10 @@ respire_impl!(PIR, {
     /// We structure the database as `[2] x [D1 / S] x [DIM2_SIZE] x [DIM1_SIZE] x [S]` for optimal first dimension
     /// processing. The outermost pair is the first resp. second CRT projections, packed as two u32 into one u64;
     /// `S` is the SIMD lane count that we can use, i.e. 4 for AVX2.
-    type Database = Vec<SimdVec>;
+    type Database = SimdDatabase;
     type DatabaseHint = ();
-    type State = ();
+    /// Number of requested records
+    type State = usize;
 
     // Public types & constants
     type RecordBytes = RecordBytesImpl<BYTES_PER_RECORD>;
@@ -574,24 +1081,10 @@ respire_impl!(PIR, {
         assert_eq!(Self::DB_SIZE % Self::PACK_RATIO_DB, 0);
 
         let records_packed_generator = |chunk_idx: usize| {
-            let chunk = (Self::PACK_RATIO_DB * chunk_idx..Self::PACK_RATIO_DB * (chunk_idx + 1))
-                .map(|idx| records_encoded_generator(idx));
-            let mut record_packed = IntModCyclo::<D1, P>::zero();
-            for (record_in_chunk, record) in chunk.enumerate() {
-                for (coeff_idx, coeff) in record.coeff.iter().enumerate() {
-                    record_packed.coeff[Self::PACK_RATIO_DB * coeff_idx + record_in_chunk] = *coeff;
-                }
-            }
-            let value = <Self as Respire>::RingQ1Fast::from(&record_packed.include_into::<Q1>());
-            let mut packed_value = [0u64; D1];
-            for i in 0..D1 {
-                packed_value[i] = {
-                    let lo = u64::from(value.proj1.evals[i]);
-                    let hi = u64::from(value.proj2.evals[i]);
-                    (hi << 32) | lo
-                };
-            }
-            packed_value
+            Self::pack_db_chunk(
+                (Self::PACK_RATIO_DB * chunk_idx..Self::PACK_RATIO_DB * (chunk_idx + 1))
+                    .map(|idx| records_encoded_generator(idx)),
+            )
         };
 
         // Quick and dirty buffered iterator implementation
@@ -666,12 +1159,7 @@ respire_impl!(PIR, {
             db = (0..(D1 * Self::PACKED_DB_SIZE)).map(|_| 0_u64).collect();
             for (db_idx, record_packed) in records_packed_iter.enumerate() {
                 for eval_vec_idx in 0..D1 {
-                    // Transpose the index
-                    let (db_i, db_j) = (
-                        db_idx / Self::PACKED_DIM2_SIZE,
-                        db_idx % Self::PACKED_DIM2_SIZE,
-                    );
-                    let db_idx_t = db_j * Self::PACKED_DIM1_SIZE + db_i;
+                    let db_idx_t = Self::db_idx_transposed(db_idx);
 
                     let to_idx = eval_vec_idx * Self::PACKED_DB_SIZE + db_idx_t;
                     let from_idx = eval_vec_idx;
@@ -688,12 +1176,7 @@ respire_impl!(PIR, {
 
             for (db_idx, record_packed) in records_packed_iter.enumerate() {
                 for eval_vec_idx in 0..(D1 / SIMD_LANES) {
-                    // Transpose the index
-                    let (db_i, db_j) = (
-                        db_idx / Self::PACKED_DIM2_SIZE,
-                        db_idx % Self::PACKED_DIM2_SIZE,
-                    );
-                    let db_idx_t = db_j * Self::PACKED_DIM1_SIZE + db_i;
+                    let db_idx_t = Self::db_idx_transposed(db_idx);
 
                     let mut db_vec: SimdVec = Aligned32([0_u64; 4]);
                     for lane in 0..SIMD_LANES {
@@ -713,130 +1196,134 @@ respire_impl!(PIR, {
         }
 
         info!("Done processing DB");
-        (db, ())
+        (db.into(), ())
     }
 
-    fn setup(
+    fn save_db(
+        db: &<Self as PIR>::Database,
+        _: &<Self as PIR>::DatabaseHint,
+        path: &Path,
+    ) -> io::Result<()> {
+        write_db_file(path, Self::params().fingerprint(), &[db], &[])
+    }
+
+    fn load_db(path: &Path) -> io::Result<(<Self as PIR>::Database, <Self as PIR>::DatabaseHint)> {
+        let part_len = (D1 / SIMD_LANES) * Self::PACKED_DB_SIZE;
+        let (parts, hint) = map_db_file(path, Self::params().fingerprint(), part_len)?;
+        if parts.len() != 1 || !hint.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "database file does not hold a single RESPIRE database",
+            ));
+        }
+        Ok((parts.into_iter().next().unwrap(), ()))
+    }
+
+    fn update_records(
+        db: &mut <Self as PIR>::Database,
+        _: &<Self as PIR>::DatabaseHint,
+        records: &[(usize, <Self as PIR>::RecordBytes)],
         time_stats: Option<&mut Stats<Duration>>,
-    ) -> (<Self as PIR>::QueryKey, <Self as PIR>::PublicParams) {
+    ) -> Result<(), PirError> {
         let begin = Instant::now();
-        // RLWE/GSW secret
-        let s_encode = Self::encode_setup();
+        if let Some(&(idx, _)) = records.iter().find(|(idx, _)| *idx >= Self::DB_SIZE) {
+            return Err(PirError::IndexOutOfRange {
+                idx,
+                num_records: Self::DB_SIZE,
+            });
+        }
 
-        // Vector RLWE secret
-        let s_vec: <Self as Respire>::VecEncodingSecret = Self::encode_vec_setup();
+        let mut updates_by_chunk: BTreeMap<usize, Vec<(usize, &<Self as PIR>::RecordBytes)>> =
+            BTreeMap::new();
+        for (idx, record) in records {
+            updates_by_chunk
+                .entry(idx / Self::PACK_RATIO_DB)
+                .or_default()
+                .push((idx % Self::PACK_RATIO_DB, record));
+        }
 
-        // Compression target
-        let s_small: <Self as Respire>::VecEncodingSecretQ2Small = {
-            let mut rng = ChaCha20Rng::from_entropy();
-            let mut result = Matrix::zero();
-            for i in 0..N_VEC {
-                result[(i, 0)] = IntModCycloEval::rand_discrete_gaussian::<
-                    _,
-                    SECRET_WIDTH_COMPRESS_MILLIONTHS,
-                >(&mut rng);
+        let db = db.to_mut();
+        for (chunk_idx, updates) in updates_by_chunk {
+            let mut chunk = Self::unpack_db_chunk(db, chunk_idx);
+            for (record_in_chunk, record) in updates {
+                chunk[record_in_chunk] = Self::encode_record(record);
             }
-            result
-        };
-
-        // Vector secret mod Q2 (compression source secret)
-        let s_vec_q2 = s_vec.map_ring(|r| {
-            IntModCycloEval::from(IntModCyclo::from(
-                IntModCyclo::<D1, Q1>::from(r)
-                    .coeff
-                    .map(|x| IntMod::from(i64::from(x))),
-            ))
-        });
-        let s_small_q2 =
-            s_small.map_ring(|r| IntModCycloEval::from(IntModCyclo::from(r).include_dim()));
-        let compress_params = Self::compress_setup(&s_vec_q2, &s_small_q2);
-
-        let mut auto_params_short: Vec<<Self as Respire>::AutoParamsShort> =
-            Vec::with_capacity(Self::RLWE_EXPAND_ITERS);
-        for i in 0..floor_log(2, D1 as u64) {
-            let tau_power = (D1 >> i) + 1;
-            auto_params_short.push(Self::auto_setup::<T_PROJ_SHORT, Z_PROJ_SHORT>(
-                tau_power, &s_encode,
-            ));
-        }
-        let mut auto_params_long: Vec<<Self as Respire>::AutoParamsLong> =
-            Vec::with_capacity(Self::GSW_EXPAND_ITERS);
-        for i in 0..floor_log(2, D1 as u64) {
-            let tau_power = (D1 >> i) + 1;
-            auto_params_long.push(Self::auto_setup::<T_PROJ_LONG, Z_PROJ_LONG>(
-                tau_power, &s_encode,
-            ));
+            Self::write_db_chunk(db, chunk_idx, &Self::pack_db_chunk(chunk.into_iter()));
         }
 
-        let rlwe_to_gsw_params = Self::rlwe_to_gsw_setup(&s_encode);
-        let vectorize_params = Self::vectorize_setup(&s_encode, &s_vec);
-
         let end = Instant::now();
         if let Some(time_stats) = time_stats {
-            time_stats.add("setup", end - begin);
+            time_stats.add("update", end - begin);
         }
+        Ok(())
+    }
 
-        (
-            (s_encode, s_vec, s_small),
-            (
-                (auto_params_short, auto_params_long),
-                rlwe_to_gsw_params,
-                compress_params,
-                vectorize_params,
-            ),
-        )
+    fn setup(
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> (<Self as PIR>::QueryKey, <Self as PIR>::PublicParams) {
+        let (qk, pp_seeded) = Self::setup_seeded(time_stats);
+        (qk, Self::public_params_expand(&pp_seeded))
     }
 
     fn query(
         qk: &<Self as PIR>::QueryKey,
         indices: &[usize],
         _: &<Self as PIR>::DatabaseHint,
-        mut time_stats: Option<&mut Stats<Duration>>,
-    ) -> (<Self as PIR>::Query, <Self as PIR>::State) {
-        assert_eq!(indices.len(), Self::BATCH_SIZE);
-        let q = indices
-            .iter()
-            .copied()
-            .map(|idx| Self::query_one(qk, idx, time_stats.as_deref_mut()))
-            .collect_vec();
-        (q, ())
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<(<Self as PIR>::Query, <Self as PIR>::State), PirError> {
+        Self::query_with_rng(qk, indices, &mut ChaCha20Rng::from_entropy(), time_stats)
     }
 
     fn answer(
         pp: &<Self as PIR>::PublicParams,
         db: &<Self as PIR>::Database,
         qs: &<Self as PIR>::Query,
-        qk: Option<&<Self as PIR>::QueryKey>,
         mut time_stats: Option<&mut Stats<Duration>>,
-    ) -> <Self as PIR>::Response {
-        assert_eq!(qs.len(), Self::BATCH_SIZE);
-        let answers = qs
-            .iter()
-            .map(|q| Self::answer_one(pp, db, q, qk, time_stats.as_deref_mut()))
-            .collect_vec();
-        let answers_compressed = answers
+    ) -> Result<<Self as PIR>::Response, PirError> {
+        Self::check_batch_size(qs.len())?;
+        let answers = parallel::map_with_stats(qs.len(), time_stats.as_deref_mut(), |i, stats| {
+            Self::answer_one(pp, db, &qs[i], stats)
+        })
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+        let chunks = answers
             .chunks(N_VEC * Self::PACK_RATIO_RESPONSE)
-            .map(|chunk| Self::answer_compress_chunk(pp, chunk, qk, time_stats.as_deref_mut()))
             .collect_vec();
-        answers_compressed
+        let answers_compressed = parallel::map_with_stats(chunks.len(), time_stats, |i, stats| {
+            Self::answer_compress_chunk(pp, chunks[i], stats)
+        });
+        Ok(answers_compressed)
     }
 
     fn extract(
         qk: &Self::QueryKey,
         r: &Self::Response,
-        _: &Self::State,
+        num_requested: &Self::State,
         mut time_stats: Option<&mut Stats<Duration>>,
-    ) -> Vec<Self::RecordBytes> {
-        let mut result = Vec::with_capacity(Self::BATCH_SIZE);
+    ) -> Result<Vec<Self::RecordBytes>, PirError> {
+        let num_requested = *num_requested;
+        let expected_len =
+            Self::padded_batch_size(num_requested).div_ceil(Self::RESPONSE_CHUNK_SIZE);
+        if r.len() != expected_len {
+            return Err(PirError::BatchSizeMismatch {
+                expected: expected_len,
+                found: r.len(),
+            });
+        }
+        // Drop the records of the dummy queries, and of the unused slots of the last ring element
+        let mut result = Vec::with_capacity(num_requested);
         for r_one in r {
-            let extracted = Self::extract_one(qk, r_one, time_stats.as_deref_mut());
+            let extracted = Self::extract_one(qk, r_one, time_stats.as_deref_mut())?;
             for record in extracted {
-                if result.len() < Self::BATCH_SIZE {
+                if result.len() < num_requested {
                     result.push(record);
                 }
             }
         }
-        result
+        if result.len() != num_requested {
+            return Err(PirError::MalformedResponse);
+        }
+        Ok(result)
     }
 });
 
@@ -872,6 +1359,20 @@ respire_impl!(Respire, {
         Vec<IntModCyclo<D2, Q3>>,
     );
 
+    type AutoParamsSeeded<const T: usize> = ([u8; 32], Matrix<1, T, Self::RingQ1Fast>, usize);
+    type RLWEToGSWParamsSeeded = ([u8; 32], Matrix<1, M_RLWE_TO_GSW, Self::RingQ1Fast>);
+    type CompressParamsSeeded = ([u8; 32], Matrix<N_VEC, T_COMPRESS, IntModCycloEval<D1, Q2>>);
+    type VectorizeParamsSeeded = Vec<([u8; 32], Matrix<N_VEC, T_VECTORIZE, Self::RingQ1Fast>)>;
+    type PublicParamsSeeded = (
+        (
+            Vec<Self::AutoParamsSeeded<T_PROJ_SHORT>>,
+            Vec<Self::AutoParamsSeeded<T_PROJ_LONG>>,
+        ),
+        Self::RLWEToGSWParamsSeeded,
+        Self::CompressParamsSeeded,
+        Self::VectorizeParamsSeeded,
+    );
+
     type Record = IntModCyclo<D3, P>;
     type RecordPackedSmall = Matrix<N_VEC, 1, IntModCyclo<D2, P>>;
     type RecordPacked = IntModCyclo<D1, P>;
@@ -906,45 +1407,162 @@ respire_impl!(Respire, {
 
     const GSW_COUNT: usize = (Self::GSW_FOLD_COUNT + Self::GSW_ROT_COUNT) * T_GSW;
     const GSW_EXPAND_ITERS: usize = ceil_log(2, Self::GSW_COUNT as u64);
+    const PUBLIC_PARAM_SEED_COUNT: usize = 2 * floor_log(2, D1 as u64) + 2 + N_VEC;
 
-    fn query_one(
-        (s_encode, _, _): &<Self as PIR>::QueryKey,
-        idx: usize,
+    fn setup_seeded_with_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
         time_stats: Option<&mut Stats<Duration>>,
-    ) -> <Self as Respire>::QueryOne {
+    ) -> (
+        <Self as PIR>::QueryKey,
+        <Self as Respire>::PublicParamsSeeded,
+    ) {
         let begin = Instant::now();
-        assert!(idx < Self::DB_SIZE);
-        let last_dims_size = 2usize.pow((Self::NU2 + Self::NU3) as u32);
-        let (idx_i, idx_j) = (idx / last_dims_size, idx % last_dims_size);
+        // Build the sampler tables up front, so no table is built while sampling secrets or noise
+        for width in [
+            ERROR_WIDTH_MILLIONTHS,
+            ERROR_WIDTH_VEC_MILLIONTHS,
+            ERROR_WIDTH_COMPRESS_MILLIONTHS,
+            SECRET_WIDTH_VEC_MILLIONTHS,
+            SECRET_WIDTH_COMPRESS_MILLIONTHS,
+        ] {
+            DiscreteGaussian::precompute::<CdtSampler>(width);
+        }
 
-        let mut mu_rlwe = <Self as Respire>::RingQ1::zero();
-        for i in 0..Self::RLWE_COUNT {
-            mu_rlwe.coeff[reverse_bits_fast::<D1>(i)] =
-                IntMod::<P>::from((i == idx_i) as u64).scale_up_into();
+        // RLWE/GSW secret
+        let s_encode = Self::encode_setup(rng);
+
+        // Vector RLWE secret
+        let s_vec: <Self as Respire>::VecEncodingSecret = Self::encode_vec_setup(rng);
+
+        // Compression target
+        let s_small: <Self as Respire>::VecEncodingSecretQ2Small = {
+            let mut result = Matrix::zero();
+            for i in 0..N_VEC {
+                result[(i, 0)] = IntModCycloEval::rand_discrete_gaussian::<
+                    _,
+                    SECRET_WIDTH_COMPRESS_MILLIONTHS,
+                >(rng);
+            }
+            result
+        };
+
+        // Vector secret mod Q2 (compression source secret)
+        let s_vec_q2 = s_vec.map_ring(|r| {
+            IntModCycloEval::from(IntModCyclo::from(
+                IntModCyclo::<D1, Q1>::from(r)
+                    .coeff
+                    .map(|x| IntMod::from(i64::from(x))),
+            ))
+        });
+        let s_small_q2 =
+            s_small.map_ring(|r| IntModCycloEval::from(IntModCyclo::from(r).include_dim()));
+        let compress_params = Self::compress_setup_seeded(&s_vec_q2, &s_small_q2, rng);
+
+        let mut auto_params_short = Vec::with_capacity(Self::RLWE_EXPAND_ITERS);
+        for i in 0..floor_log(2, D1 as u64) {
+            let tau_power = (D1 >> i) + 1;
+            auto_params_short.push(Self::auto_setup_seeded::<T_PROJ_SHORT, Z_PROJ_SHORT, _>(
+                tau_power, &s_encode, rng,
+            ));
+        }
+        let mut auto_params_long = Vec::with_capacity(Self::GSW_EXPAND_ITERS);
+        for i in 0..floor_log(2, D1 as u64) {
+            let tau_power = (D1 >> i) + 1;
+            auto_params_long.push(Self::auto_setup_seeded::<T_PROJ_LONG, Z_PROJ_LONG, _>(
+                tau_power, &s_encode, rng,
+            ));
         }
 
-        // [NU2 + NU3] x [T_GSW]
-        let mut mu_gsw = <Self as Respire>::RingQ1::zero();
+        let rlwe_to_gsw_params = Self::rlwe_to_gsw_setup_seeded(&s_encode, rng);
+        let vectorize_params = Self::vectorize_setup_seeded(&s_encode, &s_vec, rng);
 
-        let mut bits = Vec::with_capacity(NU2);
-        let mut idx_j_curr = idx_j;
-        for _ in 0..(Self::NU2 + Self::NU3) {
-            bits.push(idx_j_curr % 2);
-            idx_j_curr /= 2;
+        let end = Instant::now();
+        if let Some(time_stats) = time_stats {
+            time_stats.add("setup", end - begin);
         }
 
-        for (bit_idx, bit) in bits.into_iter().rev().enumerate() {
-            let mut msg = IntMod::from(bit as u64);
-            for gsw_pow in 0..T_GSW {
-                let pack_idx = T_GSW * bit_idx + gsw_pow;
-                mu_gsw.coeff[reverse_bits_fast::<D1>(pack_idx)] = msg;
-                msg *= IntMod::from(Z_GSW);
-            }
+        (
+            RespireQueryKey {
+                s_encode,
+                s_vec,
+                s_small,
+            },
+            (
+                (auto_params_short, auto_params_long),
+                rlwe_to_gsw_params,
+                compress_params,
+                vectorize_params,
+            ),
+        )
+    }
+
+    fn public_params_expand(
+        (
+            (auto_params_short, auto_params_long),
+            rlwe_to_gsw_params,
+            compress_params,
+            vectorize_params,
+        ): &<Self as Respire>::PublicParamsSeeded,
+    ) -> <Self as PIR>::PublicParams {
+        (
+            (
+                auto_params_short
+                    .iter()
+                    .map(Self::auto_recover_from_seeded)
+                    .collect(),
+                auto_params_long
+                    .iter()
+                    .map(Self::auto_recover_from_seeded)
+                    .collect(),
+            ),
+            Self::rlwe_to_gsw_recover_from_seeded(rlwe_to_gsw_params),
+            Self::compress_recover_from_seeded(compress_params),
+            Self::vectorize_recover_from_seeded(vectorize_params),
+        )
+    }
+
+    fn query_with_rng<R: RngCore + CryptoRng>(
+        qk: &<Self as PIR>::QueryKey,
+        indices: &[usize],
+        rng: &mut R,
+        mut time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<(<Self as PIR>::Query, <Self as PIR>::State), PirError> {
+        if indices.is_empty() || indices.len() > Self::BATCH_SIZE {
+            return Err(PirError::BatchSizeMismatch {
+                expected: Self::BATCH_SIZE,
+                found: indices.len(),
+            });
+        }
+        // Dummy queries for record 0 fill up the last packed response ring element, so the server
+        // only learns the number of ring elements to respond with
+        let padding = Self::padded_batch_size(indices.len()) - indices.len();
+        let q = indices
+            .iter()
+            .copied()
+            .chain(std::iter::repeat_n(0, padding))
+            .map(|idx| Self::query_one_with_rng(qk, idx, rng, time_stats.as_deref_mut()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((q, indices.len()))
+    }
+
+    fn query_one_with_rng<R: RngCore + CryptoRng>(
+        RespireQueryKey { s_encode, .. }: &<Self as PIR>::QueryKey,
+        idx: usize,
+        rng: &mut R,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<<Self as Respire>::QueryOne, PirError> {
+        let begin = Instant::now();
+        if idx >= Self::DB_SIZE {
+            return Err(PirError::IndexOutOfRange {
+                idx,
+                num_records: Self::DB_SIZE,
+            });
         }
+        let (mu_rlwe, mu_gsw) = Self::query_messages(idx);
 
-        let (seed_rlwe, ct1_rlwe) = Self::encode_rlwe_seeded(s_encode, &mu_rlwe);
+        let (seed_rlwe, ct1_rlwe) = Self::encode_rlwe_seeded(s_encode, &mu_rlwe, rng);
         let ct1_rlwe_coeff = <Self as Respire>::RingQ1::from(&ct1_rlwe).coeff;
-        let (seed_gsw, ct1_gsw) = Self::encode_rlwe_seeded(s_encode, &mu_gsw);
+        let (seed_gsw, ct1_gsw) = Self::encode_rlwe_seeded(s_encode, &mu_gsw, rng);
         let ct1_gsw_coeff = <Self as Respire>::RingQ1::from(&ct1_gsw).coeff;
         let compressed_rlwe = (
             seed_rlwe,
@@ -963,102 +1581,60 @@ respire_impl!(Respire, {
         if let Some(time_stats) = time_stats {
             time_stats.add("query", end - begin);
         }
-        (compressed_rlwe, compressed_gsw)
+        Ok((compressed_rlwe, compressed_gsw))
     }
 
     fn answer_one(
         pp: &<Self as PIR>::PublicParams,
         db: &<Self as PIR>::Database,
         q: &<Self as Respire>::QueryOne,
-        qk: Option<&<Self as PIR>::QueryKey>,
-        mut time_stats: Option<&mut Stats<Duration>>,
-    ) -> <Self as Respire>::AnswerOne {
-        // Query expansion
-        let (rlwes, gsws_fold, gsws_rot) =
-            Self::answer_query_unpack(pp, q, qk, time_stats.as_deref_mut());
-        let rlwe_saved = rlwes[0].clone();
-
-        let i1 = Instant::now();
-
-        // First dimension
-        let c_firstdim = Self::answer_first_dim(db, &rlwes);
-        let firstdim_saved = c_firstdim[0].clone(); // save for noise logging
-        let i2 = Instant::now();
-
-        // Folding
-        let c_fold = Self::answer_fold(c_firstdim, gsws_fold.as_slice());
-        let i3 = Instant::now();
-
-        // Rotate select
-        let c_rot = Self::answer_rotate(&c_fold, gsws_rot.as_slice());
-        let i4 = Instant::now();
-
-        // Project
-        let c_proj = Self::answer_project(pp, &c_rot);
-        let i5 = Instant::now();
-
-        if let Some(time_stats) = time_stats {
-            time_stats.add("answer_first_dim", i2 - i1);
-            time_stats.add("answer_fold", i3 - i2);
-            time_stats.add("answer_rotate", i4 - i3);
-            time_stats.add("answer_project", i5 - i4);
-        }
-
-        if let Some((s_enc, _, _)) = qk {
-            if log_enabled!(Info) {
-                let e_rlwe = Self::noise_subgaussian_bits(s_enc, &rlwe_saved);
-                let e_firstdim = Self::noise_subgaussian_bits(s_enc, &firstdim_saved);
-                let e_fold = Self::noise_subgaussian_bits(s_enc, &c_fold);
-                let e_rot = Self::noise_subgaussian_bits(s_enc, &c_rot);
-                let e_proj = Self::noise_subgaussian_bits(s_enc, &c_proj);
-
-                info!("measured noise query expanded rlwe: {}", e_rlwe);
-                info!("measured noise first dim: {}", e_firstdim);
-                info!("measured noise fold: {}", e_fold);
-                info!("measured noise rotate: {}", e_rot);
-                // TODO: note that project noise is lower on the coefficients that are projected away. So reporting this average is a bit inaccurate.
-                info!("measured noise project*: {}", e_proj);
-            }
-        }
-
-        c_proj
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<<Self as Respire>::AnswerOne, PirError> {
+        Self::answer_one_probed(pp, db, q, None, time_stats)
     }
 
     fn answer_compress_chunk(
         pp: &<Self as PIR>::PublicParams,
         chunk: &[<Self as Respire>::AnswerOne],
-        qk: Option<&<Self as PIR>::QueryKey>,
         time_stats: Option<&mut Stats<Duration>>,
     ) -> <Self as Respire>::AnswerOneCompressed {
-        let begin = Instant::now();
-        let mut scalar_cts = Vec::with_capacity(Self::RESPONSE_CHUNK_SIZE);
-        let (_, _, _, vectorize_params) = pp;
-        for vec_idx in 0..N_VEC {
-            let mut scalar_ct = Matrix::zero();
-            for pack_idx in 0..Self::PACK_RATIO_RESPONSE {
-                let idx = vec_idx * Self::PACK_RATIO_RESPONSE + pack_idx;
-                if idx < chunk.len() {
-                    scalar_ct += &Self::rlwe_mul_x_pow(&chunk[idx], pack_idx * (D1 / D2));
-                }
-            }
-            scalar_cts.push(scalar_ct)
-        }
-        let vec = Self::scal_to_vec(vectorize_params, scalar_cts.as_slice().try_into().unwrap());
-        let compressed =
-            Self::answer_compress_vec(pp, &vec, chunk.len().div_ceil(Self::PACK_RATIO_RESPONSE));
-
-        let end = Instant::now();
-        if let Some(time_stats) = time_stats {
-            time_stats.add("answer_compress", end - begin);
-        }
+        Self::answer_compress_chunk_probed(pp, chunk, None, time_stats)
+    }
 
-        if let Some((_, s_vec, _)) = qk {
-            info!(
-                "pre compression noise (subgaussian widths): 2^({})",
-                Self::noise_subgaussian_bits_vec(s_vec, &vec)
-            );
-        }
-        compressed
+    fn answer_probed(
+        pp: &<Self as PIR>::PublicParams,
+        db: &<Self as PIR>::Database,
+        qs: &[<Self as Respire>::QueryOne],
+        probe: &mut NoiseProbe<<Self as PIR>::QueryKey>,
+        mut time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<<Self as PIR>::Response, PirError> {
+        Self::check_batch_size(qs.len())?;
+        let answers = qs
+            .iter()
+            .enumerate()
+            .map(|(i, q)| {
+                Self::answer_one_probed(
+                    pp,
+                    db,
+                    q,
+                    Some((&mut *probe, i)),
+                    time_stats.as_deref_mut(),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let answers_compressed = answers
+            .chunks(N_VEC * Self::PACK_RATIO_RESPONSE)
+            .enumerate()
+            .map(|(i, chunk)| {
+                Self::answer_compress_chunk_probed(
+                    pp,
+                    chunk,
+                    Some((&mut *probe, i)),
+                    time_stats.as_deref_mut(),
+                )
+            })
+            .collect();
+        Ok(answers_compressed)
     }
 
     fn answer_compress_vec(
@@ -1104,14 +1680,238 @@ respire_impl!(Respire, {
         qk: &<Self as PIR>::QueryKey,
         r: &<Self as Respire>::AnswerOneCompressed,
         time_stats: Option<&mut Stats<Duration>>,
-    ) -> Vec<<Self as PIR>::RecordBytes> {
+    ) -> Result<Vec<<Self as PIR>::RecordBytes>, PirError> {
+        let (_, c_m_hat_trunc) = r;
+        if c_m_hat_trunc.is_empty() || c_m_hat_trunc.len() > N_VEC {
+            return Err(PirError::MalformedResponse);
+        }
         let begin = Instant::now();
         let ret = Self::extract_bytes_one(&Self::extract_ring_one(qk, r));
         let end = Instant::now();
         if let Some(time_stats) = time_stats {
             time_stats.add("extract", end - begin);
         }
-        ret
+        Ok(ret)
+    }
+
+    fn query_one_to_bytes(
+        ((seed_rlwe, vec_rlwe), (seed_gsw, vec_gsw)): &<Self as Respire>::QueryOne,
+    ) -> Vec<u8> {
+        let mut bits = BitVec::<u8, Msb0>::with_capacity(
+            (Self::RLWE_COUNT + Self::GSW_COUNT) * ceil_log(2, Q1),
+        );
+        pack_coeffs(&mut bits, vec_rlwe.iter().copied());
+        pack_coeffs(&mut bits, vec_gsw.iter().copied());
+        let mut result = Vec::with_capacity(Self::params_query_one_size());
+        result.extend_from_slice(seed_rlwe);
+        result.extend_from_slice(seed_gsw);
+        result.extend_from_slice(bits.as_raw_slice());
+        debug_assert_eq!(result.len(), Self::params_query_one_size());
+        result
+    }
+
+    fn query_one_from_bytes(bytes: &[u8]) -> Result<<Self as Respire>::QueryOne, WireError> {
+        if bytes.len() != Self::params_query_one_size() {
+            return Err(WireError::InvalidLength(bytes.len()));
+        }
+        let seed_rlwe: [u8; 32] = bytes[0..32].try_into().unwrap();
+        let seed_gsw: [u8; 32] = bytes[32..64].try_into().unwrap();
+        let mut cursor = BitCursor::new(&bytes[64..]);
+        let mut vec_rlwe = vec![IntMod::zero(); Self::RLWE_COUNT];
+        let mut vec_gsw = vec![IntMod::zero(); Self::GSW_COUNT];
+        cursor.read_coeffs(&mut vec_rlwe)?;
+        cursor.read_coeffs(&mut vec_gsw)?;
+        Ok(((seed_rlwe, vec_rlwe), (seed_gsw, vec_gsw)))
+    }
+
+    fn response_one_len((_, c_m_hat_trunc): &<Self as Respire>::AnswerOneCompressed) -> usize {
+        c_m_hat_trunc.len()
+    }
+
+    fn response_one_to_bytes(
+        (c_r_hat, c_m_hat_trunc): &<Self as Respire>::AnswerOneCompressed,
+    ) -> Vec<u8> {
+        let mut bits = BitVec::<u8, Msb0>::with_capacity(
+            D2 * (ceil_log(2, Q2) + c_m_hat_trunc.len() * ceil_log(2, Q3)),
+        );
+        pack_coeffs(&mut bits, c_r_hat.coeff.iter().copied());
+        for c_m in c_m_hat_trunc.iter() {
+            pack_coeffs(&mut bits, c_m.coeff.iter().copied());
+        }
+        let result = bits.into_vec();
+        debug_assert_eq!(
+            result.len(),
+            Self::params_response_one_size(c_m_hat_trunc.len())
+        );
+        result
+    }
+
+    fn response_one_from_bytes(
+        bytes: &[u8],
+        len: usize,
+    ) -> Result<<Self as Respire>::AnswerOneCompressed, WireError> {
+        if len > N_VEC || bytes.len() != Self::params_response_one_size(len) {
+            return Err(WireError::InvalidLength(bytes.len()));
+        }
+        let mut cursor = BitCursor::new(bytes);
+        let mut c_r_hat = IntModCyclo::<D2, Q2>::zero();
+        cursor.read_coeffs(&mut c_r_hat.coeff)?;
+        let mut c_m_hat_trunc = vec![IntModCyclo::<D2, Q3>::zero(); len];
+        for c_m in c_m_hat_trunc.iter_mut() {
+            cursor.read_coeffs(&mut c_m.coeff)?;
+        }
+        Ok((c_r_hat, c_m_hat_trunc))
+    }
+
+    fn public_params_to_bytes(
+        (
+            (auto_params_short, auto_params_long),
+            (seed_rlwe_to_gsw, bottom_rlwe_to_gsw),
+            (seed_compress, bottom_compress),
+            vectorize_params,
+        ): &<Self as Respire>::PublicParamsSeeded,
+    ) -> Vec<u8> {
+        let mut result = Vec::with_capacity(Self::params_public_param_size());
+        for (seed, _, _) in auto_params_short.iter() {
+            result.extend_from_slice(seed);
+        }
+        for (seed, _, _) in auto_params_long.iter() {
+            result.extend_from_slice(seed);
+        }
+        result.extend_from_slice(seed_rlwe_to_gsw);
+        result.extend_from_slice(seed_compress);
+        for (seed, _) in vectorize_params.iter() {
+            result.extend_from_slice(seed);
+        }
+
+        let mut bits = BitVec::<u8, Msb0>::new();
+        for (_, bottom, _) in auto_params_short.iter() {
+            Self::pack_ring_q1_mat(&mut bits, bottom);
+        }
+        for (_, bottom, _) in auto_params_long.iter() {
+            Self::pack_ring_q1_mat(&mut bits, bottom);
+        }
+        Self::pack_ring_q1_mat(&mut bits, bottom_rlwe_to_gsw);
+        for (_, bottom) in vectorize_params.iter() {
+            Self::pack_ring_q1_mat(&mut bits, bottom);
+        }
+        for i in 0..N_VEC {
+            for j in 0..T_COMPRESS {
+                pack_coeffs(
+                    &mut bits,
+                    IntModCyclo::from(&bottom_compress[(i, j)])
+                        .coeff
+                        .iter()
+                        .copied(),
+                );
+            }
+        }
+        result.extend_from_slice(bits.as_raw_slice());
+        debug_assert_eq!(result.len(), Self::params_public_param_size());
+        result
+    }
+
+    fn public_params_from_bytes(
+        bytes: &[u8],
+    ) -> Result<<Self as Respire>::PublicParamsSeeded, WireError> {
+        if bytes.len() != Self::params_public_param_size() {
+            return Err(WireError::InvalidLength(bytes.len()));
+        }
+        let log_d = floor_log(2, D1 as u64);
+        let mut seeds = bytes
+            .chunks_exact(32)
+            .take(Self::PUBLIC_PARAM_SEED_COUNT)
+            .map(|seed| <[u8; 32]>::try_from(seed).unwrap());
+        let mut cursor = BitCursor::new(&bytes[32 * Self::PUBLIC_PARAM_SEED_COUNT..]);
+
+        let seeds_short = seeds.by_ref().take(log_d).collect_vec();
+        let seeds_long = seeds.by_ref().take(log_d).collect_vec();
+        let seed_rlwe_to_gsw = seeds.next().unwrap();
+        let seed_compress = seeds.next().unwrap();
+        let seeds_vectorize = seeds.collect_vec();
+
+        let mut auto_params_short = Vec::with_capacity(log_d);
+        for (i, seed) in seeds_short.into_iter().enumerate() {
+            let bottom = Self::unpack_ring_q1_mat(&mut cursor)?;
+            auto_params_short.push((seed, bottom, (D1 >> i) + 1));
+        }
+        let mut auto_params_long = Vec::with_capacity(log_d);
+        for (i, seed) in seeds_long.into_iter().enumerate() {
+            let bottom = Self::unpack_ring_q1_mat(&mut cursor)?;
+            auto_params_long.push((seed, bottom, (D1 >> i) + 1));
+        }
+        let bottom_rlwe_to_gsw = Self::unpack_ring_q1_mat(&mut cursor)?;
+        let mut vectorize_params = Vec::with_capacity(N_VEC);
+        for seed in seeds_vectorize {
+            vectorize_params.push((seed, Self::unpack_ring_q1_mat(&mut cursor)?));
+        }
+        let mut bottom_compress = Matrix::zero();
+        for i in 0..N_VEC {
+            for j in 0..T_COMPRESS {
+                let mut elem = IntModCyclo::<D1, Q2>::zero();
+                cursor.read_coeffs(&mut elem.coeff)?;
+                bottom_compress[(i, j)] = IntModCycloEval::from(elem);
+            }
+        }
+
+        Ok((
+            (auto_params_short, auto_params_long),
+            (seed_rlwe_to_gsw, bottom_rlwe_to_gsw),
+            (seed_compress, bottom_compress),
+            vectorize_params,
+        ))
+    }
+
+    fn query_key_to_bytes(qk: &<Self as PIR>::QueryKey) -> Zeroizing<Vec<u8>> {
+        let (bound_vec, bound_small) = Self::secret_gaussian_bounds();
+        let mut bits = BitVec::<u8, Msb0>::with_capacity(8 * Self::params_query_key_size());
+        let mut coeffs = <Self as Respire>::RingQ1::from(&qk.s_encode);
+        pack_small_coeffs(&mut bits, coeffs.coeff.iter().copied(), SECRET_BOUND);
+        for i in 0..N_VEC {
+            coeffs = <Self as Respire>::RingQ1::from(&qk.s_vec[(i, 0)]);
+            pack_small_coeffs(&mut bits, coeffs.coeff.iter().copied(), bound_vec);
+        }
+        coeffs.zeroize();
+        for i in 0..N_VEC {
+            let mut coeffs_small = IntModCyclo::<D2, Q2>::from(&qk.s_small[(i, 0)]);
+            pack_small_coeffs(&mut bits, coeffs_small.coeff.iter().copied(), bound_small);
+            coeffs_small.zeroize();
+        }
+        let result = Zeroizing::new(bits.into_vec());
+        debug_assert_eq!(result.len(), Self::params_query_key_size());
+        result
+    }
+
+    fn query_key_from_bytes(bytes: &[u8]) -> Result<<Self as PIR>::QueryKey, WireError> {
+        if bytes.len() != Self::params_query_key_size() {
+            return Err(WireError::InvalidLength(bytes.len()));
+        }
+        let (bound_vec, bound_small) = Self::secret_gaussian_bounds();
+        let mut cursor = BitCursor::new(bytes);
+        let mut qk = RespireQueryKey {
+            s_encode: <Self as Respire>::RingQ1Fast::zero(),
+            s_vec: Matrix::zero(),
+            s_small: Matrix::zero(),
+        };
+        let mut coeffs = <Self as Respire>::RingQ1::zero();
+        let mut coeffs_small = IntModCyclo::<D2, Q2>::zero();
+        let mut read = || {
+            cursor.read_small_coeffs(&mut coeffs.coeff, SECRET_BOUND)?;
+            qk.s_encode = <Self as Respire>::RingQ1Fast::from(&coeffs);
+            for i in 0..N_VEC {
+                cursor.read_small_coeffs(&mut coeffs.coeff, bound_vec)?;
+                qk.s_vec[(i, 0)] = <Self as Respire>::RingQ1Fast::from(&coeffs);
+            }
+            for i in 0..N_VEC {
+                cursor.read_small_coeffs(&mut coeffs_small.coeff, bound_small)?;
+                qk.s_small[(i, 0)] = IntModCycloEval::from(&coeffs_small);
+            }
+            Ok(())
+        };
+        let result = read();
+        coeffs.zeroize();
+        coeffs_small.zeroize();
+        result.map(|()| qk)
     }
 
     fn params() -> RespireParamsExpanded {
@@ -1154,236 +1954,248 @@ respire_impl!(Respire, {
     }
 
     fn params_error_rate_estimate() -> f64 {
-        info!("*** Error estimates (bits) ***");
-        // We use square subgaussian widths as units
-        let error_width_sq: f64 = ((ERROR_WIDTH_MILLIONTHS as f64) / 1_000_000_f64).powi(2);
-        let error_width_vec_sq: f64 = ((ERROR_WIDTH_VEC_MILLIONTHS as f64) / 1_000_000_f64).powi(2);
-        let error_width_switch_sq: f64 =
-            ((ERROR_WIDTH_COMPRESS_MILLIONTHS as f64) / 1_000_000_f64).powi(2);
-        let secret_bound_sq: f64 = (SECRET_BOUND as f64).powi(2);
-        let secret_width_vec_sq: f64 =
-            ((SECRET_WIDTH_VEC_MILLIONTHS as f64) / 1_000_000_f64).powi(2);
-        // let secret_width_switch_sq: f64 =
-        //     ((SECRET_WIDTH_COMPRESS_MILLIONTHS as f64) / 1_000_000_f64).powi(2);
+        let report = Self::params().noise_report();
+        info!("*** Error estimates (bits) ***\n{}", report);
+        report.error_rate
+    }
 
-        let log_d: usize = ceil_log(2, D1 as u64);
+    fn params_public_param_size() -> usize {
+        Self::params().public_param_size_impl(true)
+    }
 
-        let e_to_bits = |e: f64| -> f64 { e.log2() / 2_f64 };
+    fn params_query_one_size() -> usize {
+        Self::params().query_one_size()
+    }
 
-        let gadget_factor = |t: usize, z: u64| -> f64 {
-            assert!(z >= 2);
+    fn params_record_one_size() -> usize {
+        Self::params().record_one_size()
+    }
 
-            let z_factor = match z {
-                2 => {
-                    // With probability <= 2^(-48.421), a random binary gadget will have <= 1200 equal 1
-                    // N(log(1 - sum(binomial(2048, x) * (1/2)^2048, x, 0, 1200), 2)) = -48.4216972197488
-                    const BINARY_FACTOR: f64 = 1200_f64 / 2048_f64;
-                    BINARY_FACTOR
-                }
-                // 3 => {
-                //     // With probability <= 2^(-48.506), a random ternary gadget will have <= 1528 coefficients equal +- 1
-                //     // N(log(1 - sum(binomial(2048, x) * (2/3)^x * (1/3)^(2048-x), x, 0, 1528), 2)) = -48.5067228501629
-                //     const TERNARY_FACTOR: f64 = 1528_f64 / 2048_f64;
-                //     TERNARY_FACTOR
-                // }
-                _ => {
-                    // TODO noise: verify this factor is right
-                    // const CHERNOFF_FACTOR: f64 = 0.6_f64;
-                    const CHERNOFF_FACTOR: f64 = 1.0_f64;
-                    ((z / 2) as f64).powi(2) * CHERNOFF_FACTOR
-                }
-            };
+    fn params_response_one_size(trunc_len: usize) -> usize {
+        Self::params().response_one_size(trunc_len)
+    }
 
-            (t as f64) * z_factor
-        };
+    fn params_query_key_size() -> usize {
+        Self::params().query_key_size()
+    }
+});
 
-        let select_noise = |e_gsw_sq: f64, e_reg_sq: f64, depth: usize| -> f64 {
-            // m = 2t; t is absorbed into gadget_factor()
-            e_reg_sq + (depth as f64) * 2_f64 * (D1 as f64) * gadget_factor(T_GSW, Z_GSW) * e_gsw_sq
-        };
+respire_impl!({
+    fn check_batch_size(num_queries: usize) -> Result<(), PirError> {
+        if num_queries == 0 || num_queries > Self::BATCH_SIZE {
+            return Err(PirError::BatchSizeMismatch {
+                expected: Self::BATCH_SIZE,
+                found: num_queries,
+            });
+        }
+        Ok(())
+    }
 
-        let proj_noise = |e_sq: f64, t_auto: usize, z_auto: u64, depth: usize| -> f64 {
-            let ct = (4usize.pow(depth as u32) - 1) / 3;
-            e_sq + ct as f64 * (D1 as f64) * gadget_factor(t_auto, z_auto) * error_width_sq
-        };
+    ///
+    /// The messages encrypted by the RLWE and GSW parts of a query for record `idx`, before query
+    /// expansion.
+    ///
+    pub fn query_messages(idx: usize) -> (<Self as Respire>::RingQ1, <Self as Respire>::RingQ1) {
+        let last_dims_size = 2usize.pow((Self::NU2 + Self::NU3) as u32);
+        let (idx_i, idx_j) = (idx / last_dims_size, idx % last_dims_size);
 
-        info!("Initial: {}", e_to_bits(error_width_sq));
+        let mut mu_rlwe = <Self as Respire>::RingQ1::zero();
+        for i in 0..Self::RLWE_COUNT {
+            mu_rlwe.coeff[reverse_bits_fast::<D1>(i)] =
+                IntMod::<P>::from((i == idx_i) as u64).scale_up_into();
+        }
 
-        // Query expansion
-        let e_reg = proj_noise(error_width_sq, T_PROJ_SHORT, Z_PROJ_SHORT, log_d);
-        info!("Query expand rlwe: {}", e_to_bits(e_reg));
-        let e_gsw_raw = proj_noise(error_width_sq, T_PROJ_LONG, Z_PROJ_LONG, log_d);
-        info!("Query expand GSW (raw): {}", e_to_bits(e_gsw_raw));
-        let e_gsw = {
-            // RLWE to GSW
-            let initial_component = (D1 as f64) * e_gsw_raw * secret_bound_sq;
-            let gadget_component =
-                // m = 2t; t is absorbed into gadget_factor()
-                2_f64 * (D1 as f64) * gadget_factor(T_RLWE_TO_GSW, Z_RLWE_TO_GSW) * error_width_sq;
-            let e_converted = initial_component + gadget_component;
-            info!(
-                "    RLWE to GSW initial component: {}",
-                e_to_bits(initial_component),
-            );
-            info!(
-                "    RLWE to GSW gadget component: {}",
-                e_to_bits(gadget_component),
-            );
-            e_converted
-        };
-        info!("Query expand GSW (converted): {}", e_to_bits(e_gsw));
+        // [NU2 + NU3] x [T_GSW]
+        let mut mu_gsw = <Self as Respire>::RingQ1::zero();
 
-        // First dimension (NU1)
-        let e_firstdim =
-            (Self::PACKED_DIM1_SIZE as f64) * (D1 as f64) * ((P / 2) as f64).powi(2) * e_reg;
-        info!("First dimension: {}", e_to_bits(e_firstdim));
+        let mut bits = Vec::with_capacity(NU2);
+        let mut idx_j_curr = idx_j;
+        for _ in 0..(Self::NU2 + Self::NU3) {
+            bits.push(idx_j_curr % 2);
+            idx_j_curr /= 2;
+        }
 
-        // Folding (NU2)
-        let e_fold = select_noise(e_gsw, e_firstdim, Self::NU2);
-        info!("Fold: {}", e_to_bits(e_fold));
+        for (bit_idx, bit) in bits.into_iter().rev().enumerate() {
+            let mut msg = IntMod::from(bit as u64);
+            for gsw_pow in 0..T_GSW {
+                let pack_idx = T_GSW * bit_idx + gsw_pow;
+                mu_gsw.coeff[reverse_bits_fast::<D1>(pack_idx)] = msg;
+                msg *= IntMod::from(Z_GSW);
+            }
+        }
 
-        // Rotating (NU3)
-        let e_rot = select_noise(e_gsw, e_fold, Self::NU3);
-        info!("Rotate select: {}", e_to_bits(e_rot));
+        (mu_rlwe, mu_gsw)
+    }
 
-        // Proj/select (NU3) + ring packing
-        let e_proj_component = proj_noise(0_f64, T_PROJ_LONG, Z_PROJ_LONG, Self::NU3);
-        info!(
-            "    Projection *new* error component: {}",
-            e_to_bits(e_proj_component)
-        );
-        let ring_num_records = min(Self::BATCH_SIZE, Self::PACK_RATIO_RESPONSE);
-        let e_pack_ring = e_rot + e_proj_component * ring_num_records as f64;
-        info!(
-            "Ring packing ({} record(s)): {}",
-            ring_num_records,
-            e_to_bits(e_pack_ring)
-        );
+    ///
+    /// Like [`Respire::answer_one`], but if `probe` is given, recording there the noise of every
+    /// intermediate ciphertext as that of the query at the given position of the batch.
+    ///
+    pub fn answer_one_probed(
+        pp: &<Self as PIR>::PublicParams,
+        db: &<Self as PIR>::Database,
+        q: &<Self as Respire>::QueryOne,
+        mut probe: Option<(&mut NoiseProbe<<Self as PIR>::QueryKey>, usize)>,
+        mut time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<<Self as Respire>::AnswerOne, PirError> {
+        let ((_, vec_reg), (_, vec_gsw)) = q;
+        if vec_reg.len() != Self::RLWE_COUNT || vec_gsw.len() != Self::GSW_COUNT {
+            return Err(PirError::MalformedQuery);
+        }
 
-        // Vector packing
-        let e_pack_vec = {
-            // Scalar to vector conversion
-            let e_init = e_pack_ring;
-            let vec_num_elems = min(
-                Self::BATCH_SIZE.div_ceil(Self::PACK_RATIO_RESPONSE),
-                Self::N_VEC,
-            );
-            let e_extra = vec_num_elems as f64
-                * (D1 as f64)
-                * gadget_factor(T_VECTORIZE, Z_VECTORIZE)
-                * error_width_vec_sq;
-            info!(
-                "    Vector packing *new* error component ({} ring elem(s)): {}",
-                vec_num_elems,
-                e_to_bits(e_extra),
-            );
-            let e_full = e_init + e_extra;
-            info!("Vector packing: {}", e_to_bits(e_full));
-            e_full
+        // Query expansion
+        let (rlwes, gsws_raw) = Self::answer_query_expand(pp, q, time_stats.as_deref_mut());
+        let (gsws_fold, gsws_rot) =
+            Self::answer_query_to_gsw(pp, gsws_raw.as_slice(), time_stats.as_deref_mut());
+        if let Some((probe, query)) = probe.as_mut() {
+            Self::probe_query_gsws(probe, *query, &gsws_raw, &gsws_fold, &gsws_rot);
+        }
+        let mut note = |phase: NoisePhase, i: usize, c: &<Self as Respire>::RLWEEncoding| {
+            if let Some((probe, query)) = probe.as_mut() {
+                let RespireQueryKey { s_encode, .. } = probe.query_key();
+                let bits = Self::noise_subgaussian_bits(s_encode, c);
+                probe.record(phase, *query, i, bits);
+            }
         };
+        for (i, c) in rlwes.iter().enumerate() {
+            note(NoisePhase::QueryExpansion, i, c);
+        }
 
-        let e_preswitch = e_pack_vec;
-        info!("***");
-        info!(
-            "Preswitch noise: {:.3} total bits; approx {:.3} of margin",
-            e_to_bits(e_preswitch),
-            (Q1 as f64).log2() - (P as f64).log2() - e_to_bits(e_preswitch) - 3_f64 // 3 bits = 8 widths
-        );
-        assert_eq!(Z_COMPRESS, 2);
-        let e_preswitch = 8_f64 * e_preswitch;
-
-        let e_subg_preswitch = e_preswitch * (Q3 as f64).powi(2) / (Q1 as f64).powi(2);
-        let e_subg_gadget = (Q3 as f64).powi(2) / (4_f64 * (Q2 as f64).powi(2))
-            * ((D1 as f64) * secret_width_vec_sq
-                + 4_f64
-                    * (D1 as f64)
-                    * gadget_factor(T_COMPRESS, Z_COMPRESS)
-                    * error_width_switch_sq);
-        let e_subg = e_subg_preswitch + e_subg_gadget;
-        let e_round = (2f64 + Q3 as f64 / Q1 as f64 * (Q1 % P) as f64) / 2f64;
-        let threshold = Q3 / (2 * P);
+        let i1 = Instant::now();
 
-        info!(
-            "Switch rounding term noise bound (absolute / threshold): {} / {}",
-            e_round, threshold
-        );
+        // First dimension
+        let c_firstdim = Self::answer_first_dim_with_stats(db, &rlwes, time_stats.as_deref_mut());
+        for (i, c) in c_firstdim.iter().enumerate() {
+            note(NoisePhase::FirstDim, i, c);
+        }
+        let i2 = Instant::now();
 
-        info!("Switch subgaussian term noise widths (absolute / threshold):");
+        // Folding
+        let mut fold_count = 0;
+        let c_fold = Self::answer_fold_with(c_firstdim, gsws_fold.as_slice(), |c| {
+            note(NoisePhase::Fold, fold_count, c);
+            fold_count += 1;
+        });
+        let i3 = Instant::now();
 
-        info!(
-            "    preswitch: {:.3} / {}",
-            e_subg_preswitch.sqrt(),
-            threshold
-        );
-        info!("    gadget: {:.3} / {}", e_subg_gadget.sqrt(), threshold);
-        info!("    total: {:.3} / {}", e_subg.sqrt(), threshold);
+        // Rotate select
+        let mut rot_count = 0;
+        let c_rot = Self::answer_rotate_with(&c_fold, gsws_rot.as_slice(), |c| {
+            note(NoisePhase::Rotate, rot_count, c);
+            rot_count += 1;
+        });
+        let i4 = Instant::now();
 
-        use std::f64::consts::PI;
-        let error_rate = 2_f64
-            * (D3 as f64)
-            * f64::exp(-PI * (0.5_f64 * (Q3 / P) as f64 - e_round).powi(2) / e_subg);
+        // Project
+        let c_proj = Self::answer_project(pp, &c_rot);
+        // The intermediate projections scale the message by powers of two, so only the final
+        // one is measured. Its noise is lower on the coefficients that are projected away, so
+        // this average is a bit inaccurate.
+        note(NoisePhase::Project, 0, &c_proj);
+        let i5 = Instant::now();
 
-        info!("Error rate: 2^({})", error_rate.log2());
-        info!("***");
+        if let Some(time_stats) = time_stats {
+            time_stats.add("answer_first_dim", i2 - i1);
+            time_stats.add("answer_fold", i3 - i2);
+            time_stats.add("answer_rotate", i4 - i3);
+            time_stats.add("answer_project", i5 - i4);
+        }
 
-        clamp(error_rate, 0_f64, 1_f64)
+        Ok(c_proj)
     }
 
-    fn params_public_param_size() -> usize {
-        let automorph_elems = floor_log(2, D1 as u64) * (T_PROJ_SHORT + T_PROJ_LONG);
-        let reg_to_gsw_elems = 2 * T_RLWE_TO_GSW;
-        let scal_to_vec_elems = N_VEC * T_VECTORIZE;
-        let q_elem_size = D1 * ceil_log(2, Q1) / 8;
-
-        let compress_elems = N_VEC * T_COMPRESS;
-        let q2_elem_size = D1 * ceil_log(2, Q2) / 8;
-
-        // This code assumes we implement use PRG trick to compress the randomness components of the
-        // public params. Technically though, this hasn't been implemented.
-
-        info!(
-            "automorph pp: {:.3} KiB",
-            (automorph_elems * q_elem_size) as f64 / 1024_f64
-        );
-        info!(
-            "rlwe to GSW pp: {:.3} KiB",
-            (reg_to_gsw_elems * q_elem_size) as f64 / 1024_f64
-        );
-        info!(
-            "scal to vec pp: {:.3} KiB",
-            (scal_to_vec_elems * q_elem_size) as f64 / 1024_f64
-        );
-        info!(
-            "compress pp: {:.3} KiB",
-            (compress_elems * q2_elem_size) as f64 / 1024_f64
-        );
-        return (automorph_elems + reg_to_gsw_elems + scal_to_vec_elems) * q_elem_size
-            + compress_elems * q2_elem_size;
+    ///
+    /// Records the noise of the expanded GSW parts of the query at position `query`, before
+    /// (`raw`) and after conversion to GSW. Their messages are not multiples of the scaling
+    /// factor, so they are measured against the exact messages of the probed index.
+    ///
+    fn probe_query_gsws(
+        probe: &mut NoiseProbe<<Self as PIR>::QueryKey>,
+        query: usize,
+        raw: &[<Self as Respire>::RLWEEncoding],
+        gsws_fold: &[<Self as Respire>::GSWEncoding],
+        gsws_rot: &[<Self as Respire>::GSWEncoding],
+    ) {
+        let RespireQueryKey { s_encode, .. } = probe.query_key();
+        let (_, mu_gsw) = Self::query_messages(probe.index(query));
+        let constant = |pack_idx: usize| {
+            let mut mu = <Self as Respire>::RingQ1::zero();
+            mu.coeff[0] = mu_gsw.coeff[reverse_bits_fast::<D1>(pack_idx)];
+            mu
+        };
+        for (i, c) in raw.iter().enumerate() {
+            let noise = &Self::decode_rlwe(s_encode, c) - &constant(i);
+            let bits = Self::variance_to_subgaussian_bits(Self::coeff_variance(&[noise]));
+            probe.record(NoisePhase::QueryExpansionGsw, query, i, bits);
+        }
+        for (i, c) in gsws_fold.iter().chain(gsws_rot.iter()).enumerate() {
+            let bits = Self::noise_subgaussian_bits_gsw(s_encode, c, &constant(T_GSW * i));
+            probe.record(NoisePhase::RlweToGsw, query, i, bits);
+        }
     }
 
-    fn params_query_one_size() -> usize {
-        (Self::RLWE_COUNT + Self::GSW_COUNT) * ceil_log(2, Q1) / 8
-    }
+    ///
+    /// Like [`Respire::answer_compress_chunk`], but if `probe` is given, recording there the noise
+    /// of the packed and compressed ciphertexts as that of the chunk at the given position.
+    ///
+    pub fn answer_compress_chunk_probed(
+        pp: &<Self as PIR>::PublicParams,
+        chunk: &[<Self as Respire>::AnswerOne],
+        probe: Option<(&mut NoiseProbe<<Self as PIR>::QueryKey>, usize)>,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> <Self as Respire>::AnswerOneCompressed {
+        let begin = Instant::now();
+        let mut scalar_cts = Vec::with_capacity(Self::RESPONSE_CHUNK_SIZE);
+        let (_, _, _, vectorize_params) = pp;
+        for vec_idx in 0..N_VEC {
+            let mut scalar_ct = Matrix::zero();
+            for pack_idx in 0..Self::PACK_RATIO_RESPONSE {
+                let idx = vec_idx * Self::PACK_RATIO_RESPONSE + pack_idx;
+                if idx < chunk.len() {
+                    scalar_ct += &Self::rlwe_mul_x_pow(&chunk[idx], pack_idx * (D1 / D2));
+                }
+            }
+            scalar_cts.push(scalar_ct)
+        }
+        let vec = Self::scal_to_vec(vectorize_params, scalar_cts.as_slice().try_into().unwrap());
+        let compressed =
+            Self::answer_compress_vec(pp, &vec, chunk.len().div_ceil(Self::PACK_RATIO_RESPONSE));
+
+        let end = Instant::now();
+        if let Some(time_stats) = time_stats {
+            time_stats.add("answer_compress", end - begin);
+        }
 
-    fn params_record_one_size() -> usize {
-        let log_p = floor_log(2, P);
-        D3 * log_p / 8
+        if let Some((probe, chunk_idx)) = probe {
+            let qk = probe.query_key();
+            let num_packed = chunk.len().div_ceil(Self::PACK_RATIO_RESPONSE);
+            for (i, c) in scalar_cts.iter().take(num_packed).enumerate() {
+                let bits = Self::noise_subgaussian_bits(&qk.s_encode, c);
+                probe.record(NoisePhase::RingPacking, chunk_idx, i, bits);
+            }
+            let bits = Self::noise_subgaussian_bits_vec(&qk.s_vec, &vec);
+            probe.record(NoisePhase::Vectorize, chunk_idx, 0, bits);
+            let bits = Self::noise_subgaussian_bits_compressed(qk, &compressed);
+            probe.record(NoisePhase::Compress, chunk_idx, 0, bits);
+        }
+        compressed
     }
 
-    fn params_response_one_size(trunc_len: usize) -> usize {
-        // Technically we can do ceil(d * (log(q2) + len * log(q1)) by packing into a single large integer.
-        // But for simplicity assume each IntMod<Q1> / IntMod<Q2> is serialized individually.
-        let log_q1 = ceil_log(2, Q3);
-        let log_q2 = ceil_log(2, Q2);
-        ((D2 as f64) * (log_q2 as f64 + (trunc_len as f64) * log_q1 as f64) / 8_f64).ceil() as usize
+    pub fn extract_ring_one(
+        qk: &<Self as PIR>::QueryKey,
+        r: &<Self as Respire>::AnswerOneCompressed,
+    ) -> <Self as Respire>::RecordPackedSmall {
+        Self::decode_compressed(qk, r).map_ring(|r| r.round_down_into())
     }
-});
 
-respire_impl!({
-    pub fn extract_ring_one(
-        (_, _, s_small): &<Self as PIR>::QueryKey,
+    ///
+    /// Decrypts a compressed answer, without rounding away the noise. Only the first
+    /// `c_m_hat_trunc.len()` entries are meaningful.
+    ///
+    pub fn decode_compressed(
+        RespireQueryKey { s_small, .. }: &<Self as PIR>::QueryKey,
         (c_r_hat, c_m_hat_trunc): &<Self as Respire>::AnswerOneCompressed,
-    ) -> <Self as Respire>::RecordPackedSmall {
+    ) -> Matrix<N_VEC, 1, IntModCyclo<D2, Q3>> {
         let neg_s_small_cr =
             (-&(s_small * &IntModCycloEval::from(c_r_hat))).map_ring(|r| IntModCyclo::from(r));
         let mut result = Matrix::<N_VEC, 1, IntModCyclo<D2, Q3>>::zero();
@@ -1400,7 +2212,7 @@ respire_impl!({
             }
             result[(i, 0)] += &c_m_hat_trunc[i];
         }
-        result.map_ring(|r| r.round_down_into())
+        result
     }
 
     pub fn extract_bytes_one(
@@ -1427,11 +2239,28 @@ respire_impl!({
     }
 
     pub fn answer_query_unpack(
-        ((auto_params_short, auto_params_long), rlwe_to_gsw_params, _, _): &<Self as PIR>::PublicParams,
+        pp: &<Self as PIR>::PublicParams,
+        q: &<Self as Respire>::QueryOne,
+        mut time_stats: Option<&mut Stats<Duration>>,
+    ) -> <Self as Respire>::QueryOneExpanded {
+        let (c_rlwes, c_gsws) = Self::answer_query_expand(pp, q, time_stats.as_deref_mut());
+        let (c_gsws_fold, c_gsws_rot) =
+            Self::answer_query_to_gsw(pp, c_gsws.as_slice(), time_stats);
+        (c_rlwes, c_gsws_fold, c_gsws_rot)
+    }
+
+    ///
+    /// Expands a query into its RLWE ciphertexts and the RLWE ciphertexts that
+    /// [`Self::answer_query_to_gsw`] converts to GSW.
+    ///
+    pub fn answer_query_expand(
+        ((auto_params_short, auto_params_long), _, _, _): &<Self as PIR>::PublicParams,
         ((seed_reg, vec_reg), (seed_gsw, vec_gsw)): &<Self as Respire>::QueryOne,
-        _: Option<&<Self as PIR>::QueryKey>,
         time_stats: Option<&mut Stats<Duration>>,
-    ) -> <Self as Respire>::QueryOneExpanded {
+    ) -> (
+        Vec<<Self as Respire>::RLWEEncoding>,
+        Vec<<Self as Respire>::RLWEEncoding>,
+    ) {
         let inv = <Self as Respire>::RingQ1Fast::from(mod_inverse(D1 as u64, Q1));
         let mut c_rlwes = {
             let mut c1_reg = IntModCyclo::zero();
@@ -1489,6 +2318,24 @@ respire_impl!({
         assert_eq!(c_gsws.len(), Self::GSW_COUNT);
 
         let i2 = Instant::now();
+
+        if let Some(time_stats) = time_stats {
+            time_stats.add("answer_query_expand_reg", i1 - i0);
+            time_stats.add("answer_query_expand_gsw", i2 - i1);
+        }
+
+        (c_rlwes, c_gsws)
+    }
+
+    pub fn answer_query_to_gsw(
+        (_, rlwe_to_gsw_params, _, _): &<Self as PIR>::PublicParams,
+        c_gsws: &[<Self as Respire>::RLWEEncoding],
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> (
+        Vec<<Self as Respire>::GSWEncoding>,
+        Vec<<Self as Respire>::GSWEncoding>,
+    ) {
+        let begin = Instant::now();
         let mut c_gsws_iter = c_gsws
             .chunks_exact(T_GSW)
             .map(|cs| Self::rlwe_to_gsw(rlwe_to_gsw_params, cs));
@@ -1501,25 +2348,105 @@ respire_impl!({
             .collect();
         assert_eq!(c_gsws_iter.next(), None);
 
-        let i3 = Instant::now();
-
+        let end = Instant::now();
         if let Some(time_stats) = time_stats {
-            time_stats.add("answer_query_expand_reg", i1 - i0);
-            time_stats.add("answer_query_expand_gsw", i2 - i1);
-            time_stats.add("answer_query_expand_reg_to_gsw", i3 - i2);
+            time_stats.add("answer_query_expand_reg_to_gsw", end - begin);
         }
-
-        // TODO measure and report noise through this phase? Difficult because need to know the exact encoding (since they are not rounded to q/p)
-        (c_rlwes, c_gsws_fold, c_gsws_rot)
+        (c_gsws_fold, c_gsws_rot)
     }
 
     pub fn answer_first_dim(
         db: &<Self as PIR>::Database,
         rlwes: &[<Self as Respire>::RLWEEncoding],
+    ) -> Vec<<Self as Respire>::RLWEEncoding> {
+        Self::answer_first_dim_with_stats(db, rlwes, None)
+    }
+
+    ///
+    /// Like [`Self::answer_first_dim`]. With the `parallel` feature, the evaluation slices are split
+    /// into chunks that are computed in parallel, and the time spent on each chunk is recorded in
+    /// `time_stats` under the worker thread that computed it.
+    ///
+    pub fn answer_first_dim_with_stats(
+        db: &<Self as PIR>::Database,
+        rlwes: &[<Self as Respire>::RLWEEncoding],
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Vec<<Self as Respire>::RLWEEncoding> {
+        #[cfg(not(feature = "parallel"))]
+        {
+            let _ = time_stats;
+            Self::answer_first_dim_sequential(db, rlwes)
+        }
+
+        // Each worker computes the sums of its own chunk of evaluation slices, for all outputs.
+        // The slices are disjoint, so the sums are then stored into the outputs in any order.
+        #[cfg(feature = "parallel")]
+        {
+            assert_eq!(rlwes.len(), Self::PACKED_DIM1_SIZE);
+            let (c0s, c1s) = Self::first_dim_transpose(rlwes);
+            let (c0s, c1s) = (c0s.as_slice(), c1s.as_slice());
+            let num_slices = D1 / SIMD_LANES;
+            let chunk_size = num_slices.div_ceil(FIRST_DIM_CHUNKS);
+            let chunks = parallel::map_with_stats(
+                num_slices.div_ceil(chunk_size),
+                time_stats,
+                |chunk_idx, stats| {
+                    let begin = Instant::now();
+                    let slices =
+                        chunk_idx * chunk_size..min((chunk_idx + 1) * chunk_size, num_slices);
+                    let sums = slices
+                        .flat_map(|eval_vec_idx| {
+                            (0..Self::PACKED_DIM2_SIZE)
+                                .map(move |j| Self::first_dim_sums(db, c0s, c1s, eval_vec_idx, j))
+                        })
+                        .collect_vec();
+                    if let Some(stats) = stats {
+                        stats.add("answer_first_dim_chunk", Instant::now() - begin);
+                    }
+                    sums
+                },
+            );
+
+            let mut result: Vec<<Self as Respire>::RLWEEncoding> = (0..Self::PACKED_DIM2_SIZE)
+                .map(|_| <Self as Respire>::RLWEEncoding::zero())
+                .collect();
+            for (idx, sums) in chunks.into_iter().flatten().enumerate() {
+                let (eval_vec_idx, j) =
+                    (idx / Self::PACKED_DIM2_SIZE, idx % Self::PACKED_DIM2_SIZE);
+                Self::first_dim_store(&mut result[j], eval_vec_idx, sums);
+            }
+            result
+        }
+    }
+
+    /// Like [`Self::answer_first_dim`], on the current thread only, storing each sum into the
+    /// outputs as it is computed.
+    pub fn answer_first_dim_sequential(
+        db: &<Self as PIR>::Database,
+        rlwes: &[<Self as Respire>::RLWEEncoding],
     ) -> Vec<<Self as Respire>::RLWEEncoding> {
         assert_eq!(rlwes.len(), Self::PACKED_DIM1_SIZE);
+        let (c0s, c1s) = Self::first_dim_transpose(rlwes);
+        let mut result: Vec<<Self as Respire>::RLWEEncoding> = (0..Self::PACKED_DIM2_SIZE)
+            .map(|_| <Self as Respire>::RLWEEncoding::zero())
+            .collect();
+        for eval_vec_idx in 0..(D1 / SIMD_LANES) {
+            for (j, ct) in result.iter_mut().enumerate() {
+                Self::first_dim_store(
+                    ct,
+                    eval_vec_idx,
+                    Self::first_dim_sums(db, &c0s, &c1s, eval_vec_idx, j),
+                );
+            }
+        }
+        result
+    }
 
-        // Flatten + transpose the ciphertexts
+    /// Flattens and transposes the first dimension query ciphertexts, into their (c0, c1) parts
+    /// per slice of `SIMD_LANES` evaluation points.
+    fn first_dim_transpose(
+        rlwes: &[<Self as Respire>::RLWEEncoding],
+    ) -> (Vec<SimdVec>, Vec<SimdVec>) {
         let mut c0s: Vec<SimdVec> = Vec::with_capacity((D1 / SIMD_LANES) * Self::PACKED_DIM1_SIZE);
         let mut c1s: Vec<SimdVec> = Vec::with_capacity((D1 / SIMD_LANES) * Self::PACKED_DIM1_SIZE);
 
@@ -1557,172 +2484,171 @@ respire_impl!({
             }
         }
 
-        // First dimension processing
-        let mut result: Vec<<Self as Respire>::RLWEEncoding> = (0..Self::PACKED_DIM2_SIZE)
-            .map(|_| <Self as Respire>::RLWEEncoding::zero())
-            .collect();
+        (c0s, c1s)
+    }
+
+    // We want to compute the sum over i of ct_i * db_(i, j).
+    // Here db_(i, j) are scalars; ct_i are 2 x 1 matrices.
+    // Each output j and slice of SIMD_LANES evaluation points is independent. `first_dim_sums`
+    // computes one of them, as the reduced sums in the order
+    // (c0 proj1, c0 proj2, c1 proj1, c1 proj2).
 
+    #[cfg(not(target_feature = "avx2"))]
+    fn first_dim_sums(
+        db: &<Self as PIR>::Database,
+        c0s: &[SimdVec],
+        c1s: &[SimdVec],
+        eval_idx: usize,
+        j: usize,
+    ) -> [SimdVec; 4] {
         // Norm is at most max(Q1A, Q1B)^2 for each term
         // Add one for margin
         let reduce_every = 1 << (64 - 2 * ceil_log(2, max(Q1A, Q1B)) - 1);
 
-        // We want to compute the sum over i of ct_i * db_(i, j).
-        // Here db_(i, j) are scalars; ct_i are 2 x 1 matrices.
-
-        #[cfg(not(target_feature = "avx2"))]
-        for eval_idx in 0..D1 {
-            for j in 0..Self::PACKED_DIM2_SIZE {
-                let mut sum0_proj1 = 0_u64;
-                let mut sum0_proj2 = 0_u64;
-                let mut sum1_proj1 = 0_u64;
-                let mut sum1_proj2 = 0_u64;
-
-                for i in 0..Self::PACKED_DIM1_SIZE {
-                    let lhs0 = c0s[eval_idx * Self::PACKED_DIM1_SIZE + i];
-                    let lhs0_proj1 = lhs0 as u32 as u64;
-                    let lhs0_proj2 = lhs0 >> 32;
-
-                    let lhs1 = c1s[eval_idx * Self::PACKED_DIM1_SIZE + i];
-                    let lhs1_proj1 = lhs1 as u32 as u64;
-                    let lhs1_proj2 = lhs1 >> 32;
-
-                    let rhs = db[eval_idx * Self::PACKED_DB_SIZE + j * Self::PACKED_DIM1_SIZE + i];
-                    let rhs_proj1 = rhs as u32 as u64;
-                    let rhs_proj2 = rhs >> 32;
-
-                    sum0_proj1 += lhs0_proj1 * rhs_proj1;
-                    sum0_proj2 += lhs0_proj2 * rhs_proj2;
-                    sum1_proj1 += lhs1_proj1 * rhs_proj1;
-                    sum1_proj2 += lhs1_proj2 * rhs_proj2;
-
-                    if i % reduce_every == 0 || i == Self::PACKED_DIM1_SIZE - 1 {
-                        sum0_proj1 %= Q1A;
-                        sum0_proj2 %= Q1B;
-                        sum1_proj1 %= Q1A;
-                        sum1_proj2 %= Q1B;
-                    }
-                }
-
-                result[j][(0, 0)].proj1.evals[eval_idx] = IntMod::from(sum0_proj1);
-                result[j][(0, 0)].proj2.evals[eval_idx] = IntMod::from(sum0_proj2);
-                result[j][(1, 0)].proj1.evals[eval_idx] = IntMod::from(sum1_proj1);
-                result[j][(1, 0)].proj2.evals[eval_idx] = IntMod::from(sum1_proj2);
+        let mut sum0_proj1 = 0_u64;
+        let mut sum0_proj2 = 0_u64;
+        let mut sum1_proj1 = 0_u64;
+        let mut sum1_proj2 = 0_u64;
+
+        for i in 0..Self::PACKED_DIM1_SIZE {
+            let lhs0 = c0s[eval_idx * Self::PACKED_DIM1_SIZE + i];
+            let lhs0_proj1 = lhs0 as u32 as u64;
+            let lhs0_proj2 = lhs0 >> 32;
+
+            let lhs1 = c1s[eval_idx * Self::PACKED_DIM1_SIZE + i];
+            let lhs1_proj1 = lhs1 as u32 as u64;
+            let lhs1_proj2 = lhs1 >> 32;
+
+            let rhs = db[eval_idx * Self::PACKED_DB_SIZE + j * Self::PACKED_DIM1_SIZE + i];
+            let rhs_proj1 = rhs as u32 as u64;
+            let rhs_proj2 = rhs >> 32;
+
+            sum0_proj1 += lhs0_proj1 * rhs_proj1;
+            sum0_proj2 += lhs0_proj2 * rhs_proj2;
+            sum1_proj1 += lhs1_proj1 * rhs_proj1;
+            sum1_proj2 += lhs1_proj2 * rhs_proj2;
+
+            if i % reduce_every == 0 || i == Self::PACKED_DIM1_SIZE - 1 {
+                sum0_proj1 %= Q1A;
+                sum0_proj2 %= Q1B;
+                sum1_proj1 %= Q1A;
+                sum1_proj2 %= Q1B;
             }
         }
 
-        #[cfg(target_feature = "avx2")]
-        for eval_vec_idx in 0..(D1 / SIMD_LANES) {
-            use std::arch::x86_64::*;
-            unsafe {
-                for j in 0..Self::PACKED_DIM2_SIZE {
-                    let mut sum0_proj1 = _mm256_setzero_si256();
-                    let mut sum0_proj2 = _mm256_setzero_si256();
-                    let mut sum1_proj1 = _mm256_setzero_si256();
-                    let mut sum1_proj2 = _mm256_setzero_si256();
-
-                    for i in 0..Self::PACKED_DIM1_SIZE {
-                        let lhs0_ptr = c0s.get_unchecked(eval_vec_idx * Self::PACKED_DIM1_SIZE + i)
-                            as *const SimdVec
-                            as *const __m256i;
-                        let lhs1_ptr = c1s.get_unchecked(eval_vec_idx * Self::PACKED_DIM1_SIZE + i)
-                            as *const SimdVec
-                            as *const __m256i;
-                        let rhs_ptr = db.get_unchecked(
-                            eval_vec_idx * Self::PACKED_DB_SIZE + j * Self::PACKED_DIM1_SIZE + i,
-                        ) as *const SimdVec as *const __m256i;
-
-                        let lhs0_proj1 = _mm256_load_si256(lhs0_ptr);
-                        let lhs0_proj2 = _mm256_srli_epi64::<32>(lhs0_proj1);
-                        let lhs1_proj1 = _mm256_load_si256(lhs1_ptr);
-                        let lhs1_proj2 = _mm256_srli_epi64::<32>(lhs1_proj1);
-                        let rhs_proj1 = _mm256_load_si256(rhs_ptr);
-                        let rhs_proj2 = _mm256_srli_epi64::<32>(rhs_proj1);
-
-                        sum0_proj1 =
-                            _mm256_add_epi64(sum0_proj1, _mm256_mul_epu32(lhs0_proj1, rhs_proj1));
-                        sum0_proj2 =
-                            _mm256_add_epi64(sum0_proj2, _mm256_mul_epu32(lhs0_proj2, rhs_proj2));
-                        sum1_proj1 =
-                            _mm256_add_epi64(sum1_proj1, _mm256_mul_epu32(lhs1_proj1, rhs_proj1));
-                        sum1_proj2 =
-                            _mm256_add_epi64(sum1_proj2, _mm256_mul_epu32(lhs1_proj2, rhs_proj2));
-
-                        if i % reduce_every == 0 || i == Self::PACKED_DIM1_SIZE - 1 {
-                            let mut tmp0_proj1: SimdVec = Aligned32([0_u64; 4]);
-                            let mut tmp0_proj2: SimdVec = Aligned32([0_u64; 4]);
-                            let mut tmp1_proj1: SimdVec = Aligned32([0_u64; 4]);
-                            let mut tmp1_proj2: SimdVec = Aligned32([0_u64; 4]);
-                            _mm256_store_si256(
-                                &mut tmp0_proj1 as *mut SimdVec as *mut __m256i,
-                                sum0_proj1,
-                            );
-                            _mm256_store_si256(
-                                &mut tmp0_proj2 as *mut SimdVec as *mut __m256i,
-                                sum0_proj2,
-                            );
-                            _mm256_store_si256(
-                                &mut tmp1_proj1 as *mut SimdVec as *mut __m256i,
-                                sum1_proj1,
-                            );
-                            _mm256_store_si256(
-                                &mut tmp1_proj2 as *mut SimdVec as *mut __m256i,
-                                sum1_proj2,
-                            );
-                            for lane in 0..SIMD_LANES {
-                                tmp0_proj1.0[lane] %= Q1A;
-                                tmp0_proj2.0[lane] %= Q1B;
-                                tmp1_proj1.0[lane] %= Q1A;
-                                tmp1_proj2.0[lane] %= Q1B;
-                            }
-                            sum0_proj1 =
-                                _mm256_load_si256(&tmp0_proj1 as *const SimdVec as *const __m256i);
-                            sum0_proj2 =
-                                _mm256_load_si256(&tmp0_proj2 as *const SimdVec as *const __m256i);
-                            sum1_proj1 =
-                                _mm256_load_si256(&tmp1_proj1 as *const SimdVec as *const __m256i);
-                            sum1_proj2 =
-                                _mm256_load_si256(&tmp1_proj2 as *const SimdVec as *const __m256i);
-                        }
-                    }
+        [sum0_proj1, sum0_proj2, sum1_proj1, sum1_proj2]
+    }
+
+    #[cfg(target_feature = "avx2")]
+    fn first_dim_sums(
+        db: &<Self as PIR>::Database,
+        c0s: &[SimdVec],
+        c1s: &[SimdVec],
+        eval_vec_idx: usize,
+        j: usize,
+    ) -> [SimdVec; 4] {
+        // Norm is at most max(Q1A, Q1B)^2 for each term
+        // Add one for margin
+        let reduce_every = 1 << (64 - 2 * ceil_log(2, max(Q1A, Q1B)) - 1);
 
-                    let sum0_proj1_ptr = result[j][(0, 0)]
-                        .proj1
-                        .evals
-                        .get_unchecked_mut(eval_vec_idx * SIMD_LANES)
-                        as *mut IntMod<Q1A>
-                        as *mut __m256i;
-                    let sum0_proj2_ptr = result[j][(0, 0)]
-                        .proj2
-                        .evals
-                        .get_unchecked_mut(eval_vec_idx * SIMD_LANES)
-                        as *mut IntMod<Q1B>
-                        as *mut __m256i;
-                    let sum1_proj1_ptr = result[j][(1, 0)]
-                        .proj1
-                        .evals
-                        .get_unchecked_mut(eval_vec_idx * SIMD_LANES)
-                        as *mut IntMod<Q1A>
-                        as *mut __m256i;
-                    let sum1_proj2_ptr = result[j][(1, 0)]
-                        .proj2
-                        .evals
-                        .get_unchecked_mut(eval_vec_idx * SIMD_LANES)
-                        as *mut IntMod<Q1B>
-                        as *mut __m256i;
-                    _mm256_store_si256(sum0_proj1_ptr, sum0_proj1);
-                    _mm256_store_si256(sum0_proj2_ptr, sum0_proj2);
-                    _mm256_store_si256(sum1_proj1_ptr, sum1_proj1);
-                    _mm256_store_si256(sum1_proj2_ptr, sum1_proj2);
+        use std::arch::x86_64::*;
+        unsafe {
+            let mut sum0_proj1 = _mm256_setzero_si256();
+            let mut sum0_proj2 = _mm256_setzero_si256();
+            let mut sum1_proj1 = _mm256_setzero_si256();
+            let mut sum1_proj2 = _mm256_setzero_si256();
+
+            for i in 0..Self::PACKED_DIM1_SIZE {
+                let lhs0_ptr = c0s.get_unchecked(eval_vec_idx * Self::PACKED_DIM1_SIZE + i)
+                    as *const SimdVec as *const __m256i;
+                let lhs1_ptr = c1s.get_unchecked(eval_vec_idx * Self::PACKED_DIM1_SIZE + i)
+                    as *const SimdVec as *const __m256i;
+                let rhs_ptr = db.get_unchecked(
+                    eval_vec_idx * Self::PACKED_DB_SIZE + j * Self::PACKED_DIM1_SIZE + i,
+                ) as *const SimdVec as *const __m256i;
+
+                let lhs0_proj1 = _mm256_load_si256(lhs0_ptr);
+                let lhs0_proj2 = _mm256_srli_epi64::<32>(lhs0_proj1);
+                let lhs1_proj1 = _mm256_load_si256(lhs1_ptr);
+                let lhs1_proj2 = _mm256_srli_epi64::<32>(lhs1_proj1);
+                let rhs_proj1 = _mm256_load_si256(rhs_ptr);
+                let rhs_proj2 = _mm256_srli_epi64::<32>(rhs_proj1);
+
+                sum0_proj1 = _mm256_add_epi64(sum0_proj1, _mm256_mul_epu32(lhs0_proj1, rhs_proj1));
+                sum0_proj2 = _mm256_add_epi64(sum0_proj2, _mm256_mul_epu32(lhs0_proj2, rhs_proj2));
+                sum1_proj1 = _mm256_add_epi64(sum1_proj1, _mm256_mul_epu32(lhs1_proj1, rhs_proj1));
+                sum1_proj2 = _mm256_add_epi64(sum1_proj2, _mm256_mul_epu32(lhs1_proj2, rhs_proj2));
+
+                if i % reduce_every == 0 || i == Self::PACKED_DIM1_SIZE - 1 {
+                    let mut tmp0_proj1: SimdVec = Aligned32([0_u64; 4]);
+                    let mut tmp0_proj2: SimdVec = Aligned32([0_u64; 4]);
+                    let mut tmp1_proj1: SimdVec = Aligned32([0_u64; 4]);
+                    let mut tmp1_proj2: SimdVec = Aligned32([0_u64; 4]);
+                    _mm256_store_si256(&mut tmp0_proj1 as *mut SimdVec as *mut __m256i, sum0_proj1);
+                    _mm256_store_si256(&mut tmp0_proj2 as *mut SimdVec as *mut __m256i, sum0_proj2);
+                    _mm256_store_si256(&mut tmp1_proj1 as *mut SimdVec as *mut __m256i, sum1_proj1);
+                    _mm256_store_si256(&mut tmp1_proj2 as *mut SimdVec as *mut __m256i, sum1_proj2);
+                    for lane in 0..SIMD_LANES {
+                        tmp0_proj1.0[lane] %= Q1A;
+                        tmp0_proj2.0[lane] %= Q1B;
+                        tmp1_proj1.0[lane] %= Q1A;
+                        tmp1_proj2.0[lane] %= Q1B;
+                    }
+                    sum0_proj1 = _mm256_load_si256(&tmp0_proj1 as *const SimdVec as *const __m256i);
+                    sum0_proj2 = _mm256_load_si256(&tmp0_proj2 as *const SimdVec as *const __m256i);
+                    sum1_proj1 = _mm256_load_si256(&tmp1_proj1 as *const SimdVec as *const __m256i);
+                    sum1_proj2 = _mm256_load_si256(&tmp1_proj2 as *const SimdVec as *const __m256i);
                 }
             }
+
+            let mut out: [SimdVec; 4] = [
+                Aligned32([0_u64; 4]),
+                Aligned32([0_u64; 4]),
+                Aligned32([0_u64; 4]),
+                Aligned32([0_u64; 4]),
+            ];
+            _mm256_store_si256(&mut out[0] as *mut SimdVec as *mut __m256i, sum0_proj1);
+            _mm256_store_si256(&mut out[1] as *mut SimdVec as *mut __m256i, sum0_proj2);
+            _mm256_store_si256(&mut out[2] as *mut SimdVec as *mut __m256i, sum1_proj1);
+            _mm256_store_si256(&mut out[3] as *mut SimdVec as *mut __m256i, sum1_proj2);
+            out
         }
+    }
 
-        result
+    fn first_dim_store(
+        ct: &mut <Self as Respire>::RLWEEncoding,
+        eval_vec_idx: usize,
+        [sum0_proj1, sum0_proj2, sum1_proj1, sum1_proj2]: [SimdVec; 4],
+    ) {
+        #[cfg(not(target_feature = "avx2"))]
+        {
+            ct[(0, 0)].proj1.evals[eval_vec_idx] = IntMod::from(sum0_proj1);
+            ct[(0, 0)].proj2.evals[eval_vec_idx] = IntMod::from(sum0_proj2);
+            ct[(1, 0)].proj1.evals[eval_vec_idx] = IntMod::from(sum1_proj1);
+            ct[(1, 0)].proj2.evals[eval_vec_idx] = IntMod::from(sum1_proj2);
+        }
+        #[cfg(target_feature = "avx2")]
+        for lane in 0..SIMD_LANES {
+            let eval_idx = eval_vec_idx * SIMD_LANES + lane;
+            ct[(0, 0)].proj1.evals[eval_idx] = IntMod::from(sum0_proj1.0[lane]);
+            ct[(0, 0)].proj2.evals[eval_idx] = IntMod::from(sum0_proj2.0[lane]);
+            ct[(1, 0)].proj1.evals[eval_idx] = IntMod::from(sum1_proj1.0[lane]);
+            ct[(1, 0)].proj2.evals[eval_idx] = IntMod::from(sum1_proj2.0[lane]);
+        }
     }
 
     pub fn answer_fold(
         first_dim_folded: Vec<<Self as Respire>::RLWEEncoding>,
         gsws: &[<Self as Respire>::GSWEncoding],
+    ) -> <Self as Respire>::RLWEEncoding {
+        Self::answer_fold_with(first_dim_folded, gsws, |_| {})
+    }
+
+    /// Like [`Self::answer_fold`], calling `inspect` on each ciphertext output by each level.
+    pub fn answer_fold_with(
+        first_dim_folded: Vec<<Self as Respire>::RLWEEncoding>,
+        gsws: &[<Self as Respire>::GSWEncoding],
+        mut inspect: impl FnMut(&<Self as Respire>::RLWEEncoding),
     ) -> <Self as Respire>::RLWEEncoding {
         assert_eq!(gsws.len(), Self::NU2);
         let fold_size: usize = 2usize.pow(Self::NU2 as u32);
@@ -1737,6 +2663,7 @@ respire_impl!({
                     &curr[curr_size / 2 + fold_idx],
                     &gsws[gsw_idx],
                 );
+                inspect(&curr[fold_idx]);
             }
             curr_size /= 2;
         }
@@ -1746,6 +2673,15 @@ respire_impl!({
     pub fn answer_rotate(
         ct: &<Self as Respire>::RLWEEncoding,
         gsws_rot: &[<Self as Respire>::GSWEncoding],
+    ) -> <Self as Respire>::RLWEEncoding {
+        Self::answer_rotate_with(ct, gsws_rot, |_| {})
+    }
+
+    /// Like [`Self::answer_rotate`], calling `inspect` on the ciphertext after each step.
+    pub fn answer_rotate_with(
+        ct: &<Self as Respire>::RLWEEncoding,
+        gsws_rot: &[<Self as Respire>::GSWEncoding],
+        mut inspect: impl FnMut(&<Self as Respire>::RLWEEncoding),
     ) -> <Self as Respire>::RLWEEncoding {
         assert_eq!(gsws_rot.len(), Self::NU3);
         let mut ct_curr = ct.clone();
@@ -1753,6 +2689,7 @@ respire_impl!({
             let rot = 1 << (Self::NU3 - 1 - iter_num);
             ct_curr =
                 Self::select_hom(&ct_curr, &Self::rlwe_mul_x_pow(&ct_curr, 2 * D1 - rot), gsw);
+            inspect(&ct_curr);
         }
         ct_curr
     }
@@ -1780,8 +2717,7 @@ respire_impl!({
         ct_curr
     }
 
-    pub fn encode_setup() -> <Self as Respire>::RingQ1Fast {
-        let mut rng = ChaCha20Rng::from_entropy();
+    pub fn encode_setup<R: RngCore + CryptoRng>(rng: &mut R) -> <Self as Respire>::RingQ1Fast {
         let mut result = <Self as Respire>::RingQ1::zero();
         for coeff in result.coeff.iter_mut() {
             *coeff = IntMod::from(rng.gen_range(-(SECRET_BOUND as i64)..(SECRET_BOUND as i64)));
@@ -1789,30 +2725,29 @@ respire_impl!({
         <Self as Respire>::RingQ1Fast::from(&result)
     }
 
-    pub fn encode_vec_setup() -> <Self as Respire>::VecEncodingSecret {
-        let mut rng = ChaCha20Rng::from_entropy();
+    pub fn encode_vec_setup<R: RngCore + CryptoRng>(
+        rng: &mut R,
+    ) -> <Self as Respire>::VecEncodingSecret {
         let mut result = Matrix::zero();
         for i in 0..N_VEC {
             result[(i, 0)] = <Self as Respire>::RingQ1Fast::from(
                 &<Self as Respire>::RingQ1::rand_discrete_gaussian::<_, SECRET_WIDTH_VEC_MILLIONTHS>(
-                    &mut rng,
+                    rng,
                 ),
             );
         }
         result
     }
 
-    pub fn encode_rlwe(
+    pub fn encode_rlwe<R: RngCore + CryptoRng>(
         s_encode: &<Self as Respire>::EncodingSecret,
         mu: &<Self as Respire>::RingQ1,
+        rng: &mut R,
     ) -> <Self as Respire>::RLWEEncoding {
-        let mut rng = ChaCha20Rng::from_entropy();
         let mut c = Matrix::zero();
-        c[(0, 0)] = <Self as Respire>::RingQ1Fast::rand_uniform(&mut rng);
+        c[(0, 0)] = <Self as Respire>::RingQ1Fast::rand_uniform(rng);
         let e = <Self as Respire>::RingQ1Fast::from(
-            &<Self as Respire>::RingQ1::rand_discrete_gaussian::<_, ERROR_WIDTH_MILLIONTHS>(
-                &mut rng,
-            ),
+            &<Self as Respire>::RingQ1::rand_discrete_gaussian::<_, ERROR_WIDTH_MILLIONTHS>(rng),
         );
         let mut c1 = &c[(0, 0)] * s_encode;
         c1 += &e;
@@ -1821,20 +2756,18 @@ respire_impl!({
         c
     }
 
-    pub fn encode_rlwe_seeded(
+    pub fn encode_rlwe_seeded<R: RngCore + CryptoRng>(
         s_encode: &<Self as Respire>::EncodingSecret,
         mu: &<Self as Respire>::RingQ1,
+        rng: &mut R,
     ) -> <Self as Respire>::RLWEEncodingSeeded {
-        let mut rng = ChaCha20Rng::from_entropy();
         let seed = rng.gen();
         let c0 = {
             let mut seeded_rng = ChaCha20Rng::from_seed(seed);
             <Self as Respire>::RingQ1Fast::rand_uniform(&mut seeded_rng)
         };
         let e = <Self as Respire>::RingQ1Fast::from(
-            &<Self as Respire>::RingQ1::rand_discrete_gaussian::<_, ERROR_WIDTH_MILLIONTHS>(
-                &mut rng,
-            ),
+            &<Self as Respire>::RingQ1::rand_discrete_gaussian::<_, ERROR_WIDTH_MILLIONTHS>(rng),
         );
         let mut c1 = &c0 * s_encode;
         c1 += &e;
@@ -1855,6 +2788,15 @@ respire_impl!({
         result
     }
 
+    ///
+    /// Expands a 32 byte seed into a uniformly random value. Used for the random components of
+    /// seeded ciphertexts and public params.
+    ///
+    pub fn rand_uniform_seeded<T: RandUniformSampled>(seed: [u8; 32]) -> T {
+        let mut seeded_rng = ChaCha20Rng::from_seed(seed);
+        T::rand_uniform(&mut seeded_rng)
+    }
+
     pub fn rand_discrete_gaussian_matrix<
         const WIDTH_MILLIONTHS: u64,
         const N: usize,
@@ -1874,15 +2816,13 @@ respire_impl!({
         result
     }
 
-    pub fn encode_vec_rlwe(
+    pub fn encode_vec_rlwe<R: RngCore + CryptoRng>(
         s_vec: &<Self as Respire>::VecEncodingSecret,
         mu: &Matrix<N_VEC, 1, <Self as Respire>::RingQ1>,
+        rng: &mut R,
     ) -> <Self as Respire>::VecRLWEEncoding {
-        let mut rng = ChaCha20Rng::from_entropy();
-        let c_r = <Self as Respire>::RingQ1Fast::rand_uniform(&mut rng);
-        let e = Self::rand_discrete_gaussian_matrix::<ERROR_WIDTH_VEC_MILLIONTHS, N_VEC, 1, _>(
-            &mut rng,
-        );
+        let c_r = <Self as Respire>::RingQ1Fast::rand_uniform(rng);
+        let e = Self::rand_discrete_gaussian_matrix::<ERROR_WIDTH_VEC_MILLIONTHS, N_VEC, 1, _>(rng);
         let mut c_m = s_vec * &c_r;
         c_m += &e;
         c_m += &mu.map_ring(|r| <Self as Respire>::RingQ1Fast::from(r));
@@ -1903,14 +2843,13 @@ respire_impl!({
         (c_m - &(s_vec * c_r)).map_ring(|r| <Self as Respire>::RingQ1::from(r))
     }
 
-    pub fn encode_gsw(
+    pub fn encode_gsw<R: RngCore + CryptoRng>(
         s_encode: &<Self as Respire>::EncodingSecret,
         mu: &<Self as Respire>::RingQ1,
+        rng: &mut R,
     ) -> <Self as Respire>::GSWEncoding {
-        let mut rng = ChaCha20Rng::from_entropy();
-        let a_t: Matrix<1, M_GSW, <Self as Respire>::RingQ1Fast> = Matrix::rand_uniform(&mut rng);
-        let e_mat =
-            Self::rand_discrete_gaussian_matrix::<ERROR_WIDTH_MILLIONTHS, 1, M_GSW, _>(&mut rng);
+        let a_t: Matrix<1, M_GSW, <Self as Respire>::RingQ1Fast> = Matrix::rand_uniform(rng);
+        let e_mat = Self::rand_discrete_gaussian_matrix::<ERROR_WIDTH_MILLIONTHS, 1, M_GSW, _>(rng);
         let c_mat: Matrix<2, M_GSW, <Self as Respire>::RingQ1Fast> =
             &Matrix::stack(&a_t, &(&(&a_t * s_encode) + &e_mat))
                 + &(&build_gadget::<<Self as Respire>::RingQ1Fast, 2, M_GSW, Z_GSW, T_GSW>()
@@ -1973,19 +2912,36 @@ respire_impl!({
         c.map_ring(|x| x.mul_x_pow(k))
     }
 
-    pub fn auto_setup<const LEN: usize, const BASE: u64>(
+    pub fn auto_setup<const LEN: usize, const BASE: u64, R: RngCore + CryptoRng>(
         tau_power: usize,
         s_encode: &<Self as Respire>::RingQ1Fast,
+        rng: &mut R,
     ) -> <Self as Respire>::AutoParams<LEN> {
-        let mut rng = ChaCha20Rng::from_entropy();
-        let a_t: Matrix<1, LEN, <Self as Respire>::RingQ1Fast> = Matrix::rand_uniform(&mut rng);
-        let e_t =
-            Self::rand_discrete_gaussian_matrix::<ERROR_WIDTH_MILLIONTHS, 1, LEN, _>(&mut rng);
+        Self::auto_recover_from_seeded(&Self::auto_setup_seeded::<LEN, BASE, _>(
+            tau_power, s_encode, rng,
+        ))
+    }
+
+    pub fn auto_setup_seeded<const LEN: usize, const BASE: u64, R: RngCore + CryptoRng>(
+        tau_power: usize,
+        s_encode: &<Self as Respire>::RingQ1Fast,
+        rng: &mut R,
+    ) -> <Self as Respire>::AutoParamsSeeded<LEN> {
+        let seed = rng.gen();
+        let a_t: Matrix<1, LEN, <Self as Respire>::RingQ1Fast> = Self::rand_uniform_seeded(seed);
+        let e_t = Self::rand_discrete_gaussian_matrix::<ERROR_WIDTH_MILLIONTHS, 1, LEN, _>(rng);
         let mut bottom = &a_t * s_encode;
         bottom += &e_t;
         bottom -= &(&build_gadget::<<Self as Respire>::RingQ1Fast, 1, LEN, BASE, LEN>()
             * &s_encode.auto(tau_power));
-        (Matrix::stack(&a_t, &bottom), tau_power)
+        (seed, bottom, tau_power)
+    }
+
+    pub fn auto_recover_from_seeded<const LEN: usize>(
+        (seed, bottom, tau_power): &<Self as Respire>::AutoParamsSeeded<LEN>,
+    ) -> <Self as Respire>::AutoParams<LEN> {
+        let a_t: Matrix<1, LEN, <Self as Respire>::RingQ1Fast> = Self::rand_uniform_seeded(*seed);
+        (Matrix::stack(&a_t, bottom), *tau_power)
     }
 
     pub fn auto_hom<const LEN: usize, const BASE: u64>(
@@ -2054,15 +3010,22 @@ respire_impl!({
         ct + &ct_auto
     }
 
-    pub fn rlwe_to_gsw_setup(
+    pub fn rlwe_to_gsw_setup<R: RngCore + CryptoRng>(
         s_encode: &<Self as Respire>::EncodingSecret,
+        rng: &mut R,
     ) -> <Self as Respire>::RLWEToGSWParams {
-        let mut rng = ChaCha20Rng::from_entropy();
-        let a_t = Matrix::<1, M_RLWE_TO_GSW, <Self as Respire>::RingQ1Fast>::rand_uniform(&mut rng);
+        Self::rlwe_to_gsw_recover_from_seeded(&Self::rlwe_to_gsw_setup_seeded(s_encode, rng))
+    }
+
+    pub fn rlwe_to_gsw_setup_seeded<R: RngCore + CryptoRng>(
+        s_encode: &<Self as Respire>::EncodingSecret,
+        rng: &mut R,
+    ) -> <Self as Respire>::RLWEToGSWParamsSeeded {
+        let seed = rng.gen();
+        let a_t: Matrix<1, M_RLWE_TO_GSW, <Self as Respire>::RingQ1Fast> =
+            Self::rand_uniform_seeded(seed);
         let e_mat =
-            Self::rand_discrete_gaussian_matrix::<ERROR_WIDTH_MILLIONTHS, 1, M_RLWE_TO_GSW, _>(
-                &mut rng,
-            );
+            Self::rand_discrete_gaussian_matrix::<ERROR_WIDTH_MILLIONTHS, 1, M_RLWE_TO_GSW, _>(rng);
         let mut bottom = &a_t * s_encode;
         bottom += &e_mat;
         let g_vec = build_gadget::<
@@ -2078,7 +3041,15 @@ respire_impl!({
         s_encode_tensor_g.copy_into(&(&g_vec * &(-s_encode)), 0, 0);
         bottom -= &(&s_encode_tensor_g * s_encode);
 
-        Matrix::stack(&a_t, &bottom)
+        (seed, bottom)
+    }
+
+    pub fn rlwe_to_gsw_recover_from_seeded(
+        (seed, bottom): &<Self as Respire>::RLWEToGSWParamsSeeded,
+    ) -> <Self as Respire>::RLWEToGSWParams {
+        let a_t: Matrix<1, M_RLWE_TO_GSW, <Self as Respire>::RingQ1Fast> =
+            Self::rand_uniform_seeded(*seed);
+        Matrix::stack(&a_t, bottom)
     }
 
     pub fn rlwe_to_gsw(
@@ -2108,52 +3079,85 @@ respire_impl!({
         result
     }
 
-    pub fn compress_setup(
+    pub fn compress_setup<R: RngCore + CryptoRng>(
         s_from: &<Self as Respire>::VecEncodingSecretQ2,
         s_to: &<Self as Respire>::VecEncodingSecretQ2,
+        rng: &mut R,
     ) -> <Self as Respire>::CompressParams {
-        let mut rng = ChaCha20Rng::from_entropy();
-        let a_t = Matrix::<1, T_COMPRESS, IntModCycloEval<D1, Q2>>::rand_uniform(&mut rng);
+        Self::compress_recover_from_seeded(&Self::compress_setup_seeded(s_from, s_to, rng))
+    }
+
+    pub fn compress_setup_seeded<R: RngCore + CryptoRng>(
+        s_from: &<Self as Respire>::VecEncodingSecretQ2,
+        s_to: &<Self as Respire>::VecEncodingSecretQ2,
+        rng: &mut R,
+    ) -> <Self as Respire>::CompressParamsSeeded {
+        let seed = rng.gen();
+        let a_t: Matrix<1, T_COMPRESS, IntModCycloEval<D1, Q2>> = Self::rand_uniform_seeded(seed);
         let e_mat = Matrix::<N_VEC, T_COMPRESS, IntModCycloEval<D1, Q2>>::rand_discrete_gaussian::<
             _,
             ERROR_WIDTH_COMPRESS_MILLIONTHS,
-        >(&mut rng);
+        >(rng);
         let mut b_mat = &(-s_from)
             * &build_gadget::<IntModCycloEval<D1, Q2>, 1, T_COMPRESS, Z_COMPRESS, T_COMPRESS>();
         b_mat += &(s_to * &a_t);
         b_mat += &e_mat;
-        (a_t, b_mat)
+        (seed, b_mat)
+    }
+
+    pub fn compress_recover_from_seeded(
+        (seed, b_mat): &<Self as Respire>::CompressParamsSeeded,
+    ) -> <Self as Respire>::CompressParams {
+        (Self::rand_uniform_seeded(*seed), b_mat.clone())
     }
 
-    pub fn vectorize_setup(
+    pub fn vectorize_setup<R: RngCore + CryptoRng>(
         s_scal: &<Self as Respire>::EncodingSecret,
         s_vec: &<Self as Respire>::VecEncodingSecret,
+        rng: &mut R,
     ) -> <Self as Respire>::VectorizeParams {
-        let mut rng = ChaCha20Rng::from_entropy();
+        Self::vectorize_recover_from_seeded(&Self::vectorize_setup_seeded(s_scal, s_vec, rng))
+    }
+
+    pub fn vectorize_setup_seeded<R: RngCore + CryptoRng>(
+        s_scal: &<Self as Respire>::EncodingSecret,
+        s_vec: &<Self as Respire>::VecEncodingSecret,
+        rng: &mut R,
+    ) -> <Self as Respire>::VectorizeParamsSeeded {
         let mut result = Vec::with_capacity(N_VEC);
         for i in 0..N_VEC {
             let mut unit = Matrix::<N_VEC, 1, <Self as Respire>::RingQ1Fast>::zero();
             unit[(i, 0)] = <Self as Respire>::RingQ1Fast::one();
             let unit = unit;
 
-            let a_t =
-                Matrix::<1, T_VECTORIZE, <Self as Respire>::RingQ1Fast>::rand_uniform(&mut rng);
+            let seed = rng.gen();
+            let a_t: Matrix<1, T_VECTORIZE, <Self as Respire>::RingQ1Fast> =
+                Self::rand_uniform_seeded(seed);
             let e_mat = Self::rand_discrete_gaussian_matrix::<
                 ERROR_WIDTH_VEC_MILLIONTHS,
                 N_VEC,
                 T_VECTORIZE,
                 _,
-            >(&mut rng);
+            >(rng);
             let mut bottom = s_vec * &a_t;
             bottom += &e_mat;
             let embedding =
                 &(&unit * s_scal) * &build_gadget::<_, 1, T_VECTORIZE, Z_VECTORIZE, T_VECTORIZE>();
             bottom -= &embedding;
-            result.push((a_t, bottom));
+            result.push((seed, bottom));
         }
         result
     }
 
+    pub fn vectorize_recover_from_seeded(
+        params: &<Self as Respire>::VectorizeParamsSeeded,
+    ) -> <Self as Respire>::VectorizeParams {
+        params
+            .iter()
+            .map(|(seed, bottom)| (Self::rand_uniform_seeded(*seed), bottom.clone()))
+            .collect()
+    }
+
     pub fn scal_to_vec(
         s_scal_to_vec: &<Self as Respire>::VectorizeParams,
         cs: &[<Self as Respire>::RLWEEncoding; N_VEC],
@@ -2171,6 +3175,119 @@ respire_impl!({
         (result_rand, result_embed)
     }
 
+    fn pack_ring_q1_mat<const N: usize, const M: usize>(
+        bits: &mut BitVec<u8, Msb0>,
+        mat: &Matrix<N, M, <Self as Respire>::RingQ1Fast>,
+    ) {
+        for i in 0..N {
+            for j in 0..M {
+                pack_coeffs(
+                    bits,
+                    <Self as Respire>::RingQ1::from(&mat[(i, j)])
+                        .coeff
+                        .iter()
+                        .copied(),
+                );
+            }
+        }
+    }
+
+    /// Largest absolute coefficient of the vector and compression secrets.
+    fn secret_gaussian_bounds() -> (u64, u64) {
+        (
+            DiscreteGaussian::max_value(SECRET_WIDTH_VEC_MILLIONTHS),
+            DiscreteGaussian::max_value(SECRET_WIDTH_COMPRESS_MILLIONTHS),
+        )
+    }
+
+    fn unpack_ring_q1_mat<const N: usize, const M: usize>(
+        cursor: &mut BitCursor,
+    ) -> Result<Matrix<N, M, <Self as Respire>::RingQ1Fast>, WireError> {
+        let mut result = Matrix::zero();
+        for i in 0..N {
+            for j in 0..M {
+                let mut elem = <Self as Respire>::RingQ1::zero();
+                cursor.read_coeffs(&mut elem.coeff)?;
+                result[(i, j)] = <Self as Respire>::RingQ1Fast::from(&elem);
+            }
+        }
+        Ok(result)
+    }
+
+    ///
+    /// Position of packed chunk `db_idx` within each evaluation slice of the database, which is
+    /// stored transposed (see `answer_first_dim`).
+    ///
+    fn db_idx_transposed(db_idx: usize) -> usize {
+        let (db_i, db_j) = (
+            db_idx / Self::PACKED_DIM2_SIZE,
+            db_idx % Self::PACKED_DIM2_SIZE,
+        );
+        db_j * Self::PACKED_DIM1_SIZE + db_i
+    }
+
+    ///
+    /// Packs `PACK_RATIO_DB` encoded records into one plaintext, and returns its CRT-NTT evaluations
+    /// (the two CRT components in the low and high 32 bits respectively).
+    ///
+    fn pack_db_chunk(records: impl Iterator<Item = <Self as Respire>::Record>) -> [u64; D1] {
+        let mut record_packed = IntModCyclo::<D1, P>::zero();
+        for (record_in_chunk, record) in records.enumerate() {
+            for (coeff_idx, coeff) in record.coeff.iter().enumerate() {
+                record_packed.coeff[Self::PACK_RATIO_DB * coeff_idx + record_in_chunk] = *coeff;
+            }
+        }
+        let value = <Self as Respire>::RingQ1Fast::from(&record_packed.include_into::<Q1>());
+        let mut packed_value = [0u64; D1];
+        for i in 0..D1 {
+            packed_value[i] = {
+                let lo = u64::from(value.proj1.evals[i]);
+                let hi = u64::from(value.proj2.evals[i]);
+                (hi << 32) | lo
+            };
+        }
+        packed_value
+    }
+
+    ///
+    /// Inverse of `pack_db_chunk`, reading packed chunk `chunk_idx` back from an encoded database.
+    /// The plaintext coefficients are smaller than `P < Q1A`, so the first CRT component alone
+    /// determines them.
+    ///
+    fn unpack_db_chunk(db: &[SimdVec], chunk_idx: usize) -> Vec<<Self as Respire>::Record> {
+        assert!(P < Q1A);
+        let db_idx_t = Self::db_idx_transposed(chunk_idx);
+        let mut evals = IntModCycloEval::<D1, Q1A>::zero();
+        for eval_vec_idx in 0..(D1 / SIMD_LANES) {
+            let v = &db[eval_vec_idx * Self::PACKED_DB_SIZE + db_idx_t];
+            for lane in 0..SIMD_LANES {
+                evals.evals[eval_vec_idx * SIMD_LANES + lane] =
+                    IntMod::from(simd_lane(v, lane) as u32 as u64);
+            }
+        }
+        let record_packed = IntModCyclo::<D1, Q1A>::from(evals);
+        (0..Self::PACK_RATIO_DB)
+            .map(|record_in_chunk| {
+                let coeff: [IntMod<P>; D3] = std::array::from_fn(|coeff_idx| {
+                    let coeff =
+                        record_packed.coeff[Self::PACK_RATIO_DB * coeff_idx + record_in_chunk];
+                    IntMod::from(u64::from(coeff))
+                });
+                IntModCyclo::from(coeff)
+            })
+            .collect()
+    }
+
+    fn write_db_chunk(db: &mut [SimdVec], chunk_idx: usize, packed_value: &[u64; D1]) {
+        let db_idx_t = Self::db_idx_transposed(chunk_idx);
+        for eval_vec_idx in 0..(D1 / SIMD_LANES) {
+            let v = &mut db[eval_vec_idx * Self::PACKED_DB_SIZE + db_idx_t];
+            for lane in 0..SIMD_LANES {
+                *simd_lane_mut(v, lane) = packed_value[eval_vec_idx * SIMD_LANES + lane];
+            }
+        }
+    }
+
     pub fn encode_record(bytes: &RecordBytesImpl<BYTES_PER_RECORD>) -> <Self as Respire>::Record {
         let bit_iter = BitSlice::<u8, Msb0>::from_slice(&bytes.it);
         let p_bits = floor_log(2, P);
@@ -2206,11 +3323,15 @@ respire_impl!({
         let decoded: <Self as Respire>::RingQ1 = Self::decode_rlwe(&s_scal, c);
         let message: <Self as Respire>::RingP = decoded.round_down_into();
         let noise: <Self as Respire>::RingQ1 = &decoded - &message.scale_up_into();
+        Self::coeff_variance(&[noise])
+    }
 
+    /// Mean square of the (centered) coefficients of `noise`.
+    fn coeff_variance(noise: &[<Self as Respire>::RingQ1]) -> f64 {
         let mut sum = 0_f64;
         let mut samples = 0_usize;
 
-        for e in noise.coeff.iter() {
+        for e in noise.iter().flat_map(|r| r.coeff.iter()) {
             let e_sq = (e.norm() as f64) * (e.norm() as f64);
             sum += e_sq;
             samples += 1;
@@ -2219,6 +3340,48 @@ respire_impl!({
         sum / samples as f64
     }
 
+    ///
+    /// Noise of a GSW encoding of `mu`, i.e. of `s^T C - mu s^T G` with `s^T = [-s, 1]`, as log2
+    /// of its subgaussian width.
+    ///
+    pub fn noise_subgaussian_bits_gsw(
+        s_encode: &<Self as Respire>::EncodingSecret,
+        c: &<Self as Respire>::GSWEncoding,
+        mu: &<Self as Respire>::RingQ1,
+    ) -> f64 {
+        let mut s_t = Matrix::<1, 2, <Self as Respire>::RingQ1Fast>::zero();
+        s_t[(0, 0)] = (-s_encode).clone();
+        s_t[(0, 1)] = <Self as Respire>::RingQ1Fast::one();
+        let gadget = build_gadget::<<Self as Respire>::RingQ1Fast, 2, M_GSW, Z_GSW, T_GSW>();
+        let noise = &(&s_t * c) - &(&(&s_t * &gadget) * &<Self as Respire>::RingQ1Fast::from(mu));
+        let noise = (0..M_GSW)
+            .map(|j| <Self as Respire>::RingQ1::from(&noise[(0, j)]))
+            .collect_vec();
+        Self::variance_to_subgaussian_bits(Self::coeff_variance(&noise))
+    }
+
+    ///
+    /// Noise of a compressed answer in units of `Q3`, as log2 of its subgaussian width. Like
+    /// [`Self::noise_variance`], this assumes the noise is small enough to decode correctly.
+    ///
+    pub fn noise_subgaussian_bits_compressed(
+        qk: &<Self as PIR>::QueryKey,
+        r: &<Self as Respire>::AnswerOneCompressed,
+    ) -> f64 {
+        let decoded = Self::decode_compressed(qk, r);
+        let mut sum = 0_f64;
+        let mut samples = 0_usize;
+        for i in 0..Self::response_one_len(r) {
+            let message: IntModCyclo<D2, P> = decoded[(i, 0)].round_down_into();
+            let noise = &decoded[(i, 0)] - &message.scale_up_into();
+            for e in noise.coeff.iter() {
+                sum += (e.norm() as f64).powi(2);
+                samples += 1;
+            }
+        }
+        Self::variance_to_subgaussian_bits(sum / samples as f64)
+    }
+
     fn variance_to_subgaussian_bits(x: f64) -> f64 {
         // Subgaussian widths = sqrt(2*pi) * (standard deviation)
         (x * (2f64 * PI)).log2() / 2f64
@@ -2246,6 +3409,16 @@ respire_impl!({
         Self::variance_to_subgaussian_bits(total / N_VEC as f64)
     }
 
+    ///
+    /// Number of queries sent for a batch of `num_requested` records. Batches are padded with dummy
+    /// queries to fill the last packed ring element of the response.
+    ///
+    pub fn padded_batch_size(num_requested: usize) -> usize {
+        num_requested
+            .next_multiple_of(Self::PACK_RATIO_RESPONSE)
+            .min(Self::BATCH_SIZE)
+    }
+
     pub fn params_query_size() -> usize {
         Self::BATCH_SIZE * Self::params_query_one_size()
     }
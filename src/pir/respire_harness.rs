@@ -1,10 +1,16 @@
+use crate::pir::noise::{NoiseMeasurement, NoisePhase, NoiseProbe};
 use crate::pir::pir::{PIRRecordBytes, Stats, PIR};
-use crate::pir::respire::{RespireParams, RespireParamsExpanded};
+use crate::pir::record_source::{
+    encode_db_from_source, open_record_source, read_pir_record, RecordSource, SyntheticRecords,
+};
+use crate::pir::respire::{Respire, RespireParams, RespireParamsExpanded};
 use crate::respire;
 use clap::Parser;
 use itertools::Itertools;
+use log::{info, log_enabled, Level};
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha20Rng;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
 //
@@ -147,6 +153,26 @@ pub const RESPIRE_TEST_PARAMS: RespireParamsExpanded =
 
 pub type RespireTest = respire!(RESPIRE_TEST_PARAMS);
 
+// Small enough to encode quickly in debug builds
+#[cfg(test)]
+pub(crate) const RESPIRE_SMALL_PARAMS: RespireParamsExpanded =
+    FactoryParams::single_record_256(2, 2).expand().expand();
+#[cfg(test)]
+pub(crate) type RespireSmall = respire!(RESPIRE_SMALL_PARAMS);
+
+// Like `RESPIRE_SMALL_PARAMS`, with batches of 4 records
+#[cfg(test)]
+pub(crate) const RESPIRE_SMALL_BATCH_PARAMS: RespireParamsExpanded =
+    FactoryParams::batch_256(4, 1, 2, 2).expand().expand();
+#[cfg(test)]
+pub(crate) type RespireSmallBatch = respire!(RESPIRE_SMALL_BATCH_PARAMS);
+
+/// A path named `name` in the temporary directory, unique to the test process.
+#[cfg(test)]
+pub(crate) fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("respire-{}-{}", std::process::id(), name))
+}
+
 #[cfg(not(target_feature = "avx2"))]
 pub fn has_avx2() -> bool {
     false
@@ -160,9 +186,71 @@ pub fn has_avx2() -> bool {
 pub struct RunResult {
     pub init_times: Stats<Duration>,
     pub all_trial_times: Vec<Stats<Duration>>,
+    /// The noise measured while answering each trial, if it was probed (see [`run_respire`])
+    pub all_trial_noise: Vec<Vec<NoiseMeasurement>>,
+}
+
+/// Answers a trial's query again, measuring its noise: called with the query key, public params,
+/// database, query and queried indices.
+type AnswerProbe<ThePIR> = fn(
+    &<ThePIR as PIR>::QueryKey,
+    &<ThePIR as PIR>::PublicParams,
+    &<ThePIR as PIR>::Database,
+    &<ThePIR as PIR>::Query,
+    &[usize],
+) -> Vec<NoiseMeasurement>;
+
+fn probe_respire_answer<ThePIR>(
+    qk: &<ThePIR as PIR>::QueryKey,
+    pp: &<ThePIR as PIR>::PublicParams,
+    db: &<ThePIR as PIR>::Database,
+    q: &<ThePIR as PIR>::Query,
+    indices: &[usize],
+) -> Vec<NoiseMeasurement>
+where
+    ThePIR: Respire + PIR<Query = Vec<<ThePIR as Respire>::QueryOne>>,
+{
+    let mut probe = NoiseProbe::new(qk, indices);
+    ThePIR::answer_probed(pp, db, q, &mut probe, None).expect("probed answer failed");
+    probe.measurements().to_vec()
+}
+
+///
+/// Runs the PIR scheme on batches of indices from `iter`, over the records of `records`. If
+/// `db_file` is given, the encoded database is loaded from it when it exists, and saved to it after
+/// encoding otherwise.
+///
+pub fn run_pir<ThePIR: PIR, I: Iterator<Item = usize>>(
+    iter: I,
+    records: &dyn RecordSource,
+    db_file: Option<&Path>,
+) -> RunResult {
+    run_pir_probed::<ThePIR, I>(iter, records, db_file, None)
+}
+
+///
+/// Like [`run_pir`], for a RESPIRE scheme. When info logging is enabled, each trial's query is also
+/// answered with [`Respire::answer_probed`] (outside of the timed section), and the noise of every
+/// phase is logged and returned in [`RunResult::all_trial_noise`].
+///
+pub fn run_respire<ThePIR, I: Iterator<Item = usize>>(
+    iter: I,
+    records: &dyn RecordSource,
+    db_file: Option<&Path>,
+) -> RunResult
+where
+    ThePIR: Respire + PIR<Query = Vec<<ThePIR as Respire>::QueryOne>>,
+{
+    run_pir_probed::<ThePIR, I>(iter, records, db_file, Some(probe_respire_answer::<ThePIR>))
 }
 
-pub fn run_pir<ThePIR: PIR, I: Iterator<Item = usize>>(iter: I) -> RunResult {
+fn run_pir_probed<ThePIR: PIR, I: Iterator<Item = usize>>(
+    iter: I,
+    records: &dyn RecordSource,
+    db_file: Option<&Path>,
+    probe: Option<AnswerProbe<ThePIR>>,
+) -> RunResult {
+    let probe = probe.filter(|_| log_enabled!(Level::Info));
     eprintln!("Running PIR...");
     eprintln!(
         "AVX2 is {}",
@@ -176,25 +264,29 @@ pub fn run_pir<ThePIR: PIR, I: Iterator<Item = usize>>(iter: I) -> RunResult {
     ThePIR::print_summary();
     eprintln!("========");
 
-    let records_generator = |i: usize| {
-        let mut record = vec![0_u8; ThePIR::BYTES_PER_RECORD];
-        record[0] = (i % 256) as u8;
-        record[1] = ((i / 256) % 256) as u8;
-        record[2] = 42_u8;
-        record[3] = 0_u8;
-        record[4] = (i % 100) as u8;
-        record[5] = ((i / 100) % 100) as u8;
-        record[6] = ((i / 100 / 100) % 100) as u8;
-        record[7] = ((i / 100 / 100 / 100) % 100) as u8;
-        // for i in 8..256 {
-        //     record[i] = random();
-        // }
-        ThePIR::RecordBytes::from_bytes(record.as_slice()).unwrap()
-    };
+    let records_generator =
+        |i: usize| read_pir_record::<ThePIR>(records, i).expect("failed to read record");
 
     let mut init_times = Stats::new();
     let begin = Instant::now();
-    let (db, db_hint) = ThePIR::encode_db(records_generator, Some(&mut init_times));
+    let (db, db_hint) = match db_file {
+        Some(path) if path.exists() => {
+            eprintln!("Loading DB from {}", path.display());
+            let load_begin = Instant::now();
+            let loaded = ThePIR::load_db(path).expect("failed to load DB file");
+            init_times.add("load_db", Instant::now() - load_begin);
+            loaded
+        }
+        _ => {
+            let encoded = encode_db_from_source::<ThePIR>(records, Some(&mut init_times))
+                .expect("failed to encode records");
+            if let Some(path) = db_file {
+                eprintln!("Saving DB to {}", path.display());
+                ThePIR::save_db(&encoded.0, &encoded.1, path).expect("failed to save DB file");
+            }
+            encoded
+        }
+    };
     let (qk, pp) = ThePIR::setup(Some(&mut init_times));
     let end = Instant::now();
 
@@ -214,16 +306,16 @@ pub fn run_pir<ThePIR: PIR, I: Iterator<Item = usize>>(iter: I) -> RunResult {
     eprintln!("========");
 
     let mut all_trial_times = Vec::new();
+    let mut all_trial_noise = Vec::new();
 
     let mut run_trial = |indices: &[usize]| {
         eprintln!("Running trial on indices {:?}", &indices);
-        assert_eq!(indices.len(), ThePIR::BATCH_SIZE);
         let mut trial_times = Stats::new();
 
         let begin = Instant::now();
-        let (q, st) = ThePIR::query(&qk, indices, &db_hint, Some(&mut trial_times));
-        let response = ThePIR::answer(&pp, &db, &q, Some(&qk), Some(&mut trial_times));
-        let extracted = ThePIR::extract(&qk, &response, &st, Some(&mut trial_times));
+        let (q, st) = ThePIR::query(&qk, indices, &db_hint, Some(&mut trial_times)).unwrap();
+        let response = ThePIR::answer(&pp, &db, &q, Some(&mut trial_times)).unwrap();
+        let extracted = ThePIR::extract(&qk, &response, &st, Some(&mut trial_times)).unwrap();
         let end = Instant::now();
 
         trial_times.add(
@@ -238,9 +330,31 @@ pub fn run_pir<ThePIR: PIR, I: Iterator<Item = usize>>(iter: I) -> RunResult {
         for (stat, value) in trial_times.as_vec() {
             eprintln!("    {}: {:?}", stat, value);
         }
+        for (thread, thread_times) in trial_times.per_thread().iter().enumerate() {
+            eprintln!("    Thread {}:", thread);
+            for (stat, value) in thread_times.as_vec() {
+                eprintln!("        {}: {:?}", stat, value);
+            }
+        }
         eprintln!("Trial time (end-to-end): {:?}", end - begin);
         all_trial_times.push(trial_times);
 
+        if let Some(probe) = probe {
+            let measurements = probe(&qk, &pp, &db, &q, indices);
+            info!("Noise (log2 subgaussian width, max over ciphertexts):");
+            for phase in NoisePhase::ALL {
+                let max_bits = measurements
+                    .iter()
+                    .filter(|m| m.phase == phase)
+                    .map(|m| m.bits)
+                    .reduce(f64::max);
+                if let Some(bits) = max_bits {
+                    info!("    {}: {:.3}", phase.name(), bits);
+                }
+            }
+            all_trial_noise.push(measurements);
+        }
+
         for (idx, decoded_record) in indices.iter().copied().zip(extracted) {
             if decoded_record.as_bytes() != records_generator(idx).as_bytes() {
                 eprintln!("**** **** **** **** ERROR **** **** **** ****");
@@ -261,6 +375,7 @@ pub fn run_pir<ThePIR: PIR, I: Iterator<Item = usize>>(iter: I) -> RunResult {
     RunResult {
         init_times,
         all_trial_times,
+        all_trial_noise,
     }
 }
 
@@ -273,19 +388,68 @@ macro_rules! generate_main {
     };
 }
 
+/// Like [`generate_main!`], for a RESPIRE scheme, whose noise is also measured when info logging
+/// is enabled (see [`run_respire`](crate::pir::respire_harness::run_respire)).
+#[macro_export]
+macro_rules! generate_respire_main {
+    ($name: path) => {
+        fn main() {
+            $crate::pir::respire_harness::respire_harness_main::<$name>();
+        }
+    };
+}
+
 #[derive(Parser, Debug)]
 struct Args {
     trials: usize,
+    /// Load the encoded database from this file, or save it there if it does not exist yet
+    #[arg(long)]
+    db_file: Option<PathBuf>,
+    /// Encode the records of this file instead of synthetic ones: a directory with one file per
+    /// record, a `.csv` or `.jsonl` file of key→value lines, or a flat file of fixed-size records
+    #[arg(long)]
+    records: Option<PathBuf>,
+    /// Size of each record in a flat records file [default: the record size of the parameters]
+    #[arg(long)]
+    record_size: Option<usize>,
 }
 
 pub fn harness_main<ThePIR: PIR>() {
+    harness_main_probed::<ThePIR>(None);
+}
+
+pub fn respire_harness_main<ThePIR>()
+where
+    ThePIR: Respire + PIR<Query = Vec<<ThePIR as Respire>::QueryOne>>,
+{
+    harness_main_probed::<ThePIR>(Some(probe_respire_answer::<ThePIR>));
+}
+
+fn harness_main_probed<ThePIR: PIR>(probe: Option<AnswerProbe<ThePIR>>) {
     env_logger::init();
     let args = Args::parse();
 
+    let records: Box<dyn RecordSource> = match args.records {
+        Some(path) => {
+            open_record_source(&path, args.record_size.unwrap_or(ThePIR::BYTES_PER_RECORD))
+                .expect("failed to open records")
+        }
+        None => Box::new(SyntheticRecords {
+            num_records: ThePIR::NUM_RECORDS,
+            record_size: ThePIR::BYTES_PER_RECORD,
+        }),
+    };
+
+    // Only query records that are present in the source
+    let num_records = records.num_records().min(ThePIR::NUM_RECORDS);
     let mut rng = ChaCha20Rng::from_entropy();
-    let record_gen = |_| rng.gen_range(0_usize..ThePIR::NUM_RECORDS);
-    let run_result =
-        run_pir::<ThePIR, _>((0usize..args.trials * ThePIR::BATCH_SIZE).map(record_gen));
+    let record_gen = |_| rng.gen_range(0_usize..num_records);
+    let run_result = run_pir_probed::<ThePIR, _>(
+        (0usize..args.trials * ThePIR::BATCH_SIZE).map(record_gen),
+        records.as_ref(),
+        args.db_file.as_deref(),
+        probe,
+    );
 
     let trial_times = run_result
         .all_trial_times
@@ -340,13 +504,19 @@ mod test {
     use crate::math::int_mod_cyclo::IntModCyclo;
     use crate::math::int_mod_poly::IntModPoly;
     use crate::math::matrix::Matrix;
-    use crate::pir::respire::Respire;
+    use crate::math::rand_sampled::RandUniformSampled;
+    use crate::pir::cuckoo_respire::{
+        cuckoo_failure_bound, cuckoo_failure_rate_simulated, CuckooRespireImpl,
+    };
+    use crate::pir::database::SimdDatabase;
+    use crate::pir::pir::PirError;
 
     #[test]
     fn test_regev() {
-        let s = RespireTest::encode_setup();
+        let mut rng = ChaCha20Rng::from_entropy();
+        let s = RespireTest::encode_setup(&mut rng);
         let mu = <RespireTest as Respire>::RingP::from(12_u64);
-        let encoded = RespireTest::encode_rlwe(&s, &mu.scale_up_into());
+        let encoded = RespireTest::encode_rlwe(&s, &mu.scale_up_into(), &mut rng);
         let decoded: <RespireTest as Respire>::RingP =
             RespireTest::decode_rlwe(&s, &encoded).round_down_into();
         assert_eq!(mu, decoded);
@@ -354,10 +524,11 @@ mod test {
 
     #[test]
     fn test_gsw() {
-        let s = RespireTest::encode_setup();
+        let mut rng = ChaCha20Rng::from_entropy();
+        let s = RespireTest::encode_setup(&mut rng);
         type RingPP = IntModCyclo<{ RESPIRE_TEST_PARAMS.D1 }, 1024>;
         let mu = RingPP::from(111_u64);
-        let encrypt = RespireTest::encode_gsw(&s, &mu.include_into());
+        let encrypt = RespireTest::encode_gsw(&s, &mu.include_into(), &mut rng);
 
         let scale = <RespireTest as Respire>::RingQ1Fast::from(RESPIRE_TEST_PARAMS.Q1 / 1024);
         let decrypt = RespireTest::decode_gsw_scaled(&s, &encrypt, &scale);
@@ -366,13 +537,15 @@ mod test {
 
     #[test]
     fn test_auto_hom() {
-        let s = RespireTest::encode_setup();
+        let mut rng = ChaCha20Rng::from_entropy();
+        let s = RespireTest::encode_setup(&mut rng);
         let auto_key = RespireTest::auto_setup::<
             { RESPIRE_TEST_PARAMS.T_PROJ_SHORT },
             { RESPIRE_TEST_PARAMS.Z_PROJ_SHORT },
-        >(3, &s);
+            _,
+        >(3, &s, &mut rng);
         let x = <RespireTest as Respire>::RingP::from(IntModPoly::x());
-        let encrypt = RespireTest::encode_rlwe(&s, &x.scale_up_into());
+        let encrypt = RespireTest::encode_rlwe(&s, &x.scale_up_into(), &mut rng);
         let encrypt_auto = RespireTest::auto_hom::<
             { RESPIRE_TEST_PARAMS.T_PROJ_SHORT },
             { RESPIRE_TEST_PARAMS.Z_PROJ_SHORT },
@@ -384,14 +557,15 @@ mod test {
 
     #[test]
     fn test_regev_to_gsw() {
-        let s = RespireTest::encode_setup();
-        let s_regev_to_gsw = RespireTest::rlwe_to_gsw_setup(&s);
+        let mut rng = ChaCha20Rng::from_entropy();
+        let s = RespireTest::encode_setup(&mut rng);
+        let s_regev_to_gsw = RespireTest::rlwe_to_gsw_setup(&s, &mut rng);
         type RingPP = IntModCyclo<{ RESPIRE_TEST_PARAMS.D1 }, 1024>;
         let mu = RingPP::from(567_u64);
         let mut mu_curr = mu.include_into();
         let mut encrypt_vec = Vec::with_capacity(RESPIRE_TEST_PARAMS.T_GSW);
         for _ in 0..RESPIRE_TEST_PARAMS.T_GSW {
-            encrypt_vec.push(RespireTest::encode_rlwe(&s, &mu_curr));
+            encrypt_vec.push(RespireTest::encode_rlwe(&s, &mu_curr, &mut rng));
             mu_curr *= IntMod::from(RESPIRE_TEST_PARAMS.Z_GSW);
         }
         let encrypt_gsw = RespireTest::rlwe_to_gsw(&s_regev_to_gsw, encrypt_vec.as_slice());
@@ -403,9 +577,10 @@ mod test {
 
     #[test]
     fn test_scal_to_vec() {
-        let s_scal = RespireTest::encode_setup();
-        let s_vec = RespireTest::encode_vec_setup();
-        let s_scal_to_vec = RespireTest::vectorize_setup(&s_scal, &s_vec);
+        let mut rng = ChaCha20Rng::from_entropy();
+        let s_scal = RespireTest::encode_setup(&mut rng);
+        let s_vec = RespireTest::encode_vec_setup(&mut rng);
+        let s_scal_to_vec = RespireTest::vectorize_setup(&s_scal, &s_vec, &mut rng);
 
         let mut cs =
             Vec::<<RespireTest as Respire>::RLWEEncoding>::with_capacity(RESPIRE_TEST_PARAMS.N_VEC);
@@ -414,7 +589,11 @@ mod test {
         for i in 0..RESPIRE_TEST_PARAMS.N_VEC {
             let mu = <RespireTest as Respire>::RingP::from(i as u64 + 1_u64);
             expected[(i, 0)] = mu.clone();
-            cs.push(RespireTest::encode_rlwe(&s_scal, &mu.scale_up_into()));
+            cs.push(RespireTest::encode_rlwe(
+                &s_scal,
+                &mu.scale_up_into(),
+                &mut rng,
+            ));
         }
 
         let c_vec = RespireTest::scal_to_vec(&s_scal_to_vec, cs.as_slice().try_into().unwrap());
@@ -426,13 +605,16 @@ mod test {
     #[test]
     fn test_post_process_only() {
         let (qk, pp) = RespireTest::setup(None);
-        let (_, s_vec, _) = &qk;
+        let s_vec = &qk.s_vec;
         let mut m = <RespireTest as Respire>::RecordPackedSmall::zero();
         for i in 0..RESPIRE_TEST_PARAMS.N_VEC {
             m[(i, 0)] = IntModCyclo::from(177_u64 + i as u64)
         }
-        let c =
-            RespireTest::encode_vec_rlwe(s_vec, &m.map_ring(|r| r.include_dim().scale_up_into()));
+        let c = RespireTest::encode_vec_rlwe(
+            s_vec,
+            &m.map_ring(|r| r.include_dim().scale_up_into()),
+            &mut ChaCha20Rng::from_entropy(),
+        );
         let compressed = RespireTest::answer_compress_vec(&pp, &c, RESPIRE_TEST_PARAMS.N_VEC);
         let extracted = RespireTest::extract_ring_one(&qk, &compressed);
         assert_eq!(m, extracted);
@@ -455,8 +637,393 @@ mod test {
     //     assert_eq!(bytes, decoded);
     // }
 
+    #[test]
+    fn test_invalid_inputs() {
+        let (qk, pp) = RespireTest::setup(None);
+        assert_eq!(
+            RespireTest::query(&qk, &[RespireTest::DB_SIZE], &(), None).err(),
+            Some(PirError::IndexOutOfRange {
+                idx: RespireTest::DB_SIZE,
+                num_records: RespireTest::DB_SIZE
+            })
+        );
+        assert_eq!(
+            RespireTest::query(&qk, &[0, 1], &(), None).err(),
+            Some(PirError::BatchSizeMismatch {
+                expected: 1,
+                found: 2
+            })
+        );
+
+        let db = SimdDatabase::from(Vec::new());
+        let (q, _) = RespireTest::query(&qk, &[0], &(), None).unwrap();
+        let mut truncated = q.clone();
+        truncated[0].1 .1.pop();
+        assert_eq!(
+            RespireTest::answer(&pp, &db, &truncated, None).err(),
+            Some(PirError::MalformedQuery)
+        );
+        assert_eq!(
+            RespireTest::answer(&pp, &db, &Vec::new(), None).err(),
+            Some(PirError::BatchSizeMismatch {
+                expected: 1,
+                found: 0
+            })
+        );
+        assert_eq!(
+            RespireTest::extract(&qk, &Vec::new(), &1, None).err(),
+            Some(PirError::BatchSizeMismatch {
+                expected: 1,
+                found: 0
+            })
+        );
+    }
+
+    type CuckooSmall = CuckooRespireImpl<3, 4, { RespireSmall::DB_SIZE / 2 }, RespireSmall>;
+
+    ///
+    /// A record of `ThePIR` that starts with `idx` (as 8 little endian bytes) and ends with
+    /// `version`.
+    ///
+    fn indexed_record<ThePIR: PIR>(idx: usize, version: u8) -> ThePIR::RecordBytes {
+        let mut bytes = vec![0_u8; ThePIR::BYTES_PER_RECORD];
+        bytes[..8].copy_from_slice(&(idx as u64).to_le_bytes());
+        bytes[ThePIR::BYTES_PER_RECORD - 1] = version;
+        ThePIR::RecordBytes::from_bytes(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_first_dim_parallel() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let (db, ()) = RespireSmall::encode_db(|idx| indexed_record::<RespireSmall>(idx, 0), None);
+        let rlwes = (0..RespireSmall::PACKED_DIM1_SIZE)
+            .map(|_| Matrix::rand_uniform(&mut rng))
+            .collect_vec();
+        assert_eq!(
+            RespireSmall::answer_first_dim(&db, &rlwes),
+            RespireSmall::answer_first_dim_sequential(&db, &rlwes)
+        );
+    }
+
+    #[test]
+    fn test_update_records() {
+        let record = indexed_record::<RespireSmall>;
+        let last = RespireSmall::DB_SIZE - 1;
+        let updates = [(0, 1), (1, 1), (last, 1), (1, 2)];
+
+        let (mut db, ()) = RespireSmall::encode_db(|idx| record(idx, 0), None);
+        let updates_bytes = updates
+            .iter()
+            .map(|&(idx, version)| (idx, record(idx, version)))
+            .collect_vec();
+        RespireSmall::update_records(&mut db, &(), &updates_bytes, None).unwrap();
+
+        let version = |idx: usize| {
+            updates
+                .iter()
+                .rev()
+                .find(|(i, _)| *i == idx)
+                .map_or(0, |(_, v)| *v)
+        };
+        let (expected, ()) = RespireSmall::encode_db(|idx| record(idx, version(idx)), None);
+        assert!(db[..] == expected[..]);
+
+        assert_eq!(
+            RespireSmall::update_records(
+                &mut db,
+                &(),
+                &[(RespireSmall::DB_SIZE, record(0, 0))],
+                None
+            )
+            .err(),
+            Some(PirError::IndexOutOfRange {
+                idx: RespireSmall::DB_SIZE,
+                num_records: RespireSmall::DB_SIZE
+            })
+        );
+    }
+
+    #[test]
+    fn test_update_records_cuckoo() {
+        let record = indexed_record::<CuckooSmall>;
+        let updated = [3, CuckooSmall::NUM_RECORDS - 1];
+
+        let (mut dbs, hint) = CuckooSmall::encode_db(|idx| record(idx, 0), None);
+        assert_eq!(hint, CuckooSmall::hint_from_seed(hint.seed));
+        let updates = updated
+            .iter()
+            .map(|&idx| (idx, record(idx, 1)))
+            .collect_vec();
+        CuckooSmall::update_records(&mut dbs, &hint, &updates, None).unwrap();
+
+        let (expected, _) = CuckooSmall::encode_db_with_seed(
+            hint.seed,
+            |idx| record(idx, updated.contains(&idx) as u8),
+            None,
+        );
+        for (db, expected) in dbs.iter().zip(expected.iter()) {
+            assert!(db[..] == expected[..]);
+        }
+
+        let path = temp_path("cuckoo");
+        CuckooSmall::save_db(&dbs, &hint, &path).unwrap();
+        let (loaded, loaded_hint) = CuckooSmall::load_db(&path).unwrap();
+        assert_eq!(loaded_hint, hint);
+        assert!(loaded[0][..] == dbs[0][..]);
+        drop(loaded);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_partial_batches() {
+        // 4 records per response ring element, 2 ring elements per response vector
+        const BATCH_PARAMS: RespireParamsExpanded =
+            FactoryParams::batch_256(10, 2, 2, 2).expand().expand();
+        type RespireBatch = respire!(BATCH_PARAMS);
+        let record = |idx| indexed_record::<RespireBatch>(idx, 0);
+        let (db, ()) = RespireBatch::encode_db(record, None);
+        let (qk, pp) = RespireBatch::setup(None);
+
+        // (records requested, queries sent, response ring elements)
+        for (count, padded, ring_elems) in [(1, 4, vec![1]), (5, 8, vec![2]), (9, 10, vec![2, 1])] {
+            let idxs = (0..count).map(|i| 3 * i + 1).collect_vec();
+            let (q, state) = RespireBatch::query(&qk, &idxs, &(), None).unwrap();
+            assert_eq!(q.len(), padded);
+            let r = RespireBatch::answer(&pp, &db, &q, None).unwrap();
+            assert_eq!(
+                r.iter().map(RespireBatch::response_one_len).collect_vec(),
+                ring_elems
+            );
+            let extracted = RespireBatch::extract(&qk, &r, &state, None).unwrap();
+            assert_eq!(
+                extracted.iter().map(|r| r.as_bytes()).collect_vec(),
+                idxs.iter()
+                    .map(|&i| record(i).as_bytes().to_vec())
+                    .collect_vec()
+            );
+        }
+        assert_eq!(
+            RespireBatch::query(&qk, &[0; 11], &(), None).err(),
+            Some(PirError::BatchSizeMismatch {
+                expected: 10,
+                found: 11
+            })
+        );
+
+        let (dbs, hint) = CuckooSmall::encode_db(|idx| indexed_record::<CuckooSmall>(idx, 0), None);
+        let (qk, pp) = CuckooSmall::setup(None);
+        let (q, state) = CuckooSmall::query(&qk, &[7], &hint, None).unwrap();
+        let r = CuckooSmall::answer(&pp, &dbs, &q, None).unwrap();
+        let extracted = CuckooSmall::extract(&qk, &r, &state, None).unwrap();
+        assert_eq!(extracted.len(), 1);
+        assert_eq!(extracted[0].as_bytes()[0], 7);
+    }
+
+    #[test]
+    fn test_cuckoo_duplicate_indices() {
+        let record = |idx| indexed_record::<CuckooSmall>(idx, 0);
+        let (dbs, hint) = CuckooSmall::encode_db(record, None);
+        let (qk, pp) = CuckooSmall::setup(None);
+        for idxs in [[5, 5, 5], [9, 2, 9]] {
+            let (q, state) = CuckooSmall::query(&qk, &idxs, &hint, None).unwrap();
+            let r = CuckooSmall::answer(&pp, &dbs, &q, None).unwrap();
+            let extracted = CuckooSmall::extract(&qk, &r, &state, None).unwrap();
+            assert_eq!(
+                extracted.iter().map(|r| r.as_bytes()).collect_vec(),
+                idxs.iter()
+                    .map(|&i| record(i).as_bytes().to_vec())
+                    .collect_vec()
+            );
+        }
+
+        // Three distinct records never fit in two buckets
+        type CuckooTight = CuckooRespireImpl<3, 2, 100, RespireSmall>;
+        let hint = CuckooTight::hint_from_seed([0; 16]);
+        assert_eq!(
+            CuckooTight::query(&qk, &[0, 1, 1], &hint, None).map(|_| ()),
+            Ok(())
+        );
+        assert_eq!(
+            CuckooTight::query(&qk, &[0, 1, 2], &hint, None).err(),
+            Some(PirError::CuckooInsertionFailed)
+        );
+    }
+
+    #[test]
+    fn test_cuckoo_stash() {
+        type CuckooStash =
+            CuckooRespireImpl<3, 2, { RespireSmall::DB_SIZE / 4 }, RespireSmall, 3, 1>;
+        let record = |idx| indexed_record::<CuckooStash>(idx, 0);
+        let seed = [0; 16];
+        let (dbs, hint) = CuckooStash::encode_db_with_seed(seed, record, None);
+        let (qk, pp) = CuckooStash::setup(None);
+
+        // Three records never fit in two buckets, so one of them is always stashed. Records that
+        // hash to both buckets never leave more than one over.
+        let idxs = (0..CuckooStash::NUM_RECORDS)
+            .filter(|&i| {
+                CuckooStash::idx_to_buckets(&seed, i)
+                    .iter()
+                    .unique()
+                    .count()
+                    == 2
+            })
+            .take(3)
+            .collect_vec();
+        let (q, state) = CuckooStash::query(&qk, &idxs, &hint, None).unwrap();
+        assert_eq!(q.len(), 4);
+        let r = CuckooStash::answer(&pp, &dbs, &q, None).unwrap();
+        let extracted = CuckooStash::extract(&qk, &r, &state, None).unwrap();
+        assert_eq!(
+            extracted.iter().map(|r| r.as_bytes()).collect_vec(),
+            idxs.iter()
+                .map(|&i| record(i).as_bytes().to_vec())
+                .collect_vec()
+        );
+        // Fails only if all nine hashes of the batch agree
+        assert!((CuckooStash::params_cuckoo_failure_bound() - 2_f64 / 512_f64).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_cuckoo_failure_bound() {
+        // The shape of `1g256_4`
+        let bound = cuckoo_failure_bound(4, 7, 3, 0);
+        assert!(bound > 1e-3 && bound < 1e-2, "{}", bound);
+        let simulated = cuckoo_failure_rate_simulated::<3>(4, 7, 0, 20_000);
+        assert!(simulated <= 1.5 * bound, "{} > {}", simulated, bound);
+        assert!(cuckoo_failure_bound(4, 7, 3, 1) < bound / 100_f64);
+        assert_eq!(cuckoo_failure_bound(3, 2, 3, 0), 1_f64);
+        assert_eq!(cuckoo_failure_bound(2, 2, 3, 1), 0_f64);
+        assert_eq!(cuckoo_failure_rate_simulated::<3>(2, 2, 1, 100), 0_f64);
+    }
+
+    #[test]
+    fn test_cuckoo_hash_stable() {
+        type Cuckoo2 = CuckooRespireImpl<2, 1000, 100, RespireSmall, 2>;
+        type Cuckoo5 = CuckooRespireImpl<2, 1000, 100, RespireSmall, 5>;
+        let seed: [u8; 16] = std::array::from_fn(|i| i as u8);
+        // The layout must not change across builds, since clients and servers may be built
+        // separately
+        assert_eq!(Cuckoo2::idx_to_buckets(&seed, 42), [948, 9]);
+        assert_eq!(
+            Cuckoo5::idx_to_buckets(&seed, 42)[..2],
+            Cuckoo2::idx_to_buckets(&seed, 42)
+        );
+        assert_ne!(
+            Cuckoo5::idx_to_buckets(&seed, 42),
+            Cuckoo5::idx_to_buckets(&[0; 16], 42)
+        );
+    }
+
+    #[test]
+    fn test_cuckoo_bucket_positions() {
+        type Cuckoo = CuckooRespireImpl<2, 7, 100, RespireSmall>;
+        let seed: [u8; 16] = std::array::from_fn(|i| 3 * i as u8);
+        let mut layouts = vec![Vec::new(); 7];
+        for i in 0..100 {
+            for b in Cuckoo::idx_to_buckets(&seed, i) {
+                layouts[b].push(i);
+            }
+        }
+        let hints = [
+            Cuckoo::hint_from_seed(seed),
+            Cuckoo::hint_from_seed_with_checkpoints(seed, 0),
+            Cuckoo::hint_from_seed_with_checkpoints(seed, 3),
+            Cuckoo::hint_from_seed_with_checkpoints(seed, 7),
+        ];
+        for hint in &hints {
+            for (b, layout) in layouts.iter().enumerate() {
+                for i in 0..100 {
+                    let expected = layout.iter().positions(|&j| j == i).collect_vec();
+                    assert_eq!(Cuckoo::bucket_positions(hint, i, b).collect_vec(), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_setup_from_seed() {
+        let (qk, pp) = RespireSmall::setup_from_seed([7; 32], None);
+        let (qk_again, pp_again) = RespireSmall::setup_from_seed([7; 32], None);
+        assert_eq!(qk, qk_again);
+        assert_eq!(pp, pp_again);
+        let (qk_other, _) = RespireSmall::setup_from_seed([8; 32], None);
+        assert_ne!(qk, qk_other);
+
+        let query = |seed| {
+            RespireSmall::query_one_with_rng(&qk, 3, &mut ChaCha20Rng::from_seed(seed), None)
+                .unwrap()
+        };
+        assert_eq!(query([1; 32]), query([1; 32]));
+        assert_ne!(query([1; 32]), query([2; 32]));
+
+        let batch_query = |seed| {
+            RespireSmall::query_with_rng(&qk, &[3], &mut ChaCha20Rng::from_seed(seed), None)
+                .unwrap()
+                .0
+        };
+        assert_eq!(batch_query([1; 32]), batch_query([1; 32]));
+        assert_ne!(batch_query([1; 32]), batch_query([2; 32]));
+
+        let cuckoo_hint = CuckooSmall::hint_from_seed([0; 16]);
+        let cuckoo_query = |seed| {
+            let (q, _) = CuckooSmall::query_with_rng(
+                &qk,
+                &[1, 2],
+                &cuckoo_hint,
+                &mut ChaCha20Rng::from_seed(seed),
+                None,
+            )
+            .unwrap();
+            q
+        };
+        assert_eq!(cuckoo_query([1; 32]), cuckoo_query([1; 32]));
+        assert_ne!(cuckoo_query([1; 32]), cuckoo_query([2; 32]));
+
+        // Keys from a seed work like any others
+        let records = (0..RespireSmall::NUM_RECORDS)
+            .map(|i| {
+                let bytes = (0..RespireSmall::BYTES_PER_RECORD)
+                    .map(|j| (i * 7 + j) as u8)
+                    .collect_vec();
+                <RespireSmall as PIR>::RecordBytes::from_bytes(&bytes).unwrap()
+            })
+            .collect_vec();
+        let (db, hint) = RespireSmall::encode_db(|i| records[i].clone(), None);
+        let idxs = vec![5; RespireSmall::BATCH_SIZE];
+        let (q, state) = RespireSmall::query(&qk, &idxs, &hint, None).unwrap();
+        let r = RespireSmall::answer(&pp, &db, &q, None).unwrap();
+        let extracted = RespireSmall::extract(&qk, &r, &state, None).unwrap();
+        assert!(extracted
+            .iter()
+            .all(|r| r.as_bytes() == records[5].as_bytes()));
+    }
+
     #[test]
     fn test_respire_one() {
-        run_pir::<RespireTest, _>([711_711].into_iter());
+        let records = SyntheticRecords {
+            num_records: RespireTest::NUM_RECORDS,
+            record_size: RespireTest::BYTES_PER_RECORD,
+        };
+        run_pir::<RespireTest, _>([711_711].into_iter(), &records, None);
+    }
+
+    #[test]
+    fn test_respire_noise_probed() {
+        let _ = env_logger::builder()
+            .is_test(true)
+            .filter_level(log::LevelFilter::Info)
+            .try_init();
+        let records = SyntheticRecords {
+            num_records: RespireSmall::NUM_RECORDS,
+            record_size: RespireSmall::BYTES_PER_RECORD,
+        };
+        let result = run_respire::<RespireSmall, _>([3, 5].into_iter(), &records, None);
+        assert_eq!(result.all_trial_noise.len(), result.all_trial_times.len());
+        for measurements in result.all_trial_noise.iter() {
+            for phase in NoisePhase::ALL {
+                assert!(measurements.iter().any(|m| m.phase == phase), "{:?}", phase);
+            }
+        }
     }
 }
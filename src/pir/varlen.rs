@@ -0,0 +1,274 @@
+//! Variable-length records on top of any fixed record size PIR.
+//!
+//! Each record is stored as a little endian `u32` length prefix followed by its bytes. These
+//! entries are packed back to back into the fixed-size slots of the underlying database: an entry
+//! that fits in the rest of the current slot goes there, so many tiny records share a slot, and
+//! any other entry starts at the next slot boundary and spans as many consecutive slots as it
+//! needs. The database hint is the offset directory, giving the position and length of every
+//! record, so the client knows which slots to request.
+//!
+//! A batch of records is retrieved with a single query of the underlying PIR, padded with other
+//! slots to its batch size, so all slots of a batch must fit in one query. This bounds the length
+//! of a record by [`VarLenPir::MAX_RECORD_BYTES`].
+
+use crate::pir::pir::{PIRRecordBytes, PirError, Stats, PIR};
+use itertools::Itertools;
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+/// Number of bytes of the length prefix of each record.
+pub const LENGTH_PREFIX_BYTES: usize = 4;
+
+///
+/// Offset directory of a variable-length database, together with the hint of the underlying one.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VarLenDatabaseHint<H> {
+    pub inner: H,
+    /// (byte offset of the entry, record length) of each record. Offsets count from the start of
+    /// slot 0, so record `i` starts in slot `offset / BYTES_PER_RECORD`.
+    pub records: Vec<(u64, u32)>,
+}
+
+///
+/// Client state between `query` and `extract`.
+///
+pub struct VarLenQueryState<S> {
+    inner: S,
+    /// (position of the first slot in the batch, offset in that slot, length) of each record
+    records: Vec<(usize, usize, usize)>,
+}
+
+pub struct VarLenPir<P: PIR> {
+    phantom: PhantomData<P>,
+}
+
+impl<P: PIR> VarLenPir<P> {
+    /// Length of the longest record that can be stored.
+    pub const MAX_RECORD_BYTES: usize = P::BATCH_SIZE * P::BYTES_PER_RECORD - LENGTH_PREFIX_BYTES;
+
+    ///
+    /// Encodes the given records, each of at most [`Self::MAX_RECORD_BYTES`] bytes.
+    ///
+    pub fn encode_db<V: AsRef<[u8]>>(
+        records: &[V],
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<(P::Database, VarLenDatabaseHint<P::DatabaseHint>), PirError> {
+        let begin = Instant::now();
+        let slot_size = P::BYTES_PER_RECORD;
+        let mut stream = Vec::new();
+        let mut directory = Vec::with_capacity(records.len());
+        for record in records {
+            let record = record.as_ref();
+            if record.len() > Self::MAX_RECORD_BYTES {
+                return Err(PirError::RecordTooLarge {
+                    len: record.len(),
+                    max: Self::MAX_RECORD_BYTES,
+                });
+            }
+            let entry_len = LENGTH_PREFIX_BYTES + record.len();
+            let used = stream.len() % slot_size;
+            if used != 0 && used + entry_len > slot_size {
+                stream.resize(stream.len() + slot_size - used, 0);
+            }
+            directory.push((stream.len() as u64, record.len() as u32));
+            stream.extend_from_slice(&(record.len() as u32).to_le_bytes());
+            stream.extend_from_slice(record);
+        }
+
+        let num_slots = stream.len().div_ceil(slot_size);
+        if num_slots > P::NUM_RECORDS {
+            return Err(PirError::DatabaseFull {
+                slots: num_slots,
+                capacity: P::NUM_RECORDS,
+            });
+        }
+        stream.resize(num_slots * slot_size, 0);
+        let empty_slot = vec![0_u8; slot_size];
+        let slot_generator = |i: usize| {
+            let slot = stream
+                .get(i * slot_size..(i + 1) * slot_size)
+                .unwrap_or(&empty_slot);
+            P::RecordBytes::from_bytes(slot).unwrap()
+        };
+        let (db, inner) = P::encode_db(slot_generator, None);
+
+        let end = Instant::now();
+        if let Some(time_stats) = time_stats {
+            time_stats.add("encode", end - begin);
+        }
+        Ok((
+            db,
+            VarLenDatabaseHint {
+                inner,
+                records: directory,
+            },
+        ))
+    }
+
+    ///
+    /// Queries the records `idxs`. Their slots, counting slots shared between them once, must fit
+    /// in a batch of the underlying PIR.
+    ///
+    pub fn query(
+        qk: &P::QueryKey,
+        idxs: &[usize],
+        hint: &VarLenDatabaseHint<P::DatabaseHint>,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<(P::Query, VarLenQueryState<P::State>), PirError> {
+        let slot_size = P::BYTES_PER_RECORD;
+        let mut locations = Vec::with_capacity(idxs.len());
+        for idx in idxs.iter().copied() {
+            let (offset, len) =
+                hint.records
+                    .get(idx)
+                    .copied()
+                    .ok_or(PirError::IndexOutOfRange {
+                        idx,
+                        num_records: hint.records.len(),
+                    })?;
+            let offset = offset as usize;
+            let len = len as usize;
+            let first_slot = offset / slot_size;
+            let end_slot = (offset + LENGTH_PREFIX_BYTES + len).div_ceil(slot_size);
+            if end_slot > P::NUM_RECORDS {
+                return Err(PirError::InvalidDatabaseHint);
+            }
+            locations.push((first_slot, end_slot, offset % slot_size, len));
+        }
+
+        let slots = locations
+            .iter()
+            .flat_map(|&(first_slot, end_slot, _, _)| first_slot..end_slot)
+            .sorted_unstable()
+            .dedup()
+            .collect_vec();
+        if slots.len() > P::BATCH_SIZE {
+            return Err(PirError::BatchSizeMismatch {
+                expected: P::BATCH_SIZE,
+                found: slots.len(),
+            });
+        }
+        // Pad with distinct unused slots, so the padding looks like any other batch
+        let padding = (0..P::NUM_RECORDS)
+            .filter(|slot| slots.binary_search(slot).is_err())
+            .take(P::BATCH_SIZE - slots.len())
+            .collect_vec();
+        let records = locations
+            .into_iter()
+            .map(|(first_slot, _, offset, len)| {
+                (slots.binary_search(&first_slot).unwrap(), offset, len)
+            })
+            .collect_vec();
+
+        let batch = slots.into_iter().chain(padding).collect_vec();
+        let (q, inner) = P::query(qk, &batch, &hint.inner, time_stats)?;
+        Ok((q, VarLenQueryState { inner, records }))
+    }
+
+    pub fn answer(
+        pp: &P::PublicParams,
+        db: &P::Database,
+        q: &P::Query,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<P::Response, PirError> {
+        P::answer(pp, db, q, time_stats)
+    }
+
+    ///
+    /// Reassembles the queried records from their slots.
+    ///
+    pub fn extract(
+        qk: &P::QueryKey,
+        r: &P::Response,
+        state: &VarLenQueryState<P::State>,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<Vec<Vec<u8>>, PirError> {
+        let slots = P::extract(qk, r, &state.inner, time_stats)?;
+        let slot_size = P::BYTES_PER_RECORD;
+        state
+            .records
+            .iter()
+            .map(|&(position, offset, len)| {
+                let num_slots = (offset + LENGTH_PREFIX_BYTES + len).div_ceil(slot_size);
+                let bytes = slots
+                    .get(position..position + num_slots)
+                    .ok_or(PirError::MalformedResponse)?
+                    .iter()
+                    .flat_map(|slot| slot.as_bytes().iter().copied())
+                    .skip(offset)
+                    .take(LENGTH_PREFIX_BYTES + len)
+                    .collect_vec();
+                let (prefix, record) = bytes.split_at(LENGTH_PREFIX_BYTES);
+                // A mismatch means the slots did not decrypt to the directory's record
+                if u32::from_le_bytes(prefix.try_into().unwrap()) as usize != len {
+                    return Err(PirError::MalformedResponse);
+                }
+                Ok(record.to_vec())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pir::respire_harness::RespireSmallBatch;
+
+    type VarLenTest = VarLenPir<RespireSmallBatch>;
+
+    #[test]
+    fn test_varlen_records() {
+        let records = [
+            vec![1_u8; 20],
+            vec![2_u8; 30],
+            vec![],
+            vec![3_u8; VarLenTest::MAX_RECORD_BYTES],
+            vec![4_u8; 300],
+            vec![5_u8; 20],
+        ];
+        let (db, hint) = VarLenTest::encode_db(&records, None).unwrap();
+        // The tiny records share slot 0, and the largest record fills a whole batch
+        assert_eq!(hint.records[..3], [(0, 20), (24, 30), (58, 0)]);
+        assert_eq!(
+            hint.records[3].0,
+            RespireSmallBatch::BYTES_PER_RECORD as u64
+        );
+
+        let (qk, pp) = RespireSmallBatch::setup(None);
+        for idxs in [vec![3], vec![0, 1, 2, 4, 5], vec![4, 4]] {
+            let (q, state) = VarLenTest::query(&qk, &idxs, &hint, None).unwrap();
+            let r = VarLenTest::answer(&pp, &db, &q, None).unwrap();
+            let extracted = VarLenTest::extract(&qk, &r, &state, None).unwrap();
+            assert_eq!(
+                extracted,
+                idxs.iter().map(|&i| records[i].clone()).collect_vec()
+            );
+        }
+        assert!(matches!(
+            VarLenTest::query(&qk, &[3, 0], &hint, None),
+            Err(PirError::BatchSizeMismatch { .. })
+        ));
+        assert!(matches!(
+            VarLenTest::query(&qk, &[6], &hint, None),
+            Err(PirError::IndexOutOfRange { idx: 6, .. })
+        ));
+    }
+
+    #[test]
+    fn test_varlen_invalid_records() {
+        assert_eq!(
+            VarLenTest::encode_db(&[vec![0_u8; VarLenTest::MAX_RECORD_BYTES + 1]], None).err(),
+            Some(PirError::RecordTooLarge {
+                len: VarLenTest::MAX_RECORD_BYTES + 1,
+                max: VarLenTest::MAX_RECORD_BYTES
+            })
+        );
+        let too_many =
+            vec![vec![0_u8; RespireSmallBatch::BYTES_PER_RECORD]; RespireSmallBatch::NUM_RECORDS];
+        assert!(matches!(
+            VarLenTest::encode_db(&too_many, None),
+            Err(PirError::DatabaseFull { .. })
+        ));
+    }
+}
@@ -0,0 +1,488 @@
+//! Binary wire format for RESPIRE queries, responses and public params.
+//!
+//! Every message starts with a fixed [`HEADER_SIZE`] byte header:
+//!
+//! | bytes  | field                                                        |
+//! |--------|--------------------------------------------------------------|
+//! | 0      | format version ([`WIRE_VERSION`])                            |
+//! | 1      | message kind ([`MessageKind`])                               |
+//! | 2..10  | parameter fingerprint ([`RespireParamsExpanded::fingerprint`]), little endian |
+//! | 10..14 | number of elements, little endian                            |
+//!
+//! The body is a sequence of elements (one per `QueryOne` resp. `AnswerOneCompressed`). Within an
+//! element, every coefficient of `Z_Q` is packed into exactly `ceil(log2(Q))` bits (MSB first), and
+//! the element is padded to a byte boundary. Hence a single query element has exactly
+//! `params_query_one_size()` bytes, and a single response element has exactly
+//! `params_response_one_size(len)` bytes, preceded by one byte storing `len`. Seeded public params
+//! are a single element of exactly `params_public_param_size()` bytes.
+//!
+//! A query key (which never leaves the client, but may be stored) is a single element of exactly
+//! `params_query_key_size()` bytes. Its coefficients are small, so each is stored as its offset
+//! from the lower end of its range, in just enough bits for that range (see
+//! [`pack_small_coeffs`]). Encoded keys are returned in a [`Zeroizing`] buffer.
+//!
+//! [`RespireParamsExpanded::fingerprint`]: crate::pir::respire::RespireParamsExpanded::fingerprint
+
+use crate::math::int_mod::IntMod;
+use crate::math::utils::ceil_log;
+use crate::pir::respire::Respire;
+use bitvec::prelude::*;
+use std::fmt;
+use zeroize::Zeroizing;
+
+pub const WIRE_VERSION: u8 = 1;
+pub const HEADER_SIZE: usize = 14;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum MessageKind {
+    Query = 1,
+    Response = 2,
+    PublicParams = 3,
+    QueryKey = 4,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum WireError {
+    /// The message ended before all expected fields were read.
+    Truncated,
+    /// The message has more bytes than its header declares.
+    TrailingBytes,
+    UnsupportedVersion(u8),
+    UnexpectedKind(u8),
+    /// The message was produced for a different parameter set.
+    ParamsMismatch {
+        expected: u64,
+        found: u64,
+    },
+    /// A packed coefficient is not reduced modulo its modulus.
+    CoefficientOutOfRange,
+    /// A length field is not valid for the parameter set.
+    InvalidLength(usize),
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireError::Truncated => write!(f, "message is truncated"),
+            WireError::TrailingBytes => write!(f, "message has trailing bytes"),
+            WireError::UnsupportedVersion(v) => write!(f, "unsupported wire version {}", v),
+            WireError::UnexpectedKind(k) => write!(f, "unexpected message kind {}", k),
+            WireError::ParamsMismatch { expected, found } => write!(
+                f,
+                "params fingerprint mismatch (expected {:016x}, found {:016x})",
+                expected, found
+            ),
+            WireError::CoefficientOutOfRange => write!(f, "coefficient out of range"),
+            WireError::InvalidLength(len) => write!(f, "invalid length {}", len),
+        }
+    }
+}
+
+impl std::error::Error for WireError {}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Header {
+    pub version: u8,
+    pub kind: MessageKind,
+    pub fingerprint: u64,
+    pub count: u32,
+}
+
+impl Header {
+    pub fn new(kind: MessageKind, fingerprint: u64, count: usize) -> Self {
+        Self {
+            version: WIRE_VERSION,
+            kind,
+            fingerprint,
+            count: count as u32,
+        }
+    }
+
+    pub fn write_to(&self, out: &mut Vec<u8>) {
+        out.push(self.version);
+        out.push(self.kind as u8);
+        out.extend_from_slice(&self.fingerprint.to_le_bytes());
+        out.extend_from_slice(&self.count.to_le_bytes());
+    }
+
+    pub fn read_from(bytes: &[u8]) -> Result<Self, WireError> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(WireError::Truncated);
+        }
+        let version = bytes[0];
+        if version != WIRE_VERSION {
+            return Err(WireError::UnsupportedVersion(version));
+        }
+        let kind = match bytes[1] {
+            1 => MessageKind::Query,
+            2 => MessageKind::Response,
+            3 => MessageKind::PublicParams,
+            4 => MessageKind::QueryKey,
+            other => return Err(WireError::UnexpectedKind(other)),
+        };
+        let fingerprint = u64::from_le_bytes(bytes[2..10].try_into().unwrap());
+        let count = u32::from_le_bytes(bytes[10..14].try_into().unwrap());
+        Ok(Self {
+            version,
+            kind,
+            fingerprint,
+            count,
+        })
+    }
+
+    /// Checks that the header describes a message of the given kind for the given parameters.
+    pub fn expect(&self, kind: MessageKind, fingerprint: u64) -> Result<(), WireError> {
+        if self.kind != kind {
+            return Err(WireError::UnexpectedKind(self.kind as u8));
+        }
+        if self.fingerprint != fingerprint {
+            return Err(WireError::ParamsMismatch {
+                expected: fingerprint,
+                found: self.fingerprint,
+            });
+        }
+        Ok(())
+    }
+}
+
+///
+/// Appends each coefficient to `bits` using exactly `ceil(log2(N))` bits, MSB first.
+///
+pub fn pack_coeffs<const N: u64, I: IntoIterator<Item = IntMod<N>>>(
+    bits: &mut BitVec<u8, Msb0>,
+    coeffs: I,
+) {
+    let width = ceil_log(2, N);
+    for coeff in coeffs {
+        bits.extend_from_bitslice(&u64::from(coeff).view_bits::<Msb0>()[64 - width..]);
+    }
+}
+
+///
+/// Number of bits [`pack_small_coeffs`] uses per coefficient of absolute value at most `bound`.
+///
+pub fn small_coeff_width(bound: u64) -> usize {
+    ceil_log(2, 2 * bound + 1)
+}
+
+///
+/// Appends each coefficient, which must lie in `[-bound, bound]` (as a centered representative),
+/// as `coeff + bound` using exactly [`small_coeff_width`] bits, MSB first. Used for secret keys,
+/// whose coefficients are much smaller than their modulus.
+///
+pub fn pack_small_coeffs<const N: u64, I: IntoIterator<Item = IntMod<N>>>(
+    bits: &mut BitVec<u8, Msb0>,
+    coeffs: I,
+    bound: u64,
+) {
+    let width = small_coeff_width(bound);
+    for coeff in coeffs {
+        let centered = i64::from(coeff);
+        assert!(centered.unsigned_abs() <= bound);
+        let offset = (centered + bound as i64) as u64;
+        bits.extend_from_bitslice(&offset.view_bits::<Msb0>()[64 - width..]);
+    }
+}
+
+/// Sequential reader over bit-packed coefficients, the inverse of [`pack_coeffs`].
+pub struct BitCursor<'a> {
+    bits: &'a BitSlice<u8, Msb0>,
+    pos: usize,
+}
+
+impl<'a> BitCursor<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bits: bytes.view_bits::<Msb0>(),
+            pos: 0,
+        }
+    }
+
+    pub fn read_coeffs<const N: u64>(&mut self, out: &mut [IntMod<N>]) -> Result<(), WireError> {
+        let width = ceil_log(2, N);
+        if self.pos + width * out.len() > self.bits.len() {
+            return Err(WireError::Truncated);
+        }
+        for coeff in out.iter_mut() {
+            let value: u64 = self.bits[self.pos..self.pos + width].load_be();
+            if value >= N {
+                return Err(WireError::CoefficientOutOfRange);
+            }
+            *coeff = IntMod::from(value);
+            self.pos += width;
+        }
+        Ok(())
+    }
+
+    /// Reads coefficients written by [`pack_small_coeffs`] with the same `bound`.
+    pub fn read_small_coeffs<const N: u64>(
+        &mut self,
+        out: &mut [IntMod<N>],
+        bound: u64,
+    ) -> Result<(), WireError> {
+        let width = small_coeff_width(bound);
+        if self.pos + width * out.len() > self.bits.len() {
+            return Err(WireError::Truncated);
+        }
+        for coeff in out.iter_mut() {
+            let offset: u64 = self.bits[self.pos..self.pos + width].load_be();
+            if offset > 2 * bound {
+                return Err(WireError::CoefficientOutOfRange);
+            }
+            *coeff = IntMod::from(offset as i64 - bound as i64);
+            self.pos += width;
+        }
+        Ok(())
+    }
+
+    /// Number of whole bytes consumed so far, counting the trailing partial byte.
+    pub fn bytes_read(&self) -> usize {
+        self.pos.div_ceil(8)
+    }
+}
+
+///
+/// Serializes a batch of queries, e.g. the `Query` of [`RespireImpl`] or [`CuckooRespireImpl`].
+///
+/// [`RespireImpl`]: crate::pir::respire::RespireImpl
+/// [`CuckooRespireImpl`]: crate::pir::cuckoo_respire::CuckooRespireImpl
+///
+pub fn encode_query<R: Respire>(qs: &[R::QueryOne]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_SIZE + qs.len() * R::params_query_one_size());
+    Header::new(MessageKind::Query, R::params().fingerprint(), qs.len()).write_to(&mut out);
+    for q in qs {
+        out.extend_from_slice(&R::query_one_to_bytes(q));
+    }
+    out
+}
+
+pub fn decode_query<R: Respire>(bytes: &[u8]) -> Result<Vec<R::QueryOne>, WireError> {
+    let header = Header::read_from(bytes)?;
+    header.expect(MessageKind::Query, R::params().fingerprint())?;
+    let one_size = R::params_query_one_size();
+    let count = header.count as usize;
+    let body = &bytes[HEADER_SIZE..];
+    let Some(body_size) = count.checked_mul(one_size) else {
+        return Err(WireError::Truncated);
+    };
+    match body.len().cmp(&body_size) {
+        std::cmp::Ordering::Less => return Err(WireError::Truncated),
+        std::cmp::Ordering::Greater => return Err(WireError::TrailingBytes),
+        std::cmp::Ordering::Equal => {}
+    }
+    body.chunks_exact(one_size)
+        .map(|chunk| R::query_one_from_bytes(chunk))
+        .collect()
+}
+
+///
+/// Serializes a batch of compressed responses. Each element is prefixed with its (possibly
+/// truncated) vector length.
+///
+pub fn encode_response<R: Respire>(rs: &[R::AnswerOneCompressed]) -> Vec<u8> {
+    // The vector lengths are at most N_VEC, which must fit in the length byte
+    const { assert!(R::N_VEC <= u8::MAX as usize) };
+    let mut out = Vec::with_capacity(
+        HEADER_SIZE
+            + rs.iter()
+                .map(|r| 1 + R::params_response_one_size(R::response_one_len(r)))
+                .sum::<usize>(),
+    );
+    Header::new(MessageKind::Response, R::params().fingerprint(), rs.len()).write_to(&mut out);
+    for r in rs {
+        out.push(u8::try_from(R::response_one_len(r)).unwrap());
+        out.extend_from_slice(&R::response_one_to_bytes(r));
+    }
+    out
+}
+
+pub fn decode_response<R: Respire>(bytes: &[u8]) -> Result<Vec<R::AnswerOneCompressed>, WireError> {
+    let header = Header::read_from(bytes)?;
+    header.expect(MessageKind::Response, R::params().fingerprint())?;
+    let mut rest = &bytes[HEADER_SIZE..];
+    // The count is untrusted, so only reserve as many elements as the body can hold
+    let min_one_size = 1 + R::params_response_one_size(1);
+    let mut result = Vec::with_capacity((header.count as usize).min(rest.len() / min_one_size));
+    for _ in 0..header.count {
+        let (&len, tail) = rest.split_first().ok_or(WireError::Truncated)?;
+        let len = len as usize;
+        if len == 0 || len > R::N_VEC {
+            return Err(WireError::InvalidLength(len));
+        }
+        let one_size = R::params_response_one_size(len);
+        if tail.len() < one_size {
+            return Err(WireError::Truncated);
+        }
+        result.push(R::response_one_from_bytes(&tail[..one_size], len)?);
+        rest = &tail[one_size..];
+    }
+    if !rest.is_empty() {
+        return Err(WireError::TrailingBytes);
+    }
+    Ok(result)
+}
+
+///
+/// Serializes the seeded public params, i.e. only the PRG seeds and the non-random components.
+///
+pub fn encode_public_params<R: Respire>(pp: &R::PublicParamsSeeded) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_SIZE + R::params_public_param_size());
+    Header::new(MessageKind::PublicParams, R::params().fingerprint(), 1).write_to(&mut out);
+    out.extend_from_slice(&R::public_params_to_bytes(pp));
+    out
+}
+
+pub fn decode_public_params<R: Respire>(bytes: &[u8]) -> Result<R::PublicParamsSeeded, WireError> {
+    let header = Header::read_from(bytes)?;
+    header.expect(MessageKind::PublicParams, R::params().fingerprint())?;
+    if header.count != 1 {
+        return Err(WireError::InvalidLength(header.count as usize));
+    }
+    let body = &bytes[HEADER_SIZE..];
+    match body.len().cmp(&R::params_public_param_size()) {
+        std::cmp::Ordering::Less => Err(WireError::Truncated),
+        std::cmp::Ordering::Greater => Err(WireError::TrailingBytes),
+        std::cmp::Ordering::Equal => R::public_params_from_bytes(body),
+    }
+}
+
+///
+/// Serializes the client's secret query key, e.g. to store it across restarts.
+///
+pub fn encode_query_key<R: Respire>(qk: &R::QueryKey) -> Zeroizing<Vec<u8>> {
+    let mut out = Zeroizing::new(Vec::with_capacity(HEADER_SIZE + R::params_query_key_size()));
+    Header::new(MessageKind::QueryKey, R::params().fingerprint(), 1).write_to(&mut out);
+    out.extend_from_slice(&R::query_key_to_bytes(qk));
+    out
+}
+
+pub fn decode_query_key<R: Respire>(bytes: &[u8]) -> Result<R::QueryKey, WireError> {
+    let header = Header::read_from(bytes)?;
+    header.expect(MessageKind::QueryKey, R::params().fingerprint())?;
+    if header.count != 1 {
+        return Err(WireError::InvalidLength(header.count as usize));
+    }
+    let body = &bytes[HEADER_SIZE..];
+    match body.len().cmp(&R::params_query_key_size()) {
+        std::cmp::Ordering::Less => Err(WireError::Truncated),
+        std::cmp::Ordering::Greater => Err(WireError::TrailingBytes),
+        std::cmp::Ordering::Equal => R::query_key_from_bytes(body),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::int_mod_cyclo::IntModCyclo;
+    use crate::math::matrix::Matrix;
+    use crate::math::rand_sampled::RandUniformSampled;
+    use crate::pir::respire::RespireQueryKey;
+    use crate::pir::respire_harness::{RespireTest, RESPIRE_TEST_PARAMS};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn test_query_round_trip() {
+        let mut rng = ChaCha20Rng::from_entropy();
+        let qk = RespireQueryKey {
+            s_encode: RespireTest::encode_setup(&mut rng),
+            s_vec: RespireTest::encode_vec_setup(&mut rng),
+            s_small: Matrix::zero(),
+        };
+        let qs = vec![
+            RespireTest::query_one(&qk, 12345, None).unwrap(),
+            RespireTest::query_one(&qk, 0, None).unwrap(),
+        ];
+        let bytes = encode_query::<RespireTest>(&qs);
+        assert_eq!(
+            bytes.len(),
+            HEADER_SIZE + 2 * RespireTest::params_query_one_size()
+        );
+        assert_eq!(decode_query::<RespireTest>(&bytes).unwrap(), qs);
+
+        assert_eq!(
+            decode_query::<RespireTest>(&bytes[..bytes.len() - 1]),
+            Err(WireError::Truncated)
+        );
+        let mut wrong_params = bytes.clone();
+        wrong_params[2] ^= 1;
+        assert!(matches!(
+            decode_query::<RespireTest>(&wrong_params),
+            Err(WireError::ParamsMismatch { .. })
+        ));
+        let mut huge_count = bytes.clone();
+        huge_count[10..14].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(
+            decode_query::<RespireTest>(&huge_count),
+            Err(WireError::Truncated)
+        );
+    }
+
+    #[test]
+    fn test_query_key_round_trip() {
+        let (qk, _) = RespireTest::setup_seeded(None);
+        let bytes = encode_query_key::<RespireTest>(&qk);
+        assert_eq!(
+            bytes.len(),
+            HEADER_SIZE + RespireTest::params_query_key_size()
+        );
+        assert!(bytes.len() < HEADER_SIZE + RespireTest::params_query_one_size());
+        assert_eq!(decode_query_key::<RespireTest>(&bytes).unwrap(), qk);
+
+        assert_eq!(
+            decode_query_key::<RespireTest>(&bytes[..bytes.len() - 1]).err(),
+            Some(WireError::Truncated)
+        );
+        let mut out_of_range = bytes.to_vec();
+        out_of_range[HEADER_SIZE] = 0xff;
+        assert_eq!(
+            decode_query_key::<RespireTest>(&out_of_range).err(),
+            Some(WireError::CoefficientOutOfRange)
+        );
+    }
+
+    #[test]
+    fn test_response_round_trip() {
+        let mut rng = ChaCha20Rng::from_entropy();
+        let r: <RespireTest as Respire>::AnswerOneCompressed = (
+            IntModCyclo::rand_uniform(&mut rng),
+            (0..RESPIRE_TEST_PARAMS.N_VEC)
+                .map(|_| IntModCyclo::rand_uniform(&mut rng))
+                .collect(),
+        );
+        let bytes = encode_response::<RespireTest>(std::slice::from_ref(&r));
+        assert_eq!(
+            bytes.len(),
+            HEADER_SIZE + 1 + RespireTest::params_response_one_size(RESPIRE_TEST_PARAMS.N_VEC)
+        );
+        assert_eq!(decode_response::<RespireTest>(&bytes).unwrap(), vec![r]);
+
+        assert!(matches!(
+            decode_query::<RespireTest>(&bytes),
+            Err(WireError::UnexpectedKind(2))
+        ));
+
+        let mut huge_count = bytes[..HEADER_SIZE].to_vec();
+        huge_count[10..14].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(
+            decode_response::<RespireTest>(&huge_count).err(),
+            Some(WireError::Truncated)
+        );
+    }
+
+    #[test]
+    fn test_public_params_round_trip() {
+        let (_, pp_seeded) = RespireTest::setup_seeded(None);
+        let bytes = encode_public_params::<RespireTest>(&pp_seeded);
+        assert_eq!(
+            bytes.len(),
+            HEADER_SIZE + RespireTest::params_public_param_size()
+        );
+        let decoded = decode_public_params::<RespireTest>(&bytes).unwrap();
+        assert_eq!(
+            RespireTest::public_params_expand(&decoded),
+            RespireTest::public_params_expand(&pp_seeded)
+        );
+    }
+}